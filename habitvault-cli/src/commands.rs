@@ -0,0 +1,334 @@
+use crate::unlock::{Vault, forget_session, read_backup_password};
+use crate::{Cli, Command, ExportFormat, HabitOptions, Result, parse, status};
+use chrono::{Local, NaiveDate};
+use habitvault_core::clock::Clock;
+use habitvault_core::habit::{Habit, HabitData, Quantity, Schedule};
+use habitvault_core::{csv_io, migrations};
+use std::fs::OpenOptions;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Largest daily target or logged amount, as in the app's amount forms.
+const MAX_AMOUNT: f64 = 100000.0;
+
+/// Runs one command. Arguments are checked before the vault is unlocked, so
+/// a typo doesn't cost a password prompt or count as a failed attempt.
+pub fn run(cli: Cli) -> Result<()> {
+    let today = Clock::System.today();
+    
+    match cli.command {
+        Command::List { date } => {
+            let date = parse::date(&date, today)?;
+            let vault = Vault::open(&cli.vault)?;
+            list(&vault.data, date);
+        }
+        Command::Mark { habit, date, value, note, mood } => {
+            let date = parse::date(&date, today)?;
+            let mut vault = Vault::open_to_write(&cli.vault)?;
+            let habit_id = find_habit(&vault.data, &habit)?.id.clone();
+            if mark(&mut vault.data, &habit_id, date, value, note, mood)? {
+                vault.save()?;
+            }
+        }
+        Command::Unmark { habit, date } => {
+            let date = parse::date(&date, today)?;
+            let mut vault = Vault::open_to_write(&cli.vault)?;
+            let habit = find_habit(&vault.data, &habit)?.clone();
+            // A record with a zero value only holds a note
+            if vault.data.completion_on_date(&habit.id, date).is_none_or(|c| c.value == Some(0.0)) {
+                println!("{} isn't done on {}", habit.name, date);
+                return Ok(());
+            }
+            vault.data.unmark_completed(&habit.id, date);
+            vault.save()?;
+            println!("Unmarked {} on {}", habit.name, date);
+        }
+        Command::Streaks { habit } => {
+            let vault = Vault::open(&cli.vault)?;
+            let habits = match habit {
+                Some(query) => vec![find_habit(&vault.data, &query)?],
+                None => vault.data.habits.iter().collect(),
+            };
+            streaks(&habits);
+        }
+        Command::Add { name, options } => {
+            let schedule = options.schedule.as_deref().map(|s| parse::schedule(s, today)).transpose()?;
+            let mut vault = Vault::open_to_write(&cli.vault)?;
+            add(&mut vault.data, &name, options, schedule)?;
+            vault.save()?;
+            println!("Added {}", name.trim());
+        }
+        Command::Edit { habit, name, options, no_quantity } => {
+            let schedule = options.schedule.as_deref().map(|s| parse::schedule(s, today)).transpose()?;
+            let mut vault = Vault::open_to_write(&cli.vault)?;
+            let habit = find_habit(&vault.data, &habit)?.clone();
+            edit(&mut vault.data, &habit, name, options, schedule, no_quantity)?;
+            vault.save()?;
+            println!("Updated {}", vault.data.get_habit_by_id(&habit.id).map_or(&habit.name, |h| &h.name));
+        }
+        Command::Remove { habit, yes } => {
+            let mut vault = Vault::open_to_write(&cli.vault)?;
+            let habit = find_habit(&vault.data, &habit)?.clone();
+            if !yes && !confirm(&format!("Delete {} and everything recorded for it?", habit.name))? {
+                return Ok(());
+            }
+            vault.data.remove_habit(&habit.id);
+            vault.save()?;
+            println!("Removed {}", habit.name);
+        }
+        Command::Export { format, output } => {
+            if matches!(format, ExportFormat::Backup) && output.is_none() {
+                return Err("Give the backup file with --output".into());
+            }
+            let vault = Vault::open(&cli.vault)?;
+            export(&vault, format, output.as_deref())?;
+        }
+        Command::Unlock { duration } => {
+            let duration = parse::duration(&duration)?;
+            let vault = Vault::open_with_credentials(&cli.vault)?;
+            vault.cache(duration)?;
+            println!("Unlocked until {}", (Local::now() + duration).format("%Y-%m-%d %H:%M"));
+        }
+        Command::Lock => forget_session(&cli.vault)?,
+        Command::Status { format } => {
+            let vault = Vault::open_cached(&cli.vault)?;
+            println!("{}", status::render(vault.as_ref().map(|vault| &vault.data), format));
+        }
+    }
+    
+    Ok(())
+}
+
+/// A habit by id, or by name ignoring case.
+fn find_habit<'a>(data: &'a HabitData, query: &str) -> Result<&'a Habit> {
+    if let Some(habit) = data.get_habit_by_id(query) {
+        return Ok(habit);
+    }
+    
+    let name = query.trim().to_lowercase();
+    let matches: Vec<&Habit> = data.habits.iter()
+        .filter(|habit| habit.name.trim().to_lowercase() == name)
+        .collect();
+    match matches.as_slice() {
+        [habit] => Ok(habit),
+        [] => Err(format!("No habit named \"{}\". See `habitvault list`", query).into()),
+        several => {
+            let ids: Vec<&str> = several.iter().map(|habit| habit.id.as_str()).collect();
+            Err(format!("Several habits are named \"{}\", use one of their ids: {}", query, ids.join(", ")).into())
+        }
+    }
+}
+
+/// One line per habit: whether it's done on `date` ([x]), still due ([ ]) or
+/// not scheduled ([-]), then its progress and streak.
+fn list(data: &HabitData, date: NaiveDate) {
+    if data.habits.is_empty() {
+        println!("No habits yet. Add one with `habitvault add`");
+        return;
+    }
+    
+    let width = data.habits.iter().map(|habit| habit.name.chars().count()).max().unwrap_or(0);
+    for habit in &data.habits {
+        let status = if data.is_completed_on_date(&habit.id, date) {
+            "[x]"
+        } else if habit.schedule.is_scheduled(date) {
+            "[ ]"
+        } else {
+            "[-]"
+        };
+        println!("{} {:<width$}  {}  ({})", status, habit.name, status_text(habit, data, date), habit.id, width = width);
+    }
+}
+
+/// The amount on `date`, the goal or schedule and the streak, like the
+/// subtitle of a habit in the app.
+fn status_text(habit: &Habit, data: &HabitData, date: NaiveDate) -> String {
+    let mut parts = Vec::new();
+    if let Some(quantity) = &habit.quantity {
+        parts.push(quantity.describe_progress(data.value_on_date(&habit.id, date)));
+    }
+    if habit.is_weekly_goal() {
+        parts.push(format!("{}/{} that week", data.completions_in_week(&habit.id, date), habit.target_days_per_week));
+    } else if let Schedule::TimesPerMonth { times } = habit.schedule {
+        parts.push(format!("{}/{} that month", data.completions_in_month(&habit.id, date), times));
+    } else {
+        parts.push(habit.schedule.describe());
+    }
+    parts.push(format!("{} {}", habit.streak, habit.streak_unit()));
+    parts.join(" · ")
+}
+
+fn streaks(habits: &[&Habit]) {
+    let width = habits.iter().map(|habit| habit.name.chars().count()).max().unwrap_or(0);
+    for habit in habits {
+        println!("{:<width$}  {} {} (longest {})", habit.name, habit.streak, habit.streak_unit(), habit.longest_streak, width = width);
+    }
+}
+
+/// Marks the habit done on `date`, or logs `value` for a quantitative habit,
+/// then updates the day's note and mood. Returns whether anything changed.
+fn mark(data: &mut HabitData, habit_id: &str, date: NaiveDate, value: Option<f64>, note: Option<String>, mood: Option<u8>) -> Result<bool> {
+    let Some(habit) = data.get_habit_by_id(habit_id).cloned() else {
+        return Ok(false);
+    };
+    let mut changed = true;
+    
+    match (value, &habit.quantity) {
+        (Some(value), Some(quantity)) => {
+            if !(0.0..=MAX_AMOUNT).contains(&value) {
+                return Err(format!("The amount must be between 0 and {}", MAX_AMOUNT).into());
+            }
+            data.log_value(habit_id, date, value);
+            println!("Logged {} for {} on {}", quantity.describe_progress(value), habit.name, date);
+        }
+        (Some(_), None) => return Err(format!("{} doesn't track an amount", habit.name).into()),
+        (None, _) if data.is_completed_on_date(habit_id, date) => {
+            println!("{} is already done on {}", habit.name, date);
+            changed = false;
+        }
+        (None, _) => {
+            data.mark_completed(habit_id, date, None);
+            println!("Marked {} as done on {}", habit.name, date);
+        }
+    }
+    
+    if note.is_some() || mood.is_some() {
+        let existing = data.completion_on_date(habit_id, date);
+        let notes = match note {
+            Some(note) => Some(note).filter(|note| !note.trim().is_empty()),
+            None => existing.and_then(|c| c.notes.clone()),
+        };
+        let mood = mood.or(existing.and_then(|c| c.mood));
+        data.set_day_note(habit_id, date, notes, mood);
+        changed = true;
+    }
+    
+    Ok(changed)
+}
+
+fn add(data: &mut HabitData, name: &str, options: HabitOptions, schedule: Option<Schedule>) -> Result<()> {
+    let name = checked_name(data, name, None)?;
+    let quantity = match (options.unit, options.target) {
+        (None, None) => None,
+        (Some(unit), Some(target)) => Some(quantity(unit, target)?),
+        _ => return Err("Give both --unit and --target to track an amount".into()),
+    };
+    
+    data.add_habit(Habit {
+        id: data.new_habit_id(),
+        name,
+        description: options.description.unwrap_or_default(),
+        created_at: data.clock().now(),
+        target_days_per_week: options.days_per_week.unwrap_or(7),
+        schedule: schedule.unwrap_or_default(),
+        quantity,
+        streak: 0,
+        longest_streak: 0,
+    });
+    Ok(())
+}
+
+fn edit(data: &mut HabitData, habit: &Habit, name: Option<String>, options: HabitOptions, schedule: Option<Schedule>, no_quantity: bool) -> Result<()> {
+    let name = match name {
+        Some(name) => checked_name(data, &name, Some(&habit.id))?,
+        None => habit.name.clone(),
+    };
+    let quantity = match (options.unit, options.target) {
+        _ if no_quantity => None,
+        (None, None) => habit.quantity.clone(),
+        (unit, target) => {
+            let existing = habit.quantity.as_ref();
+            let unit = unit.or(existing.map(|q| q.unit.clone()))
+                .ok_or("Give --unit as well to start tracking an amount")?;
+            let target = target.or(existing.map(|q| q.daily_target))
+                .ok_or("Give --target as well to start tracking an amount")?;
+            Some(quantity(unit, target)?)
+        }
+    };
+    
+    data.update_habit(
+        &habit.id,
+        &name,
+        options.description.as_deref().unwrap_or(&habit.description),
+        options.days_per_week.unwrap_or(habit.target_days_per_week),
+        schedule.unwrap_or_else(|| habit.schedule.clone()),
+        quantity,
+    );
+    Ok(())
+}
+
+/// A trimmed, non-empty name no other habit has, since habits are looked up
+/// by name.
+fn checked_name(data: &HabitData, name: &str, habit_id: Option<&str>) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("The habit name can't be empty".into());
+    }
+    let taken = data.habits.iter()
+        .any(|habit| Some(habit.id.as_str()) != habit_id && habit.name.trim().to_lowercase() == name.to_lowercase());
+    if taken {
+        return Err(format!("There is already a habit named \"{}\"", name).into());
+    }
+    Ok(name.to_string())
+}
+
+fn quantity(unit: String, daily_target: f64) -> Result<Quantity> {
+    let unit = unit.trim().to_string();
+    if unit.is_empty() {
+        return Err("The unit can't be empty".into());
+    }
+    if !(0.5..=MAX_AMOUNT).contains(&daily_target) {
+        return Err(format!("The daily target must be between 0.5 and {}", MAX_AMOUNT).into());
+    }
+    Ok(Quantity { unit, daily_target })
+}
+
+fn export(vault: &Vault, format: ExportFormat, output: Option<&Path>) -> Result<()> {
+    match format {
+        ExportFormat::Json | ExportFormat::HabitsCsv | ExportFormat::CompletionsCsv => {
+            let bytes = match format {
+                ExportFormat::HabitsCsv => Zeroizing::new(csv_io::habits_csv(&vault.data)?),
+                ExportFormat::CompletionsCsv => Zeroizing::new(csv_io::completions_csv(&vault.data)?),
+                _ => {
+                    let mut json = migrations::to_versioned_json(&vault.data)?;
+                    json.push(b'\n');
+                    json
+                }
+            };
+            match output {
+                Some(path) => {
+                    let mut options = OpenOptions::new();
+                    options.write(true).create(true).truncate(true);
+                    // Readable by the owner only, as the data is unencrypted
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::OpenOptionsExt;
+                        options.mode(0o600);
+                    }
+                    let mut file = options.open(path)?;
+                    file.write_all(&bytes)?;
+                    eprintln!("Wrote unencrypted habit data to {}", path.display());
+                }
+                None => std::io::stdout().lock().write_all(&bytes)?,
+            }
+        }
+        ExportFormat::Backup => {
+            let path = output.ok_or("Give the backup file with --output")?;
+            let password = read_backup_password()?;
+            vault.storage.export_backup(&vault.data, &password, path)?;
+            eprintln!("Backup written to {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
\ No newline at end of file
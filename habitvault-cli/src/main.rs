@@ -0,0 +1,186 @@
+//! `habitvault`, a command-line front end for the same vault the GTK app
+//! uses.
+
+mod commands;
+mod parse;
+mod status;
+mod unlock;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[command(name = "habitvault", version, about = "Track habits in a HabitVault vault from the terminal")]
+#[command(after_help = "The password is read from --password-fd if given, otherwise from the \
+HABITVAULT_PASSWORD environment variable, otherwise it is prompted for. After \
+`habitvault unlock` no password is needed until the session expires.\n\
+Dates are YYYY-MM-DD, \"today\" or \"yesterday\".")]
+struct Cli {
+    #[command(flatten)]
+    vault: VaultOptions,
+    
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Where the vault is and how to unlock it.
+#[derive(Args)]
+struct VaultOptions {
+    /// Directory holding the vault, instead of the app's data directory
+    #[arg(long, global = true, env = "HABITVAULT_DIR")]
+    data_dir: Option<PathBuf>,
+    
+    /// Keyfile, for vaults that need one. Defaults to the one the app last used
+    #[arg(long, global = true)]
+    keyfile: Option<PathBuf>,
+    
+    /// Read the password from this open file descriptor, e.g. `--password-fd 3 3<secret` (unix only)
+    #[arg(long, global = true, value_name = "FD")]
+    password_fd: Option<u32>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List habits and whether they are done on a day
+    List {
+        #[arg(long, default_value = "today")]
+        date: String,
+    },
+    /// Mark a habit as done, or log an amount for it
+    Mark {
+        /// Habit name or id
+        habit: String,
+        #[arg(long, default_value = "today")]
+        date: String,
+        /// Amount done, for habits that track a quantity. Replaces the day's amount
+        #[arg(long)]
+        value: Option<f64>,
+        /// Note for the day. An empty note removes it
+        #[arg(long)]
+        note: Option<String>,
+        /// Mood for the day, from 1 to 5
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        mood: Option<u8>,
+    },
+    /// Remove what was recorded for a habit on a day
+    Unmark {
+        /// Habit name or id
+        habit: String,
+        #[arg(long, default_value = "today")]
+        date: String,
+    },
+    /// Show current and longest streaks
+    Streaks {
+        /// Habit name or id; all habits if omitted
+        habit: Option<String>,
+    },
+    /// Add a habit
+    Add {
+        name: String,
+        #[command(flatten)]
+        options: HabitOptions,
+    },
+    /// Change a habit's name, schedule or target
+    Edit {
+        /// Habit name or id
+        habit: String,
+        /// New name
+        #[arg(long)]
+        name: Option<String>,
+        #[command(flatten)]
+        options: HabitOptions,
+        /// Stop tracking an amount and go back to done / not done
+        #[arg(long, conflicts_with_all = ["unit", "target"])]
+        no_quantity: bool,
+    },
+    /// Delete a habit and everything recorded for it
+    Remove {
+        /// Habit name or id
+        habit: String,
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Write all habit data to a file or standard output
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// File to write; standard output if omitted. Required for backups
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Keep the vault unlocked for a while, so commands and `status` don't ask for the password
+    Unlock {
+        /// How long, e.g. 45m, 8h or 2d
+        #[arg(long = "for", value_name = "DURATION", default_value = "12h")]
+        duration: String,
+    },
+    /// Forget the session kept by `unlock`
+    Lock,
+    /// Print today's habits for a status bar, using the session kept by `unlock`
+    Status {
+        #[arg(long, value_enum, default_value_t = StatusFormat::Text)]
+        format: StatusFormat,
+    },
+}
+
+/// Settings shared by `add` and `edit`. Anything left out keeps its current
+/// value, or the app's default for a new habit.
+#[derive(Args)]
+struct HabitOptions {
+    #[arg(long)]
+    description: Option<String>,
+    
+    /// "daily", "weekdays:mon,wed,fri", "every:N", "monthly:N" or "range:START:END"
+    #[arg(long)]
+    schedule: Option<String>,
+    
+    /// For daily habits, how many days a week count as meeting the goal
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=7))]
+    days_per_week: Option<u8>,
+    
+    /// Track an amount in this unit, e.g. "glasses"
+    #[arg(long)]
+    unit: Option<String>,
+    
+    /// Amount to reach each day
+    #[arg(long)]
+    target: Option<f64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// Unencrypted JSON in the vault's own schema
+    Json,
+    /// Unencrypted CSV with one row per habit
+    HabitsCsv,
+    /// Unencrypted CSV with one row per completed day
+    CompletionsCsv,
+    /// An encrypted backup the app can import, under a separate password
+    Backup,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatusFormat {
+    /// One line, e.g. "1/3 · ✓ Read (12) · ○ Gym (3) · ○ Water 3/8 (5)", for polybar or i3status
+    Text,
+    /// Everything shown in the other formats, for scripts
+    Json,
+    /// A waybar custom module object with text, tooltip, class and percentage
+    Waybar,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    
+    match commands::run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("habitvault: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
\ No newline at end of file
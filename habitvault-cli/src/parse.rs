@@ -0,0 +1,126 @@
+//! Dates, schedules and durations as typed on the command line.
+
+use chrono::{Duration, NaiveDate, Weekday};
+use habitvault_core::habit::Schedule;
+
+/// A day as "today", "yesterday" or YYYY-MM-DD.
+pub fn date(input: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    match input.trim().to_lowercase().as_str() {
+        "today" => Ok(today),
+        "yesterday" => Ok(today - Duration::days(1)),
+        other => NaiveDate::parse_from_str(other, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid date \"{}\". Use YYYY-MM-DD, \"today\" or \"yesterday\"", input)),
+    }
+}
+
+/// A schedule in the form shown by `--help`. Intervals start counting from
+/// `today`, as they do when picked in the app.
+pub fn schedule(input: &str, today: NaiveDate) -> Result<Schedule, String> {
+    let input = input.trim().to_lowercase();
+    let (kind, rest) = input.split_once(':').unwrap_or((&input, ""));
+    
+    match kind {
+        "daily" if rest.is_empty() => Ok(Schedule::Daily),
+        "weekdays" => {
+            let mut days = Vec::new();
+            for name in rest.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                let day: Weekday = name.parse().map_err(|_| format!("Unknown weekday \"{}\"", name))?;
+                if !days.contains(&day) {
+                    days.push(day);
+                }
+            }
+            if days.is_empty() {
+                return Err("List at least one weekday, e.g. weekdays:mon,wed,fri".to_string());
+            }
+            days.sort_by_key(|day| day.num_days_from_monday());
+            Ok(Schedule::Weekdays { days })
+        }
+        "every" => match rest.parse::<u32>() {
+            Ok(interval) if (2..=365).contains(&interval) => Ok(Schedule::EveryNDays { interval, anchor: today }),
+            _ => Err("The interval must be between 2 and 365 days, e.g. every:3".to_string()),
+        },
+        "monthly" => match rest.parse::<u32>() {
+            Ok(times) if (1..=31).contains(&times) => Ok(Schedule::TimesPerMonth { times }),
+            _ => Err("The number of times must be between 1 and 31, e.g. monthly:10".to_string()),
+        },
+        "range" => {
+            let (start, end) = rest.split_once(':')
+                .ok_or_else(|| "Give a start and end date, e.g. range:2026-01-01:2026-03-31".to_string())?;
+            let start = date(start, today)?;
+            let end = date(end, today)?;
+            if start > end {
+                return Err("The end date must not be before the start date".to_string());
+            }
+            Ok(Schedule::DateRange { start, end })
+        }
+        _ => Err(format!("Unknown schedule \"{}\". Use daily, weekdays:mon,wed, every:N, monthly:N or range:START:END", input)),
+    }
+}
+
+/// A length of time such as "45m", "8h" or "2d". A bare number is minutes.
+pub fn duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim().to_lowercase();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let invalid = || format!("Invalid duration \"{}\". Use minutes, hours or days, e.g. 45m, 8h or 2d", input);
+    
+    let number: i64 = number.parse().map_err(|_| invalid())?;
+    let duration = match unit.trim() {
+        "" | "m" | "min" => Duration::minutes(number),
+        "h" => Duration::hours(number),
+        "d" => Duration::days(number),
+        _ => return Err(invalid()),
+    };
+    if number == 0 || number > 1_000_000 {
+        return Err(invalid());
+    }
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+    
+    #[test]
+    fn dates() {
+        let today = day(2026, 3, 1);
+        assert_eq!(date("today", today), Ok(today));
+        assert_eq!(date("Yesterday", today), Ok(day(2026, 2, 28)));
+        assert_eq!(date("2025-12-24", today), Ok(day(2025, 12, 24)));
+        assert!(date("24/12/2025", today).is_err());
+    }
+    
+    #[test]
+    fn schedules() {
+        let today = day(2026, 3, 1);
+        assert_eq!(schedule("daily", today), Ok(Schedule::Daily));
+        assert_eq!(
+            schedule("weekdays:fri,Mon,friday", today),
+            Ok(Schedule::Weekdays { days: vec![Weekday::Mon, Weekday::Fri] }),
+        );
+        assert_eq!(schedule("every:3", today), Ok(Schedule::EveryNDays { interval: 3, anchor: today }));
+        assert_eq!(schedule("monthly:10", today), Ok(Schedule::TimesPerMonth { times: 10 }));
+        assert_eq!(
+            schedule("range:2026-03-01:2026-03-31", today),
+            Ok(Schedule::DateRange { start: today, end: day(2026, 3, 31) }),
+        );
+        
+        for invalid in ["weekdays:", "weekdays:someday", "every:1", "monthly:0", "range:2026-03-31:2026-03-01", "hourly"] {
+            assert!(schedule(invalid, today).is_err(), "{} should be rejected", invalid);
+        }
+    }
+    
+    #[test]
+    fn durations() {
+        assert_eq!(duration("45"), Ok(Duration::minutes(45)));
+        assert_eq!(duration("8h"), Ok(Duration::hours(8)));
+        assert_eq!(duration(" 2D "), Ok(Duration::days(2)));
+        for invalid in ["", "0h", "h", "1.5h", "3w", "-2h"] {
+            assert!(duration(invalid).is_err(), "{} should be rejected", invalid);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,244 @@
+//! Today's habits in the formats status bars read.
+
+use crate::StatusFormat;
+use chrono::NaiveDate;
+use habitvault_core::habit::{Habit, HabitData, Quantity, Schedule};
+use serde_json::{Value, json};
+
+/// How one of today's habits stands.
+struct HabitStatus<'a> {
+    habit: &'a Habit,
+    done: bool,
+    /// Today's amount against the target, for quantitative habits.
+    progress: Option<(f64, f64)>,
+}
+
+impl HabitStatus<'_> {
+    fn progress_text(&self) -> Option<String> {
+        self.progress.map(|(value, target)| format!("{}/{}", Quantity::format_amount(value), Quantity::format_amount(target)))
+    }
+}
+
+/// Habits done today, and those still needed today: due today, or part of a
+/// weekly or monthly goal that hasn't been met yet.
+fn todays_habits(data: &HabitData, today: NaiveDate) -> Vec<HabitStatus<'_>> {
+    data.habits.iter()
+        .filter_map(|habit| {
+            let done = data.is_completed_on_date(&habit.id, today);
+            let needed = if habit.is_weekly_goal() {
+                data.completions_in_week(&habit.id, today) < habit.target_days_per_week as u32
+            } else if let Schedule::TimesPerMonth { times } = habit.schedule {
+                data.completions_in_month(&habit.id, today) < times
+            } else {
+                habit.is_due_on(today)
+            };
+            (done || needed).then(|| HabitStatus {
+                habit,
+                done,
+                progress: habit.quantity.as_ref()
+                    .map(|quantity| (data.value_on_date(&habit.id, today), quantity.daily_target)),
+            })
+        })
+        .collect()
+}
+
+/// The status of the vault, `None` meaning it is locked.
+pub fn render(data: Option<&HabitData>, format: StatusFormat) -> String {
+    let Some(data) = data else {
+        return match format {
+            StatusFormat::Text => "locked".to_string(),
+            StatusFormat::Json => json!({ "locked": true }).to_string(),
+            StatusFormat::Waybar => json!({
+                "text": "locked",
+                "tooltip": "HabitVault is locked. Run habitvault unlock",
+                "class": "locked",
+            }).to_string(),
+        };
+    };
+    
+    let today = data.today();
+    let habits = todays_habits(data, today);
+    let done = habits.iter().filter(|status| status.done).count();
+    
+    match format {
+        StatusFormat::Text => text(&habits, done),
+        StatusFormat::Json => json(&habits, done, today).to_string(),
+        StatusFormat::Waybar => waybar(&habits, done).to_string(),
+    }
+}
+
+fn text(habits: &[HabitStatus], done: usize) -> String {
+    if habits.is_empty() {
+        return "No habits today".to_string();
+    }
+    
+    let mut parts = vec![format!("{}/{}", done, habits.len())];
+    for status in habits {
+        let mark = if status.done { "✓" } else { "○" };
+        let progress = match status.progress_text() {
+            Some(progress) if !status.done => format!(" {}", progress),
+            _ => String::new(),
+        };
+        parts.push(format!("{} {}{} ({})", mark, status.habit.name, progress, status.habit.streak));
+    }
+    parts.join(" · ")
+}
+
+fn json(habits: &[HabitStatus], done: usize, today: NaiveDate) -> Value {
+    let pending: Vec<&str> = habits.iter()
+        .filter(|status| !status.done)
+        .map(|status| status.habit.name.as_str())
+        .collect();
+    let habits: Vec<Value> = habits.iter()
+        .map(|status| json!({
+            "id": status.habit.id,
+            "name": status.habit.name,
+            "done": status.done,
+            "value": status.progress.map(|(value, _)| value),
+            "target": status.progress.map(|(_, target)| target),
+            "streak": status.habit.streak,
+            "streak_unit": status.habit.streak_unit(),
+            "longest_streak": status.habit.longest_streak,
+        }))
+        .collect();
+    
+    json!({
+        "locked": false,
+        "date": today.to_string(),
+        "done": done,
+        "total": habits.len(),
+        "pending": pending,
+        "habits": habits,
+    })
+}
+
+/// A waybar custom module with `"return-type": "json"`. The tooltip is Pango
+/// markup, so names are escaped.
+fn waybar(habits: &[HabitStatus], done: usize) -> Value {
+    let tooltip: Vec<String> = habits.iter()
+        .map(|status| {
+            let mark = if status.done { "✓" } else { "○" };
+            let mut line = format!("{} {}", mark, escape_markup(&status.habit.name));
+            if let Some(progress) = status.progress_text() {
+                line.push_str(&format!(" · {}", progress));
+            }
+            line.push_str(&format!(" · {} {}", status.habit.streak, status.habit.streak_unit()));
+            line
+        })
+        .collect();
+    let class = if habits.is_empty() {
+        "empty"
+    } else if done == habits.len() {
+        "done"
+    } else {
+        "pending"
+    };
+    let percentage = if habits.is_empty() { 100 } else { done * 100 / habits.len() };
+    
+    json!({
+        "text": format!("{}/{}", done, habits.len()),
+        "tooltip": if tooltip.is_empty() { "No habits today".to_string() } else { tooltip.join("\n") },
+        "class": class,
+        "percentage": percentage,
+    })
+}
+
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone, Utc, Weekday};
+    use habitvault_core::clock::Clock;
+    
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, d).unwrap()
+    }
+    
+    fn habit(id: &str, name: &str, schedule: Schedule, quantity: Option<Quantity>) -> Habit {
+        Habit {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            created_at: Utc.from_utc_datetime(&day(1).and_hms_opt(12, 0, 0).unwrap()),
+            target_days_per_week: 7,
+            schedule,
+            quantity,
+            streak: 0,
+            longest_streak: 0,
+        }
+    }
+    
+    /// Wednesday the 11th: Read is done, Water and Tea are still open and
+    /// Gym, only on Mondays, isn't due.
+    fn data() -> HabitData {
+        let mut data = HabitData::with_clock(Clock::Fixed {
+            now: Utc.from_utc_datetime(&day(11).and_hms_opt(12, 0, 0).unwrap()),
+            offset: FixedOffset::east_opt(0).unwrap(),
+        });
+        data.add_habit(habit("read", "Read", Schedule::Daily, None));
+        data.add_habit(habit("water", "Water", Schedule::Daily, Some(Quantity { unit: "glasses".to_string(), daily_target: 8.0 })));
+        data.add_habit(habit("gym", "Gym", Schedule::Weekdays { days: vec![Weekday::Mon] }, None));
+        data.add_habit(habit("tea", "Tea & <biscuits>", Schedule::Daily, None));
+        for d in 9..=11 {
+            data.mark_completed("read", day(d), None);
+        }
+        data.log_value("water", day(11), 3.0);
+        data
+    }
+    
+    fn parse(output: String) -> Value {
+        serde_json::from_str(&output).unwrap()
+    }
+    
+    #[test]
+    fn text_status() {
+        assert_eq!(
+            render(Some(&data()), StatusFormat::Text),
+            "1/3 · ✓ Read (3) · ○ Water 3/8 (0) · ○ Tea & <biscuits> (0)",
+        );
+        assert_eq!(render(Some(&HabitData::new()), StatusFormat::Text), "No habits today");
+        assert_eq!(render(None, StatusFormat::Text), "locked");
+    }
+    
+    #[test]
+    fn json_status() {
+        let status = parse(render(Some(&data()), StatusFormat::Json));
+        assert_eq!(status["locked"], false);
+        assert_eq!(status["date"], "2026-03-11");
+        assert_eq!((status["done"].clone(), status["total"].clone()), (json!(1), json!(3)));
+        assert_eq!(status["pending"], json!(["Water", "Tea & <biscuits>"]));
+        assert_eq!(status["habits"][0]["streak"], 3);
+        assert_eq!(status["habits"][0]["streak_unit"], "day streak");
+        assert_eq!(status["habits"][0]["value"], Value::Null);
+        assert_eq!((status["habits"][1]["value"].clone(), status["habits"][1]["target"].clone()), (json!(3.0), json!(8.0)));
+        
+        assert_eq!(parse(render(None, StatusFormat::Json)), json!({ "locked": true }));
+    }
+    
+    #[test]
+    fn waybar_status() {
+        let status = parse(render(Some(&data()), StatusFormat::Waybar));
+        assert_eq!(status["text"], "1/3");
+        assert_eq!(status["class"], "pending");
+        assert_eq!(status["percentage"], 33);
+        assert_eq!(
+            status["tooltip"],
+            "✓ Read · 3 day streak\n○ Water · 3/8 · 0 day streak\n○ Tea &amp; &lt;biscuits&gt; · 0 day streak",
+        );
+        
+        let mut all_done = data();
+        all_done.mark_completed("tea", day(11), None);
+        all_done.log_value("water", day(11), 8.0);
+        let status = parse(render(Some(&all_done), StatusFormat::Waybar));
+        assert_eq!((status["text"].clone(), status["class"].clone()), (json!("3/3"), json!("done")));
+        
+        let status = parse(render(Some(&HabitData::new()), StatusFormat::Waybar));
+        assert_eq!((status["class"].clone(), status["percentage"].clone()), (json!("empty"), json!(100)));
+        
+        let status = parse(render(None, StatusFormat::Waybar));
+        assert_eq!((status["text"].clone(), status["class"].clone()), (json!("locked"), json!("locked")));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,204 @@
+use crate::{Result, VaultOptions};
+use chrono::Duration;
+use habitvault_core::storage::{VaultLock, read_keyfile};
+use habitvault_core::vault::KeySlotKind;
+use habitvault_core::{Credentials, HabitData, SecureStorage, Session, StorageError, encrypt_vault};
+use zeroize::Zeroizing;
+
+/// Environment variable checked for the password when no descriptor is given.
+const PASSWORD_ENV: &str = "HABITVAULT_PASSWORD";
+
+/// Environment variable checked for the password of an exported backup.
+const BACKUP_PASSWORD_ENV: &str = "HABITVAULT_BACKUP_PASSWORD";
+
+/// An unlocked vault and what is needed to write it back.
+pub struct Vault {
+    pub storage: SecureStorage,
+    pub data: HabitData,
+    session: Session,
+    /// Held from before the data is read until it is written back, by vaults
+    /// opened with `open_to_write`.
+    lock: Option<VaultLock>,
+}
+
+impl Vault {
+    /// Unlocks the existing vault with the cached session if there is one,
+    /// otherwise with the credentials from `options`.
+    pub fn open(options: &VaultOptions) -> Result<Self> {
+        let storage = existing_storage(options)?;
+        // A damaged file is left to the full unlock, which can recover it
+        if let Ok(Some((data, session))) = storage.unlock_cached() {
+            return Ok(Self { storage, data, session, lock: None });
+        }
+        Self::unlock(storage, options)
+    }
+    
+    /// Like `open`, for commands that change the data. Fails right away
+    /// while the app has the vault unlocked, as its next save would overwrite
+    /// the change.
+    pub fn open_to_write(options: &VaultOptions) -> Result<Self> {
+        let lock = lock_vault(&existing_storage(options)?)?;
+        let mut vault = Self::open(options)?;
+        vault.lock = Some(lock);
+        Ok(vault)
+    }
+    
+    /// Unlocks with the credentials from `options` even if a session is
+    /// cached. Takes the lock like `open_to_write`, as `cache` may have to
+    /// write the vault back.
+    pub fn open_with_credentials(options: &VaultOptions) -> Result<Self> {
+        let storage = existing_storage(options)?;
+        let lock = lock_vault(&storage)?;
+        let mut vault = Self::unlock(storage, options)?;
+        vault.lock = Some(lock);
+        Ok(vault)
+    }
+    
+    /// The vault if a session is cached for it, without asking for anything.
+    pub fn open_cached(options: &VaultOptions) -> Result<Option<Self>> {
+        let storage = existing_storage(options)?;
+        Ok(storage.unlock_cached()?.map(|(data, session)| Self { storage, data, session, lock: None }))
+    }
+    
+    fn unlock(storage: SecureStorage, options: &VaultOptions) -> Result<Self> {
+        // An unreadable header falls back to trying whatever was given
+        let method = storage.unlock_method();
+        let needs_keyfile = method.is_some_and(KeySlotKind::needs_keyfile);
+        let needs_password = method.is_none_or(KeySlotKind::needs_password);
+        
+        let keyfile_path = options.keyfile.clone()
+            .or_else(|| needs_keyfile.then(|| storage.load_settings().keyfile_path).flatten());
+        let keyfile = match keyfile_path {
+            Some(path) => Some(read_keyfile(&path)?),
+            None if needs_keyfile => return Err("This vault needs its keyfile. Pass it with --keyfile".into()),
+            None => None,
+        };
+        let password = if needs_password {
+            read_password(options.password_fd)?
+        } else {
+            Zeroizing::new(String::new())
+        };
+        
+        let credentials = Credentials::new(password, keyfile).map_err(StorageError::from)?;
+        let (data, session, recovered_from) = storage.unlock_with_recovery(&credentials)?;
+        if let Some(path) = recovered_from {
+            eprintln!("Warning: the vault file is damaged, using the previous version from {}", path.display());
+        }
+        
+        Ok(Self { storage, data, session, lock: None })
+    }
+    
+    /// Encrypts and writes the data back, keeping the previous version as a
+    /// generation like the app does. The vault must have been opened with
+    /// `open_to_write`.
+    pub fn save(&self) -> Result<()> {
+        if self.lock.is_none() {
+            return Err("The vault was opened without its lock, so it can't be written".into());
+        }
+        self.storage.write_vault(&encrypt_vault(&self.data, &self.session)?)?;
+        Ok(())
+    }
+    
+    /// Keeps this session for `duration`. Vaults from before data keys can't
+    /// be opened with it until they have been saved in the current format,
+    /// so the vault must have been opened with `open_with_credentials`.
+    pub fn cache(&self, duration: Duration) -> Result<()> {
+        self.storage.cache_session(&self.session, duration)?;
+        // Also the case when the unlock fell back to an earlier generation,
+        // which saving repairs
+        if !matches!(self.storage.unlock_cached(), Ok(Some(_))) {
+            self.save()?;
+            self.storage.cache_session(&self.session, duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes the session kept by `Vault::cache`, if any.
+pub fn forget_session(options: &VaultOptions) -> Result<()> {
+    existing_storage(options)?.forget_cached_session()?;
+    Ok(())
+}
+
+/// Takes the vault lock without waiting, see `SecureStorage::lock_vault`.
+fn lock_vault(storage: &SecureStorage) -> Result<VaultLock> {
+    storage.lock_vault(std::time::Duration::ZERO).map_err(|e| match e {
+        StorageError::VaultBusy => "The vault is unlocked in the HabitVault app or being changed by another command. Lock the app and try again".into(),
+        e => e.into(),
+    })
+}
+
+/// Storage for the vault in `options`. Unlike the app this never creates a
+/// vault, so a mistyped password can't leave an empty one behind.
+fn existing_storage(options: &VaultOptions) -> Result<SecureStorage> {
+    let storage = match &options.data_dir {
+        Some(dir) => SecureStorage::with_data_dir(dir)?,
+        None => SecureStorage::new()?,
+    };
+    if !storage.exists() {
+        return Err("No vault found. Set one up in the HabitVault app first".into());
+    }
+    Ok(storage)
+}
+
+/// The vault password from `fd`, the environment or a prompt on the terminal,
+/// in that order.
+fn read_password(fd: Option<u32>) -> Result<Zeroizing<String>> {
+    let password = match fd {
+        Some(fd) => first_line(read_fd(fd)?),
+        None => match std::env::var(PASSWORD_ENV) {
+            Ok(password) => Zeroizing::new(password),
+            Err(_) => Zeroizing::new(rpassword::prompt_password("Password: ").map_err(|e| {
+                format!("Can't ask for the password ({}). Set {} or use --password-fd", e, PASSWORD_ENV)
+            })?),
+        },
+    };
+    
+    if password.is_empty() {
+        return Err("No password given".into());
+    }
+    Ok(password)
+}
+
+/// Everything readable from the open file descriptor `fd`.
+#[cfg(unix)]
+fn read_fd(fd: u32) -> Result<Zeroizing<String>> {
+    use std::fs::File;
+    use std::io::Read;
+    
+    let mut file = File::open(format!("/dev/fd/{}", fd))
+        .map_err(|e| format!("Can't read the password from descriptor {}: {}", fd, e))?;
+    let mut contents = Zeroizing::new(String::new());
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: u32) -> Result<Zeroizing<String>> {
+    Err(format!("--password-fd isn't supported on this system. Set {} instead", PASSWORD_ENV).into())
+}
+
+/// The password for a new backup, from the environment or typed twice.
+pub fn read_backup_password() -> Result<Zeroizing<String>> {
+    if let Ok(password) = std::env::var(BACKUP_PASSWORD_ENV) {
+        return Ok(Zeroizing::new(password));
+    }
+    
+    let password = Zeroizing::new(rpassword::prompt_password("Backup password: ")?);
+    let confirm = Zeroizing::new(rpassword::prompt_password("Repeat backup password: ")?);
+    if password.is_empty() {
+        return Err("No backup password given".into());
+    }
+    if password != confirm {
+        return Err("The backup passwords don't match".into());
+    }
+    Ok(password)
+}
+
+/// Drops everything from the first line break on, in place so no copy of the
+/// password is left unwiped.
+fn first_line(mut text: Zeroizing<String>) -> Zeroizing<String> {
+    let end = text.find(['\n', '\r']).unwrap_or(text.len());
+    text.truncate(end);
+    text
+}
\ No newline at end of file
@@ -0,0 +1,45 @@
+//! The current time and the user's time zone.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, Offset, Utc};
+
+/// Source of the current time and the user's time zone. Every "today" in the
+/// app comes from here so that days roll over at local midnight rather than
+/// UTC midnight, and so tests can pin the date and zone.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Clock {
+    /// The system clock in the machine's local time zone.
+    #[default]
+    System,
+    /// A fixed instant seen from a fixed UTC offset.
+    Fixed { now: DateTime<Utc>, offset: FixedOffset },
+}
+
+impl Clock {
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Clock::System => Utc::now(),
+            Clock::Fixed { now, .. } => *now,
+        }
+    }
+    
+    /// The local UTC offset at the current instant.
+    pub fn offset(&self) -> FixedOffset {
+        match self {
+            Clock::System => Local::now().offset().fix(),
+            Clock::Fixed { offset, .. } => *offset,
+        }
+    }
+    
+    /// The user's local calendar date.
+    pub fn today(&self) -> NaiveDate {
+        self.local_date(self.now())
+    }
+    
+    /// The local calendar date of a stored timestamp.
+    pub fn local_date(&self, instant: DateTime<Utc>) -> NaiveDate {
+        match self {
+            Clock::System => instant.with_timezone(&Local).date_naive(),
+            Clock::Fixed { offset, .. } => instant.with_timezone(offset).date_naive(),
+        }
+    }
+}
\ No newline at end of file
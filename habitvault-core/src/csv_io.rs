@@ -0,0 +1,503 @@
+//! Plain-text CSV export of habits and completions, and import of history
+//! from CSV files written by other trackers or spreadsheets.
+
+use crate::habit::{Habit, HabitData, Schedule};
+use crate::storage::{StorageError, write_atomic};
+use chrono::{NaiveDate, TimeZone, Utc};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// File names used by `export_to_dir`.
+pub const HABITS_FILE: &str = "habits.csv";
+pub const COMPLETIONS_FILE: &str = "completions.csv";
+
+/// Leading characters that make spreadsheets read a cell as a formula.
+const FORMULA_START: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// One row per habit.
+pub fn habits_csv(data: &HabitData) -> Result<Vec<u8>, StorageError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "id", "name", "description", "schedule", "target_days_per_week", "unit", "daily_target",
+        "created_at", "current_streak", "longest_streak",
+    ])?;
+    for habit in &data.habits {
+        let (unit, daily_target) = match &habit.quantity {
+            Some(quantity) => (quantity.unit.clone(), quantity.daily_target.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writer.write_record([
+            habit.id.clone(),
+            spreadsheet_text(&habit.name),
+            spreadsheet_text(&habit.description),
+            habit.schedule.describe(),
+            habit.target_days_per_week.to_string(),
+            spreadsheet_text(&unit),
+            daily_target,
+            habit.created_at.to_rfc3339(),
+            habit.streak.to_string(),
+            habit.longest_streak.to_string(),
+        ])?;
+    }
+    into_bytes(writer)
+}
+
+/// One row per logged day, oldest first, with the habit's name so the file
+/// can be read on its own.
+pub fn completions_csv(data: &HabitData) -> Result<Vec<u8>, StorageError> {
+    let names: HashMap<&str, &str> = data.habits.iter()
+        .map(|habit| (habit.id.as_str(), habit.name.as_str()))
+        .collect();
+    let mut completions: Vec<_> = data.completions.iter().collect();
+    completions.sort_by_key(|completion| (completion.date, names.get(completion.habit_id.as_str()).copied()));
+    
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["habit_id", "habit", "date", "completed", "value", "notes", "mood", "completed_at"])?;
+    for completion in completions {
+        writer.write_record([
+            completion.habit_id.clone(),
+            spreadsheet_text(names.get(completion.habit_id.as_str()).unwrap_or(&"")),
+            completion.date.to_string(),
+            data.is_completed_on_date(&completion.habit_id, completion.date).to_string(),
+            completion.value.map(|value| value.to_string()).unwrap_or_default(),
+            spreadsheet_text(completion.notes.as_deref().unwrap_or_default()),
+            completion.mood.map(|mood| mood.to_string()).unwrap_or_default(),
+            completion.completed_at.to_rfc3339(),
+        ])?;
+    }
+    into_bytes(writer)
+}
+
+/// User text for a cell. Text a spreadsheet would run as a formula, such as
+/// a note starting with "=", gets a leading apostrophe so it is shown as
+/// typed instead. `plan_import` drops it again.
+fn spreadsheet_text(text: &str) -> String {
+    if text.starts_with(FORMULA_START) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn into_bytes(writer: csv::Writer<Vec<u8>>) -> Result<Vec<u8>, StorageError> {
+    writer.into_inner().map_err(|e| StorageError::Io(e.into_error()))
+}
+
+/// Writes `HABITS_FILE` and `COMPLETIONS_FILE` into `dir`, replacing earlier
+/// exports. The files are not encrypted.
+pub fn export_to_dir(data: &HabitData, dir: &Path) -> Result<(), StorageError> {
+    write_atomic(&dir.join(HABITS_FILE), &habits_csv(data)?)?;
+    write_atomic(&dir.join(COMPLETIONS_FILE), &completions_csv(data)?)?;
+    Ok(())
+}
+
+/// The cells of a CSV file, as text.
+#[derive(Debug, Clone, Default)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Reads a CSV file with a header row. Commas, semicolons and tabs are
+/// recognised as separators, whichever the header row uses most.
+pub fn read_table(mut reader: impl Read) -> Result<CsvTable, StorageError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    
+    let header_line = text.lines().next().unwrap_or_default();
+    let delimiter = [b',', b';', b'\t'].into_iter()
+        .max_by_key(|delimiter| header_line.bytes().filter(|byte| byte == delimiter).count())
+        .unwrap_or(b',');
+    
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = reader.headers()?.iter().map(|header| header.trim().to_string()).collect();
+    let rows = reader.records()
+        .map(|record| record.map(|record| record.iter().map(str::to_string).collect()))
+        .collect::<Result<_, _>>()?;
+    Ok(CsvTable { headers, rows })
+}
+
+/// How the dates in an imported file are written. Only the date at the start
+/// of a cell is read, so timestamps work too.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DateFormat {
+    /// 2025-03-31, also with slashes or dots
+    #[default]
+    YearMonthDay,
+    /// 31/03/2025, also with dashes or dots
+    DayMonthYear,
+    /// 03/31/2025, also with dashes
+    MonthDayYear,
+}
+
+impl DateFormat {
+    pub const ALL: [DateFormat; 3] = [DateFormat::YearMonthDay, DateFormat::DayMonthYear, DateFormat::MonthDayYear];
+    
+    pub fn label(self) -> &'static str {
+        match self {
+            DateFormat::YearMonthDay => "Year-month-day",
+            DateFormat::DayMonthYear => "Day/month/year",
+            DateFormat::MonthDayYear => "Month/day/year",
+        }
+    }
+    
+    pub fn parse(self, text: &str) -> Option<NaiveDate> {
+        let date = text.trim().split(['T', ' ']).next()?;
+        let patterns: &[&str] = match self {
+            DateFormat::YearMonthDay => &["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d"],
+            DateFormat::DayMonthYear => &["%d/%m/%Y", "%d-%m-%Y", "%d.%m.%Y"],
+            DateFormat::MonthDayYear => &["%m/%d/%Y", "%m-%d-%Y"],
+        };
+        patterns.iter().find_map(|pattern| NaiveDate::parse_from_str(date, pattern).ok())
+    }
+}
+
+/// Which column of an imported file holds what.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnMapping {
+    /// Column with the habit's name. `None` puts every row into `habit_name`,
+    /// for files that only track one habit.
+    pub habit: Option<usize>,
+    pub habit_name: String,
+    pub date: usize,
+    pub date_format: DateFormat,
+    /// Amount for quantitative habits. For other habits a zero or "no" marks
+    /// a day that wasn't done, which is only imported for its note or mood.
+    /// Without it every row counts as done.
+    pub value: Option<usize>,
+    pub notes: Option<usize>,
+    /// Mood from 1 to 5.
+    pub mood: Option<usize>,
+}
+
+impl ColumnMapping {
+    /// A starting point guessed from the header names, e.g. those of a
+    /// completions export, and from how the dates are written.
+    pub fn guess(table: &CsvTable) -> Self {
+        let find = |names: &[&str]| table.headers.iter()
+            .position(|header| names.contains(&header.to_lowercase().as_str()));
+        
+        let date = find(&["date", "day"])
+            .or_else(|| table.headers.iter().position(|header| header.to_lowercase().contains("date")))
+            .unwrap_or(0);
+        // The first format that reads every date, so a day past the 12th
+        // settles whether the day or the month comes first
+        let dates: Vec<&str> = table.rows.iter()
+            .filter_map(|row| row.get(date).map(|cell| cell.trim()))
+            .filter(|cell| !cell.is_empty())
+            .take(100)
+            .collect();
+        let date_format = DateFormat::ALL.into_iter()
+            .find(|format| dates.iter().all(|cell| format.parse(cell).is_some()))
+            .unwrap_or_default();
+        
+        Self {
+            habit: find(&["habit", "habit name", "name", "activity", "task"]),
+            habit_name: String::new(),
+            date,
+            date_format,
+            value: find(&["value", "amount", "quantity", "count"]),
+            notes: find(&["notes", "note", "comment", "comments"]),
+            mood: find(&["mood"]),
+        }
+    }
+}
+
+/// A row of an imported file as it will be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    /// Line in the file, counting the header as line 1.
+    pub line: usize,
+    pub habit: String,
+    pub date: Option<NaiveDate>,
+    pub value: Option<f64>,
+    pub notes: Option<String>,
+    pub mood: Option<u8>,
+    /// Why the row is left out, if it is.
+    pub skipped: Option<String>,
+}
+
+/// What an import would do, for a preview before anything is changed.
+#[derive(Debug, Clone, Default)]
+pub struct ImportPlan {
+    pub rows: Vec<ImportRow>,
+    /// Habits in the file that don't exist yet and will be created as daily
+    /// habits, in the order they first appear.
+    pub new_habits: Vec<String>,
+}
+
+impl ImportPlan {
+    pub fn imported_rows(&self) -> impl Iterator<Item = &ImportRow> {
+        self.rows.iter().filter(|row| row.skipped.is_none())
+    }
+    
+    pub fn skipped_rows(&self) -> usize {
+        self.rows.iter().filter(|row| row.skipped.is_some()).count()
+    }
+}
+
+/// Reads every row of `table` with `mapping`, without changing `data`.
+pub fn plan_import(data: &HabitData, table: &CsvTable, mapping: &ColumnMapping) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+    
+    for (index, cells) in table.rows.iter().enumerate() {
+        let cell = |column: Option<usize>| column
+            .and_then(|column| cells.get(column))
+            .map(|cell| cell.trim())
+            .map(|cell| cell.strip_prefix('\'').filter(|text| text.starts_with(FORMULA_START)).unwrap_or(cell))
+            .filter(|cell| !cell.is_empty());
+        
+        let habit = match mapping.habit {
+            Some(column) => cell(Some(column)).unwrap_or_default(),
+            None => mapping.habit_name.trim(),
+        };
+        let date_cell = cell(Some(mapping.date));
+        let date = date_cell.and_then(|date| mapping.date_format.parse(date));
+        let value = cell(mapping.value).map(parse_value);
+        let mood = cell(mapping.mood).map(|mood| mood.parse::<u8>().ok().filter(|mood| (1..=5).contains(mood)));
+        
+        let skipped = if habit.is_empty() {
+            Some("No habit name".to_string())
+        } else if date.is_none() {
+            Some(match date_cell {
+                Some(text) => format!("Can't read the date \"{}\"", text),
+                None => "No date".to_string(),
+            })
+        } else if let Some(None) = value {
+            Some(format!("Can't read the value \"{}\"", cell(mapping.value).unwrap_or_default()))
+        } else if let Some(Some(value)) = value && value <= 0.0 && cell(mapping.notes).is_none() && cell(mapping.mood).is_none() {
+            Some("Not done".to_string())
+        } else if let Some(None) = mood {
+            Some("The mood must be from 1 to 5".to_string())
+        } else {
+            None
+        };
+        
+        if skipped.is_none()
+            && find_habit(data, habit).is_none()
+            && !plan.new_habits.iter().any(|name| same_name(name, habit)) {
+            plan.new_habits.push(habit.to_string());
+        }
+        plan.rows.push(ImportRow {
+            line: index + 2,
+            habit: habit.to_string(),
+            date,
+            value: value.flatten(),
+            notes: cell(mapping.notes).map(str::to_string),
+            mood: mood.flatten(),
+            skipped,
+        });
+    }
+    
+    plan
+}
+
+/// Applies the rows of `plan` that aren't skipped, creating its new habits.
+/// Amounts replace what was logged for quantitative habits; notes and mood
+/// replace the day's earlier ones only where the file has them. Returns the
+/// number of days imported.
+pub fn apply_import(data: &mut HabitData, plan: &ImportPlan) -> usize {
+    for name in &plan.new_habits {
+        let first_date = plan.imported_rows()
+            .filter(|row| same_name(&row.habit, name))
+            .filter_map(|row| row.date)
+            .min();
+        let created_at = match first_date {
+            Some(date) => Utc.from_utc_datetime(&date.and_hms_opt(12, 0, 0).unwrap()),
+            None => data.clock().now(),
+        };
+        data.add_habit(Habit {
+            id: data.new_habit_id(),
+            name: name.clone(),
+            description: String::new(),
+            created_at,
+            target_days_per_week: 7,
+            schedule: Schedule::Daily,
+            quantity: None,
+            streak: 0,
+            longest_streak: 0,
+        });
+    }
+    
+    let mut imported = 0;
+    for row in plan.imported_rows() {
+        let (Some(habit), Some(date)) = (find_habit(data, &row.habit), row.date) else {
+            continue;
+        };
+        let habit_id = habit.id.clone();
+        match (row.value, habit.quantity.is_some()) {
+            (Some(value), true) => data.log_value(&habit_id, date, value),
+            (Some(value), false) if value <= 0.0 => data.unmark_completed(&habit_id, date),
+            _ => data.mark_completed(&habit_id, date, None),
+        }
+        
+        if row.notes.is_some() || row.mood.is_some() {
+            let existing = data.completion_on_date(&habit_id, date);
+            let notes = row.notes.clone().or_else(|| existing.and_then(|c| c.notes.clone()));
+            let mood = row.mood.or_else(|| existing.and_then(|c| c.mood));
+            data.set_day_note(&habit_id, date, notes, mood);
+        }
+        imported += 1;
+    }
+    
+    data.recompute_streaks(data.today());
+    imported
+}
+
+fn find_habit<'a>(data: &'a HabitData, name: &str) -> Option<&'a Habit> {
+    data.habits.iter().find(|habit| same_name(&habit.name, name))
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().to_lowercase() == b.trim().to_lowercase()
+}
+
+/// A number, with a comma accepted as the decimal separator, or a yes/no
+/// word as 1 or 0. `None` if it is neither.
+fn parse_value(text: &str) -> Option<f64> {
+    match text.to_lowercase().as_str() {
+        "yes" | "y" | "true" | "x" | "done" | "✓" | "✔" => Some(1.0),
+        "no" | "n" | "false" | "-" => Some(0.0),
+        number => number.replace(',', ".").parse().ok().filter(|value: &f64| value.is_finite()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::habit::Quantity;
+    use chrono::FixedOffset;
+    
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+    
+    fn table(csv: &str) -> CsvTable {
+        read_table(csv.as_bytes()).unwrap()
+    }
+    
+    /// Data on 20 March with a plain habit "Read" and a quantitative habit
+    /// "Water".
+    fn sample_data() -> HabitData {
+        let mut data = HabitData::with_clock(Clock::Fixed {
+            now: Utc.from_utc_datetime(&date(20).and_hms_opt(12, 0, 0).unwrap()),
+            offset: FixedOffset::east_opt(0).unwrap(),
+        });
+        for (id, name, quantity) in [("read", "Read", None), ("water", "Water", Some(8.0))] {
+            data.add_habit(Habit {
+                id: id.to_string(),
+                name: name.to_string(),
+                description: String::new(),
+                created_at: Utc.from_utc_datetime(&date(1).and_hms_opt(12, 0, 0).unwrap()),
+                target_days_per_week: 7,
+                schedule: Schedule::Daily,
+                quantity: quantity.map(|daily_target| Quantity { unit: "glasses".to_string(), daily_target }),
+                streak: 0,
+                longest_streak: 0,
+            });
+        }
+        data
+    }
+    
+    #[test]
+    fn export_quotes_and_round_trips() {
+        let mut data = sample_data();
+        data.mark_completed("read", date(18), Some("Chapter 3, \"the good one\"\nand more".to_string()));
+        data.log_value("water", date(19), 5.0);
+        data.set_day_note("water", date(19), None, Some(4));
+        
+        let habits = String::from_utf8(habits_csv(&data).unwrap()).unwrap();
+        assert!(habits.starts_with("id,name,description,schedule,"));
+        assert!(habits.contains("water,Water,,Every day,7,glasses,8,"));
+        
+        let exported = table(&String::from_utf8(completions_csv(&data).unwrap()).unwrap());
+        assert_eq!(exported.rows.len(), 2);
+        assert_eq!(exported.rows[0][5], "Chapter 3, \"the good one\"\nand more");
+        assert_eq!(&exported.rows[1][1..7], ["Water", "2025-03-19", "false", "5", "", "4"]);
+        
+        let mapping = ColumnMapping::guess(&exported);
+        assert_eq!(mapping.habit, Some(1));
+        assert_eq!(mapping.date, 2);
+        let mut imported = sample_data();
+        let plan = plan_import(&imported, &exported, &mapping);
+        assert!(plan.new_habits.is_empty());
+        assert_eq!(apply_import(&mut imported, &plan), 2);
+        assert_eq!(imported.completion_on_date("read", date(18)).unwrap().notes, data.completion_on_date("read", date(18)).unwrap().notes);
+        assert_eq!(imported.value_on_date("water", date(19)), 5.0);
+        assert_eq!(imported.completion_on_date("water", date(19)).unwrap().mood, Some(4));
+    }
+    
+    #[test]
+    fn export_keeps_formulas_from_running() {
+        let mut data = sample_data();
+        data.habits[0].name = "=HYPERLINK(\"http://example.com\")".to_string();
+        data.habits[0].description = "@everyone".to_string();
+        data.mark_completed("read", date(18), Some("-2 pages".to_string()));
+        data.set_day_note("water", date(18), Some("+1 glass".to_string()), None);
+        
+        let habits = table(&String::from_utf8(habits_csv(&data).unwrap()).unwrap());
+        assert_eq!(habits.rows[0][1..3], ["'=HYPERLINK(\"http://example.com\")", "'@everyone"]);
+        assert_eq!(habits.rows[1][1], "Water");
+        
+        let exported = table(&String::from_utf8(completions_csv(&data).unwrap()).unwrap());
+        assert_eq!((exported.rows[0][1].as_str(), exported.rows[0][5].as_str()), ("'=HYPERLINK(\"http://example.com\")", "'-2 pages"));
+        assert_eq!((exported.rows[1][1].as_str(), exported.rows[1][5].as_str()), ("Water", "'+1 glass"));
+        
+        // Reading the export back gives the text as it was typed
+        let plan = plan_import(&data, &exported, &ColumnMapping::guess(&exported));
+        assert!(plan.new_habits.is_empty());
+        assert_eq!(plan.rows[0].notes.as_deref(), Some("-2 pages"));
+        assert_eq!(plan.rows[1].notes.as_deref(), Some("+1 glass"));
+    }
+    
+    #[test]
+    fn import_plan_skips_unreadable_rows() {
+        let data = sample_data();
+        let file = table("Activity;Day;Done;Comment\nread;05/03/2025;yes;\nRun;06/03/2025;1;Park\nRun;07/03/2025;0;\nRun;2025-03-08;1;\n;09/03/2025;1;\nRun;10/03/2025;maybe;\n");
+        let mapping = ColumnMapping {
+            value: Some(2),
+            date_format: DateFormat::DayMonthYear,
+            ..ColumnMapping::guess(&file)
+        };
+        assert_eq!((mapping.habit, mapping.date, mapping.notes), (Some(0), 1, Some(3)));
+        
+        let plan = plan_import(&data, &file, &mapping);
+        let skipped: Vec<(usize, &str)> = plan.rows.iter()
+            .filter_map(|row| row.skipped.as_deref().map(|reason| (row.line, reason)))
+            .collect();
+        assert_eq!(skipped, vec![
+            (4, "Not done"),
+            (5, "Can't read the date \"2025-03-08\""),
+            (6, "No habit name"),
+            (7, "Can't read the value \"maybe\""),
+        ]);
+        assert_eq!(plan.new_habits, vec!["Run".to_string()]);
+        assert_eq!(plan.imported_rows().count(), 2);
+    }
+    
+    #[test]
+    fn import_creates_habits_and_fills_single_habit_files() {
+        let mut data = sample_data();
+        let file = table("date,amount\n2025-03-02T08:00:00Z,3\n2025-03-03,8\n");
+        let mapping = ColumnMapping { habit: None, habit_name: "water ".to_string(), ..ColumnMapping::guess(&file) };
+        let plan = plan_import(&data, &file, &mapping);
+        assert!(plan.new_habits.is_empty());
+        assert_eq!(apply_import(&mut data, &plan), 2);
+        assert_eq!(data.value_on_date("water", date(2)), 3.0);
+        assert!(data.is_completed_on_date("water", date(3)));
+        
+        let file = table("habit,date\nStretch,2025-03-04\nstretch,2025-03-05\n");
+        let plan = plan_import(&data, &file, &ColumnMapping::guess(&file));
+        assert_eq!(plan.new_habits, vec!["Stretch".to_string()]);
+        apply_import(&mut data, &plan);
+        let stretch = data.habits.iter().find(|habit| habit.name == "Stretch").unwrap();
+        assert_eq!(data.clock().local_date(stretch.created_at), date(4));
+        assert!(data.is_completed_on_date(&stretch.id, date(5)));
+    }
+}
\ No newline at end of file
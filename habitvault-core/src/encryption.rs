@@ -0,0 +1,408 @@
+//! Key derivation, AES-256-GCM encryption and the credentials a vault can
+//! be unlocked with.
+
+use aes_gcm::{Aes256Gcm, Key, Nonce, KeyInit, aead::{Aead, Payload}};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::Read;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// Failures of the cryptographic primitives themselves. Whether a failed
+/// decryption means wrong credentials or a damaged file depends on what was
+/// being decrypted, so `storage` turns these into a `StorageError`.
+#[derive(Debug)]
+pub enum CryptoError {
+    /// Neither a password nor a keyfile was given.
+    NoCredentials,
+    /// The KDF rejected its parameters, e.g. from a damaged header.
+    InvalidKdfParams(String),
+    /// The ciphertext, its nonce or its associated data don't match the key.
+    Decryption,
+    Encryption,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::NoCredentials => write!(f, "Enter a password or choose a keyfile"),
+            CryptoError::InvalidKdfParams(e) => write!(f, "Invalid key derivation parameters: {}", e),
+            CryptoError::Decryption => write!(f, "Decryption failed"),
+            CryptoError::Encryption => write!(f, "Encryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedData {
+    pub data: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub salt: Vec<u8>,
+}
+
+/// Key derivation function and its parameters, recorded in the vault header
+/// so a vault can always be reopened with the settings it was written with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "id", rename_all = "snake_case")]
+pub enum KdfParams {
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+impl Default for KdfParams {
+    /// The `argon2` crate defaults, which every vault written before the
+    /// versioned format used.
+    fn default() -> Self {
+        KdfParams::Argon2id {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Rough amount of work per derivation, used to compare parameter sets.
+    fn cost(&self) -> u64 {
+        match self {
+            KdfParams::Argon2id { memory_kib, iterations, .. } => *memory_kib as u64 * *iterations as u64,
+        }
+    }
+    
+    pub fn is_weaker_than(&self, other: &KdfParams) -> bool {
+        self.cost() < other.cost()
+    }
+    
+    /// Rejects parameters far beyond the strongest profile. They come from the
+    /// vault header, which is read before anything is authenticated, so a
+    /// damaged or crafted one mustn't make an unlock take gigabytes of memory
+    /// or hours. Argon2 itself rejects ones that are too low.
+    fn check_limits(&self) -> Result<(), CryptoError> {
+        let KdfParams::Argon2id { memory_kib: max_memory, iterations: max_iterations, parallelism: max_parallelism } =
+            KdfProfile::Maximum.params();
+        match self {
+            KdfParams::Argon2id { memory_kib, iterations, parallelism } => {
+                if *memory_kib > max_memory * KDF_LIMIT_FACTOR
+                    || *iterations > max_iterations * KDF_LIMIT_FACTOR
+                    || *parallelism > max_parallelism * KDF_LIMIT_FACTOR {
+                    return Err(CryptoError::InvalidKdfParams(format!(
+                        "{} KiB, {} passes and {} lanes is beyond what HabitVault ever uses",
+                        memory_kib, iterations, parallelism
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How many times the `Maximum` profile's memory, passes and lanes a vault
+/// may ask for.
+const KDF_LIMIT_FACTOR: u32 = 4;
+
+/// Named KDF strength settings offered in the settings dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KdfProfile {
+    Standard,
+    Strong,
+    Maximum,
+}
+
+/// How long unlocking may take on this machine when picking a profile.
+const BENCHMARK_TARGET: Duration = Duration::from_millis(1000);
+
+impl KdfProfile {
+    pub const ALL: [KdfProfile; 3] = [KdfProfile::Standard, KdfProfile::Strong, KdfProfile::Maximum];
+    
+    pub fn params(self) -> KdfParams {
+        let (memory_kib, iterations) = match self {
+            KdfProfile::Standard => (Params::DEFAULT_M_COST, Params::DEFAULT_T_COST),
+            KdfProfile::Strong => (64 * 1024, 3),
+            KdfProfile::Maximum => (256 * 1024, 4),
+        };
+        KdfParams::Argon2id { memory_kib, iterations, parallelism: Params::DEFAULT_P_COST }
+    }
+    
+    pub fn label(self) -> &'static str {
+        match self {
+            KdfProfile::Standard => "Standard (19 MiB)",
+            KdfProfile::Strong => "Strong (64 MiB)",
+            KdfProfile::Maximum => "Maximum (256 MiB)",
+        }
+    }
+    
+    /// Times one `Standard` derivation and picks the strongest profile
+    /// expected to unlock within `BENCHMARK_TARGET` on this machine. Slow, so
+    /// keep it off the main thread.
+    pub fn benchmark() -> KdfProfile {
+        let baseline = KdfProfile::Standard.params();
+        let started = Instant::now();
+        let credentials = Credentials::Password(Zeroizing::new("benchmark".to_string()));
+        if derive_key(&credentials, &generate_salt(), &baseline).is_err() {
+            return KdfProfile::Standard;
+        }
+        let elapsed = started.elapsed().as_secs_f64();
+        
+        // Argon2 time scales roughly linearly with memory times passes
+        KdfProfile::ALL.into_iter().rev()
+            .find(|profile| {
+                let scale = profile.params().cost() as f64 / baseline.cost() as f64;
+                elapsed * scale <= BENCHMARK_TARGET.as_secs_f64()
+            })
+            .unwrap_or(KdfProfile::Standard)
+    }
+}
+
+/// The secrets a key slot can be unlocked with. A keyfile is represented by
+/// the SHA-256 digest of its contents, see `keyfile_digest`.
+#[derive(Clone)]
+pub enum Credentials {
+    Password(Zeroizing<String>),
+    PasswordAndKeyfile(Zeroizing<String>, Zeroizing<[u8; 32]>),
+    Keyfile(Zeroizing<[u8; 32]>),
+    /// A recovery key as produced by `normalize_recovery_key`.
+    RecoveryKey(Zeroizing<String>),
+}
+
+impl Credentials {
+    /// Combines what was entered in an unlock or password form. An empty
+    /// password with a keyfile means the keyfile alone.
+    pub fn new(password: Zeroizing<String>, keyfile: Option<Zeroizing<[u8; 32]>>) -> Result<Self, CryptoError> {
+        match (password.is_empty(), keyfile) {
+            (false, None) => Ok(Credentials::Password(password)),
+            (false, Some(keyfile)) => Ok(Credentials::PasswordAndKeyfile(password, keyfile)),
+            (true, Some(keyfile)) => Ok(Credentials::Keyfile(keyfile)),
+            (true, None) => Err(CryptoError::NoCredentials),
+        }
+    }
+    
+    pub fn recovery_key(input: &str) -> Self {
+        Credentials::RecoveryKey(normalize_recovery_key(input))
+    }
+    
+    /// The KDF input: the password or recovery key, and the keyfile digest
+    /// which is mixed in as Argon2's secret value.
+    fn kdf_inputs(&self) -> (&[u8], Option<&[u8]>) {
+        match self {
+            Credentials::Password(password) | Credentials::RecoveryKey(password) => (password.as_bytes(), None),
+            Credentials::PasswordAndKeyfile(password, keyfile) => (password.as_bytes(), Some(keyfile.as_slice())),
+            Credentials::Keyfile(keyfile) => (&[], Some(keyfile.as_slice())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CipherId {
+    Aes256Gcm,
+}
+
+/// An AES-256-GCM key ready for use. The expanded key schedule is wiped when
+/// this is dropped.
+pub struct Encryption {
+    cipher: Aes256Gcm,
+}
+
+impl Encryption {
+    pub fn new(credentials: &Credentials, salt: &[u8], kdf: &KdfParams) -> Result<Self, CryptoError> {
+        let key_bytes = derive_key(credentials, salt, kdf)?;
+        Ok(Self::from_key(&key_bytes))
+    }
+    
+    pub fn from_key(key_bytes: &[u8; 32]) -> Self {
+        let key = Key::<Aes256Gcm>::from_slice(key_bytes);
+        let cipher = Aes256Gcm::new(key);
+        
+        Self { cipher }
+    }
+    
+    /// Encrypts `data` under a fresh nonce. `associated_data` is not stored
+    /// in the result but must be passed unchanged to `decrypt`, which fails
+    /// if it was altered.
+    pub fn encrypt(&self, data: &[u8], associated_data: &[u8]) -> Result<EncryptedData, CryptoError> {
+        let nonce_bytes = generate_nonce();
+        let encrypted_data = self.encrypt_with_nonce(&nonce_bytes, data, associated_data)?;
+        
+        Ok(EncryptedData {
+            data: encrypted_data,
+            nonce: nonce_bytes.to_vec(),
+            salt: vec![], // Salt will be set by caller
+        })
+    }
+    
+    /// Like `encrypt`, for when the nonce has to be known up front because
+    /// it is part of the associated data. It must come from `generate_nonce`
+    /// and never be used twice.
+    pub fn encrypt_with_nonce(&self, nonce: &[u8; 12], data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let payload = Payload { msg: data, aad: associated_data };
+        self.cipher.encrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| CryptoError::Encryption)
+    }
+    
+    /// The plaintext is wiped when the returned buffer is dropped.
+    pub fn decrypt(&self, encrypted: &EncryptedData, associated_data: &[u8]) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+        if encrypted.nonce.len() != 12 {
+            return Err(CryptoError::Decryption);
+        }
+        let nonce = Nonce::from_slice(&encrypted.nonce);
+        let payload = Payload { msg: encrypted.data.as_ref(), aad: associated_data };
+        let decrypted_data = self.cipher.decrypt(nonce, payload)
+            .map_err(|_| CryptoError::Decryption)?;
+        
+        Ok(Zeroizing::new(decrypted_data))
+    }
+}
+
+/// Runs the credentials through the KDF. This is deliberately slow, so
+/// callers should do it once per unlock rather than once per save.
+pub fn derive_key(credentials: &Credentials, salt: &[u8], kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>, CryptoError> {
+    kdf.check_limits()?;
+    let (password, keyfile) = credentials.kdf_inputs();
+    let argon2 = match kdf {
+        KdfParams::Argon2id { memory_kib, iterations, parallelism } => {
+            let params = Params::new(*memory_kib, *iterations, *parallelism, Some(32))
+                .map_err(|e| CryptoError::InvalidKdfParams(e.to_string()))?;
+            match keyfile {
+                Some(secret) => Argon2::new_with_secret(secret, Algorithm::Argon2id, Version::V0x13, params)
+                    .map_err(|e| CryptoError::InvalidKdfParams(e.to_string()))?,
+                None => Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+            }
+        }
+    };
+    
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    argon2.hash_password_into(password, salt, key_bytes.as_mut())
+        .map_err(|e| CryptoError::InvalidKdfParams(e.to_string()))?;
+    Ok(key_bytes)
+}
+
+/// A random key for encrypting the habit data itself.
+pub fn generate_key() -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    thread_rng().fill_bytes(key.as_mut());
+    key
+}
+
+/// Contents for a new keyfile.
+pub fn generate_keyfile() -> Zeroizing<[u8; 64]> {
+    let mut contents = Zeroizing::new([0u8; 64]);
+    thread_rng().fill_bytes(contents.as_mut());
+    contents
+}
+
+/// Hashes a keyfile of any size down to the 32 bytes used for key derivation.
+/// Any file can serve as a keyfile, as long as it never changes.
+pub fn keyfile_digest(mut reader: impl Read) -> std::io::Result<Zeroizing<[u8; 32]>> {
+    let mut hasher = Sha256::new();
+    let mut buffer = Zeroizing::new([0u8; 8192]);
+    let mut total = 0;
+    loop {
+        let read = reader.read(buffer.as_mut())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        total += read;
+    }
+    if total == 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Keyfile is empty"));
+    }
+    
+    let mut digest = Zeroizing::new([0u8; 32]);
+    digest.copy_from_slice(&hasher.finalize());
+    Ok(digest)
+}
+
+pub fn generate_nonce() -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    thread_rng().fill_bytes(&mut nonce);
+    nonce
+}
+
+/// A random identifier for a new vault, recorded in its header.
+pub fn generate_vault_id() -> [u8; 16] {
+    let mut vault_id = [0u8; 16];
+    thread_rng().fill_bytes(&mut vault_id);
+    vault_id
+}
+
+pub fn generate_salt() -> [u8; 32] {
+    let mut salt = [0u8; 32];
+    thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+/// Characters used in recovery keys: Crockford's base32, which leaves out
+/// letters that are easily misread when typed back in from paper.
+const RECOVERY_KEY_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// A random recovery key of 40 characters (200 bits) in dash-separated groups
+/// of five, meant to be printed or written down.
+pub fn generate_recovery_key() -> Zeroizing<String> {
+    let mut bytes = Zeroizing::new([0u8; 40]);
+    thread_rng().fill_bytes(bytes.as_mut());
+    
+    let mut recovery_key = Zeroizing::new(String::with_capacity(47));
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 && i % 5 == 0 {
+            recovery_key.push('-');
+        }
+        recovery_key.push(RECOVERY_KEY_ALPHABET[(byte & 31) as usize] as char);
+    }
+    recovery_key
+}
+
+/// The form of a recovery key that is fed to the KDF. Case, spaces and dashes
+/// are ignored, and look-alike letters are read as the digits they resemble.
+pub fn normalize_recovery_key(input: &str) -> Zeroizing<String> {
+    let mut normalized = Zeroizing::new(String::with_capacity(input.len()));
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '-') {
+        normalized.push(match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        });
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn argon2id(memory_kib: u32, iterations: u32, parallelism: u32) -> KdfParams {
+        KdfParams::Argon2id { memory_kib, iterations, parallelism }
+    }
+    
+    #[test]
+    fn kdf_params_are_limited_to_four_times_the_maximum_profile() {
+        for profile in KdfProfile::ALL {
+            assert!(profile.params().check_limits().is_ok());
+        }
+        assert!(argon2id(4 * 256 * 1024, 16, 4).check_limits().is_ok());
+        
+        for params in [argon2id(4 * 256 * 1024 + 1, 1, 1), argon2id(8, 17, 1), argon2id(32, 1, 5), argon2id(u32::MAX, u32::MAX, u32::MAX)] {
+            assert!(matches!(params.check_limits(), Err(CryptoError::InvalidKdfParams(_))), "{:?}", params);
+        }
+    }
+    
+    #[test]
+    fn out_of_range_params_are_refused_before_deriving() {
+        let credentials = Credentials::Password(Zeroizing::new("pw".to_string()));
+        let salt = generate_salt();
+        assert!(derive_key(&credentials, &salt, &argon2id(8, 1, 1)).is_ok());
+        // Argon2 needs at least 8 KiB per lane
+        assert!(matches!(derive_key(&credentials, &salt, &argon2id(4, 1, 1)), Err(CryptoError::InvalidKdfParams(_))));
+        // Would take 4 TiB if it were tried
+        assert!(matches!(derive_key(&credentials, &salt, &argon2id(u32::MAX, 1, 1)), Err(CryptoError::InvalidKdfParams(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,746 @@
+//! Habits, their schedules and the days logged for them.
+
+use chrono::{DateTime, Datelike, Duration, Utc, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use crate::clock::Clock;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Habit {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+    pub target_days_per_week: u8,
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub quantity: Option<Quantity>,
+    pub streak: u32,
+    pub longest_streak: u32,
+}
+
+/// When a habit is expected to be done. Days that aren't scheduled never
+/// break a streak.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Schedule {
+    /// Every day, optionally relaxed to a weekly goal via `target_days_per_week`.
+    #[default]
+    Daily,
+    /// Only on the listed days of the week.
+    Weekdays { days: Vec<Weekday> },
+    /// Every `interval` days counting from `anchor`.
+    EveryNDays { interval: u32, anchor: NaiveDate },
+    /// Any `times` days within each calendar month.
+    TimesPerMonth { times: u32 },
+    /// Every day from `start` to `end` inclusive, and never outside it.
+    DateRange { start: NaiveDate, end: NaiveDate },
+}
+
+impl Schedule {
+    pub fn is_scheduled(&self, date: NaiveDate) -> bool {
+        match self {
+            Schedule::Daily | Schedule::TimesPerMonth { .. } => true,
+            Schedule::Weekdays { days } => days.contains(&date.weekday()),
+            Schedule::EveryNDays { interval, anchor } => {
+                date >= *anchor && (date - *anchor).num_days() % (*interval).max(1) as i64 == 0
+            }
+            Schedule::DateRange { start, end } => date >= *start && date <= *end,
+        }
+    }
+    
+    pub fn describe(&self) -> String {
+        match self {
+            Schedule::Daily => "Every day".to_string(),
+            Schedule::Weekdays { days } => {
+                let names: Vec<String> = days.iter().map(|d| d.to_string()).collect();
+                format!("Every {}", names.join(", "))
+            }
+            Schedule::EveryNDays { interval, .. } => format!("Every {} days", interval),
+            Schedule::TimesPerMonth { times } => format!("{} times per month", times),
+            Schedule::DateRange { start, end } => format!("From {} to {}", start, end),
+        }
+    }
+}
+
+/// Amount-based tracking such as 8 glasses of water or 20 pages a day. A day
+/// only counts as completed once the logged value reaches the daily target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quantity {
+    pub unit: String,
+    pub daily_target: f64,
+}
+
+impl Quantity {
+    pub fn format_amount(value: f64) -> String {
+        if value.fract() == 0.0 {
+            format!("{:.0}", value)
+        } else {
+            format!("{:.1}", value)
+        }
+    }
+    
+    /// e.g. "3/8 glasses"
+    pub fn describe_progress(&self, value: f64) -> String {
+        format!("{}/{} {}", Self::format_amount(value), Self::format_amount(self.daily_target), self.unit)
+    }
+}
+
+impl Habit {
+    /// A daily habit with a target below 7 days is tracked as a weekly goal:
+    /// its streak counts consecutive weeks in which the target was met.
+    pub fn is_weekly_goal(&self) -> bool {
+        self.schedule == Schedule::Daily && self.target_days_per_week < 7
+    }
+    
+    pub fn is_monthly_goal(&self) -> bool {
+        matches!(self.schedule, Schedule::TimesPerMonth { .. })
+    }
+    
+    /// Whether this specific day must be completed to keep the streak going.
+    /// Weekly and monthly goals don't require any particular day.
+    pub fn is_due_on(&self, date: NaiveDate) -> bool {
+        !self.is_weekly_goal() && !self.is_monthly_goal() && self.schedule.is_scheduled(date)
+    }
+    
+    pub fn streak_unit(&self) -> &'static str {
+        if self.is_weekly_goal() {
+            "week streak"
+        } else if self.is_monthly_goal() {
+            "month streak"
+        } else {
+            "day streak"
+        }
+    }
+}
+
+/// Monday of the week containing `date`, matching the calendar layout.
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+fn next_month_start(date: NaiveDate) -> NaiveDate {
+    let start = month_start(date);
+    if start.month() == 12 {
+        NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap()
+    } else {
+        NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HabitCompletion {
+    pub habit_id: String,
+    /// The user's local date the completion counts for.
+    pub date: NaiveDate,
+    pub completed_at: DateTime<Utc>,
+    /// UTC offset in seconds of the time zone `completed_at` was recorded in.
+    #[serde(default)]
+    pub utc_offset_seconds: Option<i32>,
+    pub notes: Option<String>,
+    /// How the day went, from 1 (worst) to 5 (best).
+    #[serde(default)]
+    pub mood: Option<u8>,
+    /// Amount logged for quantitative habits. Records without a value count
+    /// as fully done, which keeps history from before a habit became quantitative.
+    /// Zero marks a day that wasn't done but has a note or mood, for any habit.
+    #[serde(default)]
+    pub value: Option<f64>,
+}
+
+impl HabitCompletion {
+    fn has_note(&self) -> bool {
+        self.notes.is_some() || self.mood.is_some()
+    }
+}
+
+/// Everything stored in a vault: the habits and every day logged for them.
+/// Dates are the user's local dates according to the data's `Clock`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HabitData {
+    pub habits: Vec<Habit>,
+    pub completions: Vec<HabitCompletion>,
+    #[serde(skip)]
+    clock: Clock,
+}
+
+impl Default for HabitData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HabitData {
+    pub fn new() -> Self {
+        Self::with_clock(Clock::System)
+    }
+    
+    pub fn with_clock(clock: Clock) -> Self {
+        Self {
+            habits: Vec::new(),
+            completions: Vec::new(),
+            clock,
+        }
+    }
+    
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+    
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+    
+    /// The user's local date according to this data's clock.
+    pub fn today(&self) -> NaiveDate {
+        self.clock.today()
+    }
+    
+    /// An id for a new habit, made from the current time like every habit id
+    /// so far, with a suffix if several are created within a second.
+    pub fn new_habit_id(&self) -> String {
+        let timestamp = self.clock.now().timestamp();
+        let mut id = format!("habit_{}", timestamp);
+        let mut suffix = 1;
+        while self.get_habit_by_id(&id).is_some() {
+            suffix += 1;
+            id = format!("habit_{}_{}", timestamp, suffix);
+        }
+        id
+    }
+    
+    pub fn add_habit(&mut self, habit: Habit) {
+        self.habits.push(habit);
+    }
+    
+    pub fn remove_habit(&mut self, habit_id: &str) {
+        self.habits.retain(|h| h.id != habit_id);
+        self.completions.retain(|c| c.habit_id != habit_id);
+    }
+    
+    pub fn update_habit(&mut self, habit_id: &str, new_name: &str, new_description: &str, target_days_per_week: u8, schedule: Schedule, quantity: Option<Quantity>) {
+        if let Some(habit) = self.habits.iter_mut().find(|h| h.id == habit_id) {
+            habit.name = new_name.to_string();
+            habit.description = new_description.to_string();
+            habit.target_days_per_week = target_days_per_week.clamp(1, 7);
+            habit.schedule = schedule;
+            habit.quantity = quantity;
+        }
+        self.update_streak(habit_id);
+    }
+    
+    pub fn mark_completed(&mut self, habit_id: &str, date: NaiveDate, notes: Option<String>) {
+        if self.is_completed_on_date(habit_id, date) {
+            return;
+        }
+        
+        // Quantitative habits are completed by logging the full daily target,
+        // topping up any partial amount logged earlier
+        if let Some(target) = self.get_habit_by_id(habit_id)
+            .and_then(|h| h.quantity.as_ref())
+            .map(|q| q.daily_target) {
+            self.log_value(habit_id, date, target);
+            return;
+        }
+        
+        let now = self.clock.now();
+        let utc_offset_seconds = Some(self.clock.offset().local_minus_utc());
+        // A day that only had a note keeps it
+        if let Some(completion) = self.completions.iter_mut()
+            .find(|c| c.habit_id == habit_id && c.date == date) {
+            completion.value = None;
+            completion.completed_at = now;
+            completion.utc_offset_seconds = utc_offset_seconds;
+            if notes.is_some() {
+                completion.notes = notes;
+            }
+        } else {
+            self.completions.push(HabitCompletion {
+                habit_id: habit_id.to_string(),
+                date,
+                completed_at: now,
+                utc_offset_seconds,
+                notes,
+                mood: None,
+                value: None,
+            });
+        }
+        self.update_streak(habit_id);
+    }
+    
+    /// Records the amount done on `date` for a quantitative habit, replacing
+    /// any earlier value. Logging zero unmarks the day.
+    pub fn log_value(&mut self, habit_id: &str, date: NaiveDate, value: f64) {
+        if value <= 0.0 {
+            self.unmark_completed(habit_id, date);
+            return;
+        }
+        
+        if let Some(completion) = self.completions.iter_mut()
+            .find(|c| c.habit_id == habit_id && c.date == date) {
+            completion.value = Some(value);
+            completion.completed_at = self.clock.now();
+            completion.utc_offset_seconds = Some(self.clock.offset().local_minus_utc());
+        } else {
+            self.completions.push(HabitCompletion {
+                habit_id: habit_id.to_string(),
+                date,
+                completed_at: self.clock.now(),
+                utc_offset_seconds: Some(self.clock.offset().local_minus_utc()),
+                notes: None,
+                mood: None,
+                value: Some(value),
+            });
+        }
+        self.update_streak(habit_id);
+    }
+    
+    /// Marks `date` as not done. Its note and mood are kept, so the record
+    /// only goes away if it has neither.
+    pub fn unmark_completed(&mut self, habit_id: &str, date: NaiveDate) {
+        for completion in self.completions.iter_mut().filter(|c| c.habit_id == habit_id && c.date == date) {
+            completion.value = Some(0.0);
+        }
+        self.completions.retain(|c| !(c.habit_id == habit_id && c.date == date && !c.has_note()));
+        self.update_streak(habit_id);
+    }
+    
+    /// The record for `date`, complete or not.
+    pub fn completion_on_date(&self, habit_id: &str, date: NaiveDate) -> Option<&HabitCompletion> {
+        self.completions.iter().find(|c| c.habit_id == habit_id && c.date == date)
+    }
+    
+    /// Replaces the note and mood for `date`. A day that wasn't done gets a
+    /// record that only holds them, removed again once both are cleared.
+    pub fn set_day_note(&mut self, habit_id: &str, date: NaiveDate, notes: Option<String>, mood: Option<u8>) {
+        let mood = mood.map(|mood| mood.clamp(1, 5));
+        if let Some(index) = self.completions.iter().position(|c| c.habit_id == habit_id && c.date == date) {
+            let completion = &mut self.completions[index];
+            completion.notes = notes;
+            completion.mood = mood;
+            if completion.value == Some(0.0) && !completion.has_note() {
+                self.completions.remove(index);
+            }
+        } else if notes.is_some() || mood.is_some() {
+            self.completions.push(HabitCompletion {
+                habit_id: habit_id.to_string(),
+                date,
+                completed_at: self.clock.now(),
+                utc_offset_seconds: Some(self.clock.offset().local_minus_utc()),
+                notes,
+                mood,
+                value: Some(0.0),
+            });
+        }
+    }
+    
+    pub fn is_completed_on_date(&self, habit_id: &str, date: NaiveDate) -> bool {
+        self.completions.iter().any(|c| c.habit_id == habit_id && c.date == date && self.is_target_reached(c))
+    }
+    
+    fn is_target_reached(&self, completion: &HabitCompletion) -> bool {
+        match self.get_habit_by_id(&completion.habit_id).and_then(|h| h.quantity.as_ref()) {
+            Some(quantity) => completion.value.is_none_or(|value| value >= quantity.daily_target),
+            None => completion.value.is_none_or(|value| value > 0.0),
+        }
+    }
+    
+    /// Days the habit was completed on, with the target reached for
+    /// quantitative habits, oldest first.
+    pub fn completed_dates(&self, habit_id: &str) -> Vec<NaiveDate> {
+        let mut dates: Vec<NaiveDate> = self.completions.iter()
+            .filter(|c| c.habit_id == habit_id && self.is_target_reached(c))
+            .map(|c| c.date)
+            .collect();
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+    
+    /// Amount logged on `date` for a quantitative habit, or 0 if nothing was logged.
+    pub fn value_on_date(&self, habit_id: &str, date: NaiveDate) -> f64 {
+        let Some(quantity) = self.get_habit_by_id(habit_id).and_then(|h| h.quantity.as_ref()) else {
+            return 0.0;
+        };
+        self.completions.iter()
+            .find(|c| c.habit_id == habit_id && c.date == date)
+            .map_or(0.0, |c| c.value.unwrap_or(quantity.daily_target))
+    }
+    
+    /// Fraction of the day's target reached, from 0.0 to 1.0. Habits without
+    /// a quantity are either 0.0 or 1.0.
+    pub fn progress_on_date(&self, habit_id: &str, date: NaiveDate) -> f64 {
+        match self.get_habit_by_id(habit_id).and_then(|h| h.quantity.as_ref()) {
+            Some(quantity) if quantity.daily_target > 0.0 => {
+                (self.value_on_date(habit_id, date) / quantity.daily_target).min(1.0)
+            }
+            _ if self.is_completed_on_date(habit_id, date) => 1.0,
+            _ => 0.0,
+        }
+    }
+    
+    /// Progress on `date` for summaries like the heatmap, or None if the
+    /// day doesn't count for this habit: before it existed, or not due and
+    /// nothing logged.
+    pub fn rate_on_date(&self, habit_id: &str, date: NaiveDate) -> Option<f64> {
+        let habit = self.get_habit_by_id(habit_id)?;
+        let progress = self.progress_on_date(habit_id, date);
+        let counts = date >= self.clock.local_date(habit.created_at)
+            && (habit.is_due_on(date) || progress > 0.0);
+        counts.then_some(progress)
+    }
+    
+    /// A past day that was due but not completed. Days before the habit
+    /// existed, unscheduled days and days of weekly/monthly goals are never missed.
+    pub fn is_missed_on_date(&self, habit_id: &str, date: NaiveDate) -> bool {
+        let Some(habit) = self.get_habit_by_id(habit_id) else {
+            return false;
+        };
+        date < self.today()
+            && date >= self.clock.local_date(habit.created_at)
+            && habit.is_due_on(date)
+            && !self.is_completed_on_date(habit_id, date)
+    }
+    
+    pub fn get_habit_by_id(&self, habit_id: &str) -> Option<&Habit> {
+        self.habits.iter().find(|h| h.id == habit_id)
+    }
+    
+    pub fn get_habit_by_id_mut(&mut self, habit_id: &str) -> Option<&mut Habit> {
+        self.habits.iter_mut().find(|h| h.id == habit_id)
+    }
+    
+    fn completions_between(&self, habit_id: &str, start: NaiveDate, end: NaiveDate) -> u32 {
+        self.completions.iter()
+            .filter(|c| c.habit_id == habit_id && c.date >= start && c.date < end && self.is_target_reached(c))
+            .count() as u32
+    }
+    
+    /// Number of completions in the Monday-to-Sunday week containing `date`.
+    pub fn completions_in_week(&self, habit_id: &str, date: NaiveDate) -> u32 {
+        let start = week_start(date);
+        self.completions_between(habit_id, start, start + Duration::days(7))
+    }
+    
+    /// Number of completions in the calendar month containing `date`.
+    pub fn completions_in_month(&self, habit_id: &str, date: NaiveDate) -> u32 {
+        self.completions_between(habit_id, month_start(date), next_month_start(date))
+    }
+    
+    fn update_streak(&mut self, habit_id: &str) {
+        let streak = self.current_streak(habit_id, self.today());
+        
+        if let Some(habit) = self.get_habit_by_id_mut(habit_id) {
+            habit.streak = streak;
+            if streak > habit.longest_streak {
+                habit.longest_streak = streak;
+            }
+        }
+    }
+    
+    /// Recomputes every stored streak as of `today`. Stored values go stale
+    /// when days pass without the app being open, so this runs on load and
+    /// whenever the date rolls over.
+    pub fn recompute_streaks(&mut self, today: NaiveDate) {
+        let streaks: Vec<(String, u32)> = self.habits.iter()
+            .map(|h| (h.id.clone(), self.current_streak(&h.id, today)))
+            .collect();
+        
+        for (habit_id, streak) in streaks {
+            if let Some(habit) = self.get_habit_by_id_mut(&habit_id) {
+                habit.streak = streak;
+                habit.longest_streak = habit.longest_streak.max(streak);
+            }
+        }
+    }
+    
+    /// The streak as seen on `today`. Today (and the current week or month
+    /// for goals) is still open, so it extends the streak once done but never
+    /// breaks it.
+    pub fn current_streak(&self, habit_id: &str, today: NaiveDate) -> u32 {
+        let Some(habit) = self.get_habit_by_id(habit_id) else {
+            return 0;
+        };
+        
+        if habit.is_weekly_goal() {
+            let target = habit.target_days_per_week as u32;
+            self.goal_streak(target, week_start(today), |start| start - Duration::days(7), |date| {
+                self.completions_in_week(habit_id, date)
+            })
+        } else if let Schedule::TimesPerMonth { times } = habit.schedule {
+            self.goal_streak(times, month_start(today), |start| month_start(start - Duration::days(1)), |date| {
+                self.completions_in_month(habit_id, date)
+            })
+        } else {
+            self.scheduled_streak(habit_id, &habit.schedule, today)
+        }
+    }
+    
+    /// Consecutive periods (weeks or months) in which at least `target`
+    /// completions were logged, starting from the `current` period.
+    fn goal_streak(
+        &self,
+        target: u32,
+        current: NaiveDate,
+        previous_period: impl Fn(NaiveDate) -> NaiveDate,
+        completions_in_period: impl Fn(NaiveDate) -> u32,
+    ) -> u32 {
+        // A zero target would be met by every period back to the beginning of time
+        let target = target.max(1);
+        let mut streak = 0;
+        let mut period = current;
+        
+        // The current period is still in progress, so it only adds to the
+        // streak once the target is met and never breaks it
+        if completions_in_period(period) >= target {
+            streak = 1;
+        }
+        
+        // Count consecutive periods backwards from the previous one
+        loop {
+            period = previous_period(period);
+            if completions_in_period(period) >= target {
+                streak += 1;
+            } else {
+                break;
+            }
+        }
+        
+        streak
+    }
+    
+    /// Consecutive completed days, skipping days the schedule doesn't include.
+    fn scheduled_streak(&self, habit_id: &str, schedule: &Schedule, today: NaiveDate) -> u32 {
+        // Nothing before the first completion can extend the streak
+        let Some(earliest) = self.completions.iter()
+            .filter(|c| c.habit_id == habit_id && self.is_target_reached(c))
+            .map(|c| c.date)
+            .min() else {
+            return 0;
+        };
+        
+        let mut streak = 0;
+        let mut date = today;
+        
+        // Today isn't over yet, so if it hasn't been done the streak is
+        // counted back from yesterday instead
+        if !self.is_completed_on_date(habit_id, today) {
+            date -= Duration::days(1);
+        }
+        
+        // Only count consecutive scheduled days
+        while date >= earliest {
+            if schedule.is_scheduled(date) {
+                if self.is_completed_on_date(habit_id, date) {
+                    streak += 1;
+                } else {
+                    break;
+                }
+            }
+            date -= Duration::days(1);
+        }
+        
+        streak
+    }
+    
+    pub fn get_completions_for_habit(&self, habit_id: &str) -> Vec<&HabitCompletion> {
+        self.completions.iter()
+            .filter(|c| c.habit_id == habit_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+    
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+    
+    /// A clock at `hour:minute` UTC on `day`, seen from `offset_hours`.
+    fn clock(day: u32, hour: u32, minute: u32, offset_hours: i32) -> Clock {
+        Clock::Fixed {
+            now: Utc.from_utc_datetime(&date(day).and_hms_opt(hour, minute, 0).unwrap()),
+            offset: FixedOffset::east_opt(offset_hours * 3600).unwrap(),
+        }
+    }
+    
+    fn data_with_habit(clock: Clock, schedule: Schedule) -> HabitData {
+        let mut data = HabitData::with_clock(clock);
+        data.add_habit(Habit {
+            id: "h".to_string(),
+            name: "Habit".to_string(),
+            description: String::new(),
+            created_at: Utc.from_utc_datetime(&date(1).and_hms_opt(12, 0, 0).unwrap()),
+            target_days_per_week: 7,
+            schedule,
+            quantity: None,
+            streak: 0,
+            longest_streak: 0,
+        });
+        data
+    }
+    
+    fn streak(data: &HabitData) -> u32 {
+        data.get_habit_by_id("h").unwrap().streak
+    }
+    
+    #[test]
+    fn today_not_done_yet_keeps_the_streak() {
+        let mut data = data_with_habit(clock(10, 12, 0, 0), Schedule::Daily);
+        for day in 7..=9 {
+            data.mark_completed("h", date(day), None);
+        }
+        assert_eq!(data.current_streak("h", date(10)), 3);
+        assert_eq!(streak(&data), 3);
+        
+        data.mark_completed("h", date(10), None);
+        assert_eq!(streak(&data), 4);
+        
+        data.unmark_completed("h", date(10));
+        assert_eq!(streak(&data), 3);
+    }
+    
+    #[test]
+    fn streak_counts_back_from_yesterday() {
+        let mut data = data_with_habit(clock(10, 12, 0, 0), Schedule::Daily);
+        // Missed the 8th
+        for day in [6, 7, 9] {
+            data.mark_completed("h", date(day), None);
+        }
+        assert_eq!(data.current_streak("h", date(10)), 1);
+        
+        // Missing yesterday as well as today breaks it
+        assert_eq!(data.current_streak("h", date(11)), 0);
+        
+        // Unscheduled days are skipped rather than breaking it
+        let mut data = data_with_habit(clock(10, 12, 0, 0), Schedule::Weekdays {
+            days: vec![Weekday::Mon, Weekday::Wed, Weekday::Fri],
+        });
+        // Monday 3rd, Wednesday 5th and Friday 7th; Monday 10th is still open
+        for day in [3, 5, 7] {
+            data.mark_completed("h", date(day), None);
+        }
+        assert_eq!(data.current_streak("h", date(10)), 3);
+    }
+    
+    #[test]
+    fn weekly_goal_streak_counts_weeks_that_met_the_target() {
+        let mut data = data_with_habit(clock(19, 12, 0, 0), Schedule::Daily);
+        data.get_habit_by_id_mut("h").unwrap().target_days_per_week = 3;
+        assert!(data.get_habit_by_id("h").unwrap().is_weekly_goal());
+        
+        // Three days in the weeks of the 3rd and the 10th, one so far in the
+        // week of the 17th
+        for day in [3, 4, 5, 10, 12, 14, 17] {
+            data.mark_completed("h", date(day), None);
+        }
+        assert_eq!(streak(&data), 2);
+        
+        // The current week counts once its target is met
+        for day in [18, 19] {
+            data.mark_completed("h", date(day), None);
+        }
+        assert_eq!(streak(&data), 3);
+        
+        // A finished week below the target breaks it
+        data.unmark_completed("h", date(12));
+        assert_eq!(streak(&data), 1);
+    }
+    
+    #[test]
+    fn stale_stored_streak_is_recomputed_on_load() {
+        let json = r#"{
+            "habits": [{
+                "id": "h", "name": "Habit", "description": "",
+                "created_at": "2025-03-01T12:00:00Z", "target_days_per_week": 7,
+                "streak": 5, "longest_streak": 5
+            }],
+            "completions": [
+                {"habit_id": "h", "date": "2025-03-04", "completed_at": "2025-03-04T12:00:00Z", "notes": null},
+                {"habit_id": "h", "date": "2025-03-05", "completed_at": "2025-03-05T12:00:00Z", "notes": null}
+            ]
+        }"#;
+        let mut data: HabitData = serde_json::from_str(json).unwrap();
+        data.set_clock(clock(10, 12, 0, 0));
+        data.recompute_streaks(data.today());
+        
+        let habit = data.get_habit_by_id("h").unwrap();
+        assert_eq!(habit.streak, 0);
+        assert_eq!(habit.longest_streak, 5);
+    }
+    
+    #[test]
+    fn midnight_rollover_keeps_the_new_day_open() {
+        // 23:30 in UTC+1 on the 9th
+        let mut data = data_with_habit(clock(9, 22, 30, 1), Schedule::Daily);
+        assert_eq!(data.today(), date(9));
+        for day in 8..=9 {
+            data.mark_completed("h", date(day), None);
+        }
+        assert_eq!(streak(&data), 2);
+        
+        // 00:30 local on the 10th, which is still the 9th in UTC
+        data.set_clock(clock(9, 23, 30, 1));
+        assert_eq!(data.today(), date(10));
+        data.recompute_streaks(data.today());
+        assert_eq!(streak(&data), 2);
+        
+        // A day later the 10th was missed
+        data.set_clock(clock(10, 23, 30, 1));
+        data.recompute_streaks(data.today());
+        assert_eq!(streak(&data), 0);
+    }
+    
+    #[test]
+    fn utc_offset_change_uses_the_stored_local_dates() {
+        // Ticked the 8th and 9th in UTC+2
+        let mut data = data_with_habit(clock(9, 20, 0, 2), Schedule::Daily);
+        for day in 8..=9 {
+            data.mark_completed("h", date(day), None);
+        }
+        let completion = data.completion_on_date("h", date(9)).unwrap();
+        assert_eq!(completion.utc_offset_seconds, Some(2 * 3600));
+        
+        // 01:00 UTC on the 10th: the 10th in UTC+2, still the 9th in UTC-5
+        data.set_clock(clock(10, 1, 0, 2));
+        data.recompute_streaks(data.today());
+        assert_eq!(data.today(), date(10));
+        assert_eq!(streak(&data), 2);
+        
+        data.set_clock(clock(10, 1, 0, -5));
+        data.recompute_streaks(data.today());
+        assert_eq!(data.today(), date(9));
+        assert_eq!(streak(&data), 2);
+        assert!(data.is_completed_on_date("h", data.today()));
+    }
+    
+    #[test]
+    fn notes_outlive_the_completion() {
+        let mut data = data_with_habit(clock(10, 12, 0, 0), Schedule::Daily);
+        data.mark_completed("h", date(9), Some("Felt good".to_string()));
+        data.unmark_completed("h", date(9));
+        assert!(!data.is_completed_on_date("h", date(9)));
+        assert!(data.is_missed_on_date("h", date(9)));
+        assert_eq!(data.completion_on_date("h", date(9)).unwrap().notes.as_deref(), Some("Felt good"));
+        assert!(data.completed_dates("h").is_empty());
+        
+        // A missed day takes a note, and marking it done keeps it
+        data.set_day_note("h", date(8), Some("Sick".to_string()), Some(1));
+        assert!(!data.is_completed_on_date("h", date(8)));
+        data.mark_completed("h", date(8), None);
+        assert!(data.is_completed_on_date("h", date(8)));
+        assert_eq!(data.completion_on_date("h", date(8)).unwrap().mood, Some(1));
+        assert_eq!(data.get_completions_for_habit("h").len(), 2);
+        
+        // Clearing the note of a day that wasn't done leaves nothing behind
+        data.set_day_note("h", date(9), None, None);
+        assert!(data.completion_on_date("h", date(9)).is_none());
+    }
+}
\ No newline at end of file
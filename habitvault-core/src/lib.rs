@@ -0,0 +1,42 @@
+//! The data model and encrypted storage behind HabitVault, usable without
+//! GTK.
+//!
+//! A vault holds [`HabitData`] encrypted with a random data key, which is in
+//! turn wrapped once for each set of credentials that can unlock it.
+//! [`SecureStorage`] reads and writes vault files; unlocking one gives the
+//! data and a [`Session`] that can save changes without running the KDF
+//! again.
+//!
+//! ```no_run
+//! use habitvault_core::{Credentials, SecureStorage, encrypt_vault};
+//! use zeroize::Zeroizing;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let storage = SecureStorage::with_data_dir("/path/to/vault")?;
+//! let credentials = Credentials::new(Zeroizing::new("correct horse battery staple".to_string()), None)?;
+//! let (mut data, session, _) = storage.unlock_with_recovery(&credentials)?;
+//!
+//! let today = data.today();
+//! for habit in data.habits.clone() {
+//!     data.mark_completed(&habit.id, today, None);
+//! }
+//! storage.write_vault(&encrypt_vault(&data, &session)?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod clock;
+pub mod csv_io;
+pub mod encryption;
+pub mod habit;
+pub mod lockout;
+pub mod migrations;
+mod session_cache;
+pub mod settings;
+pub mod stats;
+pub mod storage;
+pub mod vault;
+
+pub use encryption::{Credentials, Encryption};
+pub use habit::{Habit, HabitCompletion, HabitData};
+pub use storage::{SecureStorage, Session, StorageError, encrypt_vault};
\ No newline at end of file
@@ -0,0 +1,106 @@
+//! Delays after failed unlock attempts.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Failed attempts in a row that are allowed without any delay.
+const FREE_ATTEMPTS: u32 = 3;
+
+/// Delay after the first failure past `FREE_ATTEMPTS`, doubling with each
+/// further failure up to `MAX_DELAY_SECONDS`.
+const BASE_DELAY_SECONDS: i64 = 5;
+const MAX_DELAY_SECONDS: i64 = 60 * 60;
+
+/// Consecutive failed unlock attempts, kept unencrypted next to the vault so
+/// that restarting the app doesn't reset the delay. This only slows down
+/// guessing through the app; a copy of the vault file is protected by the
+/// KDF alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnlockAttempts {
+    pub failures: u32,
+    pub last_failure: Option<DateTime<Utc>>,
+}
+
+impl UnlockAttempts {
+    pub fn record_failure(&mut self, now: DateTime<Utc>) {
+        self.failures = self.failures.saturating_add(1);
+        self.last_failure = Some(now);
+    }
+    
+    /// How long to wait after the last failure before trying again.
+    pub fn delay(&self) -> Duration {
+        if self.failures < FREE_ATTEMPTS {
+            return Duration::zero();
+        }
+        let doublings = (self.failures - FREE_ATTEMPTS).min(20);
+        Duration::seconds((BASE_DELAY_SECONDS << doublings).min(MAX_DELAY_SECONDS))
+    }
+    
+    /// Time left until the next attempt is allowed, if any. Never more than
+    /// the full delay, so setting the clock back can't extend it.
+    pub fn retry_after(&self, now: DateTime<Utc>) -> Option<std::time::Duration> {
+        let last_failure = self.last_failure?;
+        let remaining = (last_failure + self.delay() - now).min(self.delay());
+        remaining.to_std().ok().filter(|remaining| !remaining.is_zero())
+    }
+}
+
+/// A wait in words, rounded up to whole seconds or minutes.
+pub fn describe_wait(wait: std::time::Duration) -> String {
+    let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    match seconds {
+        1 => "1 second".to_string(),
+        0..=90 => format!("{} seconds", seconds),
+        _ => format!("{} minutes", seconds.div_ceil(60)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn attempts(failures: u32, last_failure: DateTime<Utc>) -> UnlockAttempts {
+        UnlockAttempts { failures, last_failure: Some(last_failure) }
+    }
+    
+    #[test]
+    fn delay_doubles_after_the_free_attempts_up_to_an_hour() {
+        let now = Utc::now();
+        let delays: Vec<i64> = (0..=8).map(|failures| attempts(failures, now).delay().num_seconds()).collect();
+        assert_eq!(delays, [0, 0, 0, 5, 10, 20, 40, 80, 160]);
+        assert_eq!(attempts(13, now).delay(), Duration::hours(1));
+        assert_eq!(attempts(u32::MAX, now).delay(), Duration::hours(1));
+    }
+    
+    #[test]
+    fn retry_after_counts_down_from_the_last_failure() {
+        let now = Utc::now();
+        assert_eq!(UnlockAttempts::default().retry_after(now), None);
+        assert_eq!(attempts(2, now).retry_after(now), None);
+        
+        let failed = attempts(4, now);
+        assert_eq!(failed.retry_after(now), Some(std::time::Duration::from_secs(10)));
+        assert_eq!(failed.retry_after(now + Duration::seconds(4)), Some(std::time::Duration::from_secs(6)));
+        assert_eq!(failed.retry_after(now + Duration::seconds(10)), None);
+        // A clock set back doesn't make the wait any longer
+        assert_eq!(failed.retry_after(now - Duration::days(1)), Some(std::time::Duration::from_secs(10)));
+    }
+    
+    #[test]
+    fn record_failure_counts_and_stamps() {
+        let now = Utc::now();
+        let mut attempts = UnlockAttempts::default();
+        attempts.record_failure(now);
+        attempts.record_failure(now);
+        assert_eq!(attempts.failures, 2);
+        assert_eq!(attempts.last_failure, Some(now));
+    }
+    
+    #[test]
+    fn waits_are_rounded_up() {
+        assert_eq!(describe_wait(std::time::Duration::from_millis(500)), "1 second");
+        assert_eq!(describe_wait(std::time::Duration::from_millis(4100)), "5 seconds");
+        assert_eq!(describe_wait(std::time::Duration::from_secs(90)), "90 seconds");
+        assert_eq!(describe_wait(std::time::Duration::from_secs(91)), "2 minutes");
+    }
+}
\ No newline at end of file
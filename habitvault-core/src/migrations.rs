@@ -0,0 +1,171 @@
+//! Versioning of the habit data schema and upgrades from older versions.
+
+use crate::habit::HabitData;
+use crate::storage::StorageError;
+use serde_json::{Map, Value};
+use zeroize::Zeroizing;
+
+/// Version of the `HabitData` schema written by this build. Bump it and add
+/// a step to `MIGRATIONS` whenever a change to `Habit`, `HabitCompletion` or
+/// `HabitData` would stop older vaults from deserializing as-is.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// `MIGRATIONS[n]` upgrades data from schema version `n` to `n + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); CURRENT_SCHEMA_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+];
+
+/// Serializes `data` tagged with the current schema version. The buffer is
+/// wiped once it has been encrypted and dropped.
+pub fn to_versioned_json(data: &HabitData) -> Result<Zeroizing<Vec<u8>>, StorageError> {
+    let mut value = serde_json::to_value(data).map_err(StorageError::Encoding)?;
+    if let Value::Object(object) = &mut value {
+        object.insert(SCHEMA_VERSION_KEY.to_string(), Value::from(CURRENT_SCHEMA_VERSION));
+    }
+    Ok(Zeroizing::new(serde_json::to_vec(&value).map_err(StorageError::Encoding)?))
+}
+
+/// Deserializes decrypted vault contents, upgrading them from whatever schema
+/// version they were written with. Data without a version predates schema
+/// versioning and is treated as version 0.
+pub fn from_versioned_json(bytes: &[u8]) -> Result<HabitData, StorageError> {
+    let mut value: Value = serde_json::from_slice(bytes)
+        .map_err(|e| StorageError::Schema(e.to_string()))?;
+    let object = value.as_object_mut()
+        .ok_or_else(|| StorageError::Schema("not a JSON object".to_string()))?;
+    
+    let version = match object.remove(SCHEMA_VERSION_KEY) {
+        Some(version) => version.as_u64()
+            .ok_or_else(|| StorageError::Schema("invalid schema version".to_string()))?,
+        None => 0,
+    };
+    // A version too large for a u32 is newer too, rather than wrapping to an old one
+    let version = u32::try_from(version).ok()
+        .filter(|version| *version <= CURRENT_SCHEMA_VERSION)
+        .ok_or_else(|| StorageError::NewerVersion(format!("Habit data schema {}", version)))?;
+    
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(object);
+    }
+    
+    serde_json::from_value(value).map_err(|e| StorageError::Schema(e.to_string()))
+}
+
+fn for_each_in(object: &mut Map<String, Value>, key: &str, f: impl Fn(&mut Map<String, Value>)) {
+    if let Some(Value::Array(items)) = object.get_mut(key) {
+        for item in items.iter_mut().filter_map(Value::as_object_mut) {
+            f(item);
+        }
+    }
+}
+
+/// Version 1 added schedules and quantities to habits, and logged values and
+/// time zone offsets to completions.
+fn migrate_v0_to_v1(data: &mut Map<String, Value>) {
+    for_each_in(data, "habits", |habit| {
+        habit.entry("schedule").or_insert_with(|| serde_json::json!({ "kind": "daily" }));
+        habit.entry("quantity").or_insert(Value::Null);
+    });
+    for_each_in(data, "completions", |completion| {
+        completion.entry("value").or_insert(Value::Null);
+        completion.entry("utc_offset_seconds").or_insert(Value::Null);
+    });
+}
+
+/// Version 2 added a mood to completions. Older builds would drop it on save,
+/// so it gets its own version even though the field is optional.
+fn migrate_v1_to_v2(data: &mut Map<String, Value>) {
+    for_each_in(data, "completions", |completion| {
+        completion.entry("mood").or_insert(Value::Null);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::habit::{Quantity, Schedule};
+    use chrono::NaiveDate;
+    
+    /// Written before schema versioning: no schedules, quantities, values,
+    /// time zone offsets or moods.
+    const V0: &str = r#"{
+        "habits": [{
+            "id": "habit_1", "name": "Read", "description": "A chapter a day",
+            "created_at": "2024-01-01T08:00:00Z", "target_days_per_week": 5,
+            "streak": 2, "longest_streak": 9
+        }],
+        "completions": [
+            {"habit_id": "habit_1", "date": "2024-01-02", "completed_at": "2024-01-02T21:00:00Z", "notes": "Good one"},
+            {"habit_id": "habit_1", "date": "2024-01-03", "completed_at": "2024-01-03T21:00:00Z", "notes": null}
+        ]
+    }"#;
+    
+    /// Schedules, quantities and offsets, but no moods yet.
+    const V1: &str = r#"{
+        "schema_version": 1,
+        "habits": [{
+            "id": "habit_2", "name": "Water", "description": "",
+            "created_at": "2024-06-01T08:00:00Z", "target_days_per_week": 7,
+            "schedule": {"kind": "weekdays", "days": ["Mon", "Wed"]},
+            "quantity": {"unit": "glasses", "daily_target": 8.0},
+            "streak": 0, "longest_streak": 3
+        }],
+        "completions": [
+            {"habit_id": "habit_2", "date": "2024-06-03", "completed_at": "2024-06-03T19:00:00Z",
+             "utc_offset_seconds": 7200, "notes": null, "value": 5.0}
+        ]
+    }"#;
+    
+    #[test]
+    fn v0_data_is_migrated() {
+        let data = from_versioned_json(V0.as_bytes()).unwrap();
+        let habit = &data.habits[0];
+        assert_eq!((habit.name.as_str(), habit.target_days_per_week, habit.longest_streak), ("Read", 5, 9));
+        assert_eq!(habit.schedule, Schedule::Daily);
+        assert!(habit.quantity.is_none());
+        
+        assert_eq!(data.completions.len(), 2);
+        let completion = &data.completions[0];
+        assert_eq!(completion.date, NaiveDate::from_ymd_opt(2024, 1, 2).unwrap());
+        assert_eq!(completion.notes.as_deref(), Some("Good one"));
+        assert_eq!((completion.value, completion.utc_offset_seconds, completion.mood), (None, None, None));
+    }
+    
+    #[test]
+    fn v1_data_is_migrated() {
+        let data = from_versioned_json(V1.as_bytes()).unwrap();
+        let habit = &data.habits[0];
+        assert_eq!(habit.schedule, Schedule::Weekdays { days: vec![chrono::Weekday::Mon, chrono::Weekday::Wed] });
+        assert_eq!(habit.quantity, Some(Quantity { unit: "glasses".to_string(), daily_target: 8.0 }));
+        
+        let completion = &data.completions[0];
+        assert_eq!((completion.value, completion.utc_offset_seconds, completion.mood), (Some(5.0), Some(7200), None));
+    }
+    
+    #[test]
+    fn migrated_data_is_saved_as_the_current_version() {
+        let data = from_versioned_json(V0.as_bytes()).unwrap();
+        let saved: Value = serde_json::from_slice(&to_versioned_json(&data).unwrap()).unwrap();
+        assert_eq!(saved[SCHEMA_VERSION_KEY], CURRENT_SCHEMA_VERSION);
+        assert_eq!(saved["completions"][0]["mood"], Value::Null);
+        
+        let reloaded = from_versioned_json(&to_versioned_json(&data).unwrap()).unwrap();
+        assert_eq!(reloaded.completions.len(), 2);
+    }
+    
+    #[test]
+    fn newer_schema_is_refused() {
+        let newer = V1.replace("\"schema_version\": 1", &format!("\"schema_version\": {}", CURRENT_SCHEMA_VERSION + 1));
+        assert!(matches!(from_versioned_json(newer.as_bytes()), Err(StorageError::NewerVersion(_))));
+        
+        // Would be version 1 if cut down to 32 bits
+        let huge = V1.replace("\"schema_version\": 1", &format!("\"schema_version\": {}", (1u64 << 32) + 1));
+        assert!(matches!(from_versioned_json(huge.as_bytes()), Err(StorageError::NewerVersion(_))));
+        
+        let invalid = V1.replace("\"schema_version\": 1", "\"schema_version\": \"one\"");
+        assert!(matches!(from_versioned_json(invalid.as_bytes()), Err(StorageError::Schema(_))));
+    }
+}
\ No newline at end of file
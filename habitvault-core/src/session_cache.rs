@@ -0,0 +1,89 @@
+//! Unlocked sessions kept for a limited time, so a status bar or script can
+//! read the vault without asking for the password on every run.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::{Zeroize, Zeroizing};
+
+/// A vault's data key and when it stops being accepted. It is kept in the
+/// user's runtime directory, which is normally in memory, private to the
+/// user and cleared at logout. Anyone who can read the file can decrypt the
+/// vault until it expires or is removed, whatever the password.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CachedKey {
+    pub data_key: [u8; 32],
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Drop for CachedKey {
+    fn drop(&mut self) {
+        self.data_key.zeroize();
+    }
+}
+
+/// Where the key of the vault `vault_id` is cached. `None` for vaults
+/// without an id yet, and on systems without a runtime directory where
+/// sessions can't be cached.
+#[cfg(unix)]
+pub(crate) fn cache_path(vault_id: &[u8]) -> Option<PathBuf> {
+    if vault_id.is_empty() {
+        return None;
+    }
+    let name: String = vault_id.iter().map(|byte| format!("{:02x}", byte)).collect();
+    dirs::runtime_dir().map(|dir| dir.join("habitvault").join(format!("{}.session", name)))
+}
+
+/// Sessions are only cached where the file can be made private to the user.
+#[cfg(not(unix))]
+pub(crate) fn cache_path(_vault_id: &[u8]) -> Option<PathBuf> {
+    None
+}
+
+/// The cached key, unless it is missing, unreadable or expired. Expired
+/// keys are removed.
+pub(crate) fn read(path: &Path) -> std::io::Result<Option<CachedKey>> {
+    let json = match fs::read(path) {
+        Ok(json) => Zeroizing::new(json),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let Ok(cached) = serde_json::from_slice::<CachedKey>(&json) else {
+        return Ok(None);
+    };
+    if cached.expires_at <= Utc::now() {
+        remove(path)?;
+        return Ok(None);
+    }
+    Ok(Some(cached))
+}
+
+/// Writes the key readable by the user alone, replacing any earlier one.
+#[cfg(unix)]
+pub(crate) fn write(path: &Path, cached: &CachedKey) -> std::io::Result<()> {
+    use std::fs::{DirBuilder, OpenOptions};
+    use std::io::Write;
+    use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+    
+    if let Some(dir) = path.parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    
+    let json = Zeroizing::new(serde_json::to_vec(cached)?);
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    file.write_all(&json)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write(_path: &Path, _cached: &CachedKey) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Sessions can't be cached on this system"))
+}
+
+/// Removes the cached key. A key that is already gone isn't an error.
+pub(crate) fn remove(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,51 @@
+//! Unencrypted preferences stored next to the vault.
+
+use crate::encryption::KdfProfile;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Preferences that are needed before the vault is unlocked, so they are kept
+/// unencrypted next to it. Nothing about the user's habits belongs here.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    /// Strength used whenever a key is wrapped. `None` until the machine has
+    /// been benchmarked.
+    #[serde(default)]
+    pub kdf_profile: Option<KdfProfile>,
+    /// Minutes without input before the vault locks itself. 0 disables it.
+    #[serde(default = "default_auto_lock_minutes")]
+    pub auto_lock_minutes: u32,
+    /// Also lock when the window is minimized or the screen locks.
+    #[serde(default)]
+    pub lock_when_hidden: bool,
+    /// Where the keyfile was last loaded from, offered again at the next
+    /// unlock. Only the location is kept, never the contents.
+    #[serde(default)]
+    pub keyfile_path: Option<PathBuf>,
+    /// Delete the vault after this many failed unlock attempts in a row. 0
+    /// disables it.
+    #[serde(default)]
+    pub wipe_after_failures: u32,
+}
+
+/// Choices offered for `auto_lock_minutes` in the settings dialog.
+pub const AUTO_LOCK_CHOICES: [u32; 5] = [0, 1, 5, 15, 30];
+
+/// Choices offered for `wipe_after_failures` in the settings dialog.
+pub const WIPE_AFTER_CHOICES: [u32; 4] = [0, 5, 10, 20];
+
+fn default_auto_lock_minutes() -> u32 {
+    5
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            kdf_profile: None,
+            auto_lock_minutes: default_auto_lock_minutes(),
+            lock_when_hidden: false,
+            keyfile_path: None,
+            wipe_after_failures: 0,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,462 @@
+//! Statistics computed from `HabitData`, such as completion rates and
+//! streak history.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use std::collections::HashSet;
+use crate::habit::{month_start, week_start, Habit, HabitData, Schedule};
+
+/// Windows, in days up to and including today, that completion rates are
+/// reported over.
+pub const RATE_WINDOWS: [u32; 4] = [7, 30, 90, 365];
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun,
+];
+
+/// One unbroken streak, measured in the habit's streak unit: days, or weeks
+/// and months for weekly and monthly goals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreakRun {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub length: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct HabitStats {
+    /// Share of the expected completions that were done, from 0.0 to 1.0,
+    /// for each of `RATE_WINDOWS`. None if nothing was expected in that
+    /// window.
+    pub completion_rates: Vec<(u32, Option<f64>)>,
+    /// Completed days per week since the habit was started.
+    pub average_per_week: f64,
+    /// Completion rate on each weekday the habit was expected on.
+    pub weekday_rates: Vec<(Weekday, f64)>,
+    pub total_completions: u32,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Every streak so far, oldest first. The last one may still be going.
+    pub streak_history: Vec<StreakRun>,
+}
+
+impl HabitStats {
+    /// The weekday with the highest completion rate, the earliest in the
+    /// week on a tie.
+    pub fn best_weekday(&self) -> Option<(Weekday, f64)> {
+        self.weekday_rates.iter().copied()
+            .fold(None, |best, day| match best {
+                Some(best) if best.1 >= day.1 => Some(best),
+                _ => Some(day),
+            })
+    }
+    
+    /// The weekday with the lowest completion rate, the earliest in the week
+    /// on a tie.
+    pub fn worst_weekday(&self) -> Option<(Weekday, f64)> {
+        self.weekday_rates.iter().copied()
+            .fold(None, |worst, day| match worst {
+                Some(worst) if worst.1 <= day.1 => Some(worst),
+                _ => Some(day),
+            })
+    }
+}
+
+/// Weeks, counting back from the current one, that trend charts can show.
+pub const TREND_RANGES: [u32; 4] = [4, 12, 26, 52];
+
+/// The days that count for one habit as of `today`: from when it was
+/// created, or from its first completion if that is earlier. Today is still
+/// open, so it only counts once it has been done.
+struct HabitDays<'a> {
+    habit: &'a Habit,
+    completed: HashSet<NaiveDate>,
+    first_day: NaiveDate,
+    today: NaiveDate,
+}
+
+impl<'a> HabitDays<'a> {
+    fn new(data: &'a HabitData, habit_id: &str, today: NaiveDate) -> Option<Self> {
+        let habit = data.get_habit_by_id(habit_id)?;
+        let completed: HashSet<NaiveDate> = data.completed_dates(habit_id).into_iter().collect();
+        let created = data.clock().local_date(habit.created_at);
+        let first_day = completed.iter().copied().fold(created, NaiveDate::min).min(today);
+        Some(Self { habit, completed, first_day, today })
+    }
+    
+    /// Counted days from `from` to `to` inclusive.
+    fn counted_days(&self, from: NaiveDate, to: NaiveDate) -> impl Iterator<Item = NaiveDate> + '_ {
+        let to = to.min(self.today);
+        from.max(self.first_day).iter_days()
+            .take_while(move |date| *date <= to)
+            .filter(|date| *date < self.today || self.completed.contains(date))
+    }
+    
+    /// Share of the completions expected from `from` to `to` that were done,
+    /// or None if nothing was expected.
+    fn rate_between(&self, from: NaiveDate, to: NaiveDate) -> Option<f64> {
+        let (done, expected) = self.counted_days(from, to)
+            .map(|date| (self.completed.contains(&date), expected_on(self.habit, date)))
+            .fold((0.0, 0.0), |(done, expected), (is_done, expected_today)| {
+                // Goals are met by any days, so only scheduled habits
+                // ignore completions on days that weren't due
+                let counts = is_done && (expected_today > 0.0 || is_goal(self.habit));
+                (done + if counts { 1.0 } else { 0.0 }, expected + expected_today)
+            });
+        (expected > 0.0).then(|| (done / expected).min(1.0))
+    }
+}
+
+/// Statistics for one habit as of `today`. See `HabitDays` for which days
+/// count.
+pub fn habit_stats(data: &HabitData, habit_id: &str, today: NaiveDate) -> Option<HabitStats> {
+    let days = HabitDays::new(data, habit_id, today)?;
+    let habit = days.habit;
+    
+    let completion_rates = RATE_WINDOWS.iter()
+        .map(|window| (*window, days.rate_between(today - Duration::days(*window as i64 - 1), today)))
+        .collect();
+    
+    let weekday_rates = WEEKDAYS.iter()
+        .filter_map(|weekday| {
+            let (done, counted) = days.counted_days(days.first_day, today)
+                .filter(|date| date.weekday() == *weekday && (is_goal(habit) || habit.is_due_on(*date)))
+                .fold((0, 0), |(done, counted), date| {
+                    (done + u32::from(days.completed.contains(&date)), counted + 1)
+                });
+            (counted > 0).then(|| (*weekday, done as f64 / counted as f64))
+        })
+        .collect();
+    
+    let total_completions = days.completed.len() as u32;
+    let active_days = (today - days.first_day).num_days() + 1;
+    let average_per_week = total_completions as f64 * 7.0 / active_days.max(7) as f64;
+    
+    let streak_history = streak_history(habit, &days.completed, days.first_day, today);
+    let current_streak = data.current_streak(habit_id, today);
+    let longest_streak = streak_history.iter()
+        .map(|run| run.length)
+        .max()
+        .unwrap_or(0)
+        .max(habit.longest_streak)
+        .max(current_streak);
+    
+    Some(HabitStats {
+        completion_rates,
+        average_per_week,
+        weekday_rates,
+        total_completions,
+        current_streak,
+        longest_streak,
+        streak_history,
+    })
+}
+
+/// Mondays of the last `weeks` weeks up to the current one, oldest first.
+fn recent_weeks(today: NaiveDate, weeks: u32) -> impl Iterator<Item = NaiveDate> {
+    let current = week_start(today);
+    (0..weeks as i64).rev().map(move |back| current - Duration::weeks(back))
+}
+
+/// Completion rate in each of the last `weeks` weeks, keyed by Monday and
+/// oldest first. Without a habit id this is the average over all habits
+/// that had something expected that week. None for weeks where nothing was.
+pub fn weekly_rates(data: &HabitData, habit_id: Option<&str>, today: NaiveDate, weeks: u32) -> Vec<(NaiveDate, Option<f64>)> {
+    let habits: Vec<HabitDays> = match habit_id {
+        Some(habit_id) => HabitDays::new(data, habit_id, today).into_iter().collect(),
+        None => data.habits.iter()
+            .filter_map(|habit| HabitDays::new(data, &habit.id, today))
+            .collect(),
+    };
+    
+    recent_weeks(today, weeks)
+        .map(|monday| {
+            let rates: Vec<f64> = habits.iter()
+                .filter_map(|days| days.rate_between(monday, monday + Duration::days(6)))
+                .collect();
+            let average = (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64);
+            (monday, average)
+        })
+        .collect()
+}
+
+/// Average amount logged per counted day in each of the last `weeks` weeks
+/// for a quantitative habit, keyed by Monday and oldest first. None for
+/// weeks without counted days.
+pub fn weekly_amounts(data: &HabitData, habit_id: &str, today: NaiveDate, weeks: u32) -> Vec<(NaiveDate, Option<f64>)> {
+    let Some(days) = HabitDays::new(data, habit_id, today) else {
+        return Vec::new();
+    };
+    
+    recent_weeks(today, weeks)
+        .map(|monday| {
+            let values: Vec<f64> = days.counted_days(monday, monday + Duration::days(6))
+                .map(|date| data.value_on_date(habit_id, date))
+                .collect();
+            let average = (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64);
+            (monday, average)
+        })
+        .collect()
+}
+
+fn is_goal(habit: &Habit) -> bool {
+    habit.is_weekly_goal() || habit.is_monthly_goal()
+}
+
+/// Completions expected on `date`. Goals spread their target evenly over
+/// the days of the week or month.
+fn expected_on(habit: &Habit, date: NaiveDate) -> f64 {
+    if habit.is_weekly_goal() {
+        habit.target_days_per_week as f64 / 7.0
+    } else if let Schedule::TimesPerMonth { times } = habit.schedule {
+        times as f64 / days_in_month(date) as f64
+    } else if habit.is_due_on(date) {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn days_in_month(date: NaiveDate) -> i64 {
+    let start = month_start(date);
+    (month_start(start + Duration::days(31)) - start).num_days()
+}
+
+/// Splits the time since `first_day` into the units streaks are counted in
+/// and groups the consecutive ones that were met, matching
+/// `HabitData::current_streak`.
+fn streak_history(habit: &Habit, completed: &HashSet<NaiveDate>, first_day: NaiveDate, today: NaiveDate) -> Vec<StreakRun> {
+    let done_between = |start: NaiveDate, end: NaiveDate| {
+        completed.iter().filter(|date| **date >= start && **date <= end).count() as u32
+    };
+    
+    // (first day, last day, met) of each unit in order
+    let mut units: Vec<(NaiveDate, NaiveDate, bool)> = Vec::new();
+    if habit.is_weekly_goal() {
+        let target = (habit.target_days_per_week as u32).max(1);
+        let mut start = week_start(first_day);
+        while start <= today {
+            let end = start + Duration::days(6);
+            units.push((start, end, done_between(start, end) >= target));
+            start += Duration::days(7);
+        }
+    } else if let Schedule::TimesPerMonth { times } = habit.schedule {
+        let mut start = month_start(first_day);
+        while start <= today {
+            let end = start + Duration::days(days_in_month(start) - 1);
+            units.push((start, end, done_between(start, end) >= times.max(1)));
+            start = end + Duration::days(1);
+        }
+    } else {
+        units = first_day.iter_days()
+            .take_while(|date| *date <= today)
+            .filter(|date| habit.schedule.is_scheduled(*date))
+            .map(|date| (date, date, completed.contains(&date)))
+            .collect();
+    }
+    
+    // The current unit is still open, so it extends a streak once met but
+    // never ends one
+    if units.last().is_some_and(|unit| !unit.2) {
+        units.pop();
+    }
+    
+    let mut runs: Vec<StreakRun> = Vec::new();
+    let mut in_run = false;
+    for (start, end, met) in units {
+        match (met, in_run, runs.last_mut()) {
+            (true, true, Some(run)) => {
+                run.end = end.min(today);
+                run.length += 1;
+            }
+            (true, _, _) => runs.push(StreakRun { start, end: end.min(today), length: 1 }),
+            (false, _, _) => {}
+        }
+        in_run = met;
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::habit::Quantity;
+    use chrono::{FixedOffset, TimeZone, Utc};
+    
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+    
+    /// Data as seen at noon UTC on `today`, with one habit created on
+    /// `created`.
+    fn data_with_habit(today: NaiveDate, created: NaiveDate, schedule: Schedule, target_days_per_week: u8) -> HabitData {
+        let at_noon = |date: NaiveDate| Utc.from_utc_datetime(&date.and_hms_opt(12, 0, 0).unwrap());
+        let mut data = HabitData::with_clock(Clock::Fixed {
+            now: at_noon(today),
+            offset: FixedOffset::east_opt(0).unwrap(),
+        });
+        data.add_habit(Habit {
+            id: "h".to_string(),
+            name: "Habit".to_string(),
+            description: String::new(),
+            created_at: at_noon(created),
+            target_days_per_week,
+            schedule,
+            quantity: None,
+            streak: 0,
+            longest_streak: 0,
+        });
+        data
+    }
+    
+    #[test]
+    fn daily_rates_and_streaks() {
+        // Monday 3rd to Sunday 16th, missing the 6th and 10th
+        let today = date(16);
+        let mut data = data_with_habit(today, date(3), Schedule::Daily, 7);
+        for day in (3..=16).filter(|day| *day != 6 && *day != 10) {
+            data.mark_completed("h", date(day), None);
+        }
+        
+        let stats = habit_stats(&data, "h", today).unwrap();
+        assert_eq!(stats.completion_rates[0], (7, Some(6.0 / 7.0)));
+        assert_eq!(stats.completion_rates[1], (30, Some(12.0 / 14.0)));
+        assert_eq!(stats.total_completions, 12);
+        assert_eq!(stats.average_per_week, 6.0);
+        assert_eq!(stats.streak_history, vec![
+            StreakRun { start: date(3), end: date(5), length: 3 },
+            StreakRun { start: date(7), end: date(9), length: 3 },
+            StreakRun { start: date(11), end: date(16), length: 6 },
+        ]);
+        assert_eq!(stats.current_streak, 6);
+        assert_eq!(stats.longest_streak, 6);
+        
+        // Missed on a Thursday and a Monday
+        assert_eq!(stats.best_weekday(), Some((Weekday::Tue, 1.0)));
+        assert_eq!(stats.worst_weekday(), Some((Weekday::Mon, 0.5)));
+    }
+    
+    #[test]
+    fn today_only_counts_once_done() {
+        let today = date(16);
+        let mut data = data_with_habit(today, date(14), Schedule::Daily, 7);
+        data.mark_completed("h", date(14), None);
+        data.mark_completed("h", date(15), None);
+        
+        let stats = habit_stats(&data, "h", today).unwrap();
+        assert_eq!(stats.completion_rates[0], (7, Some(1.0)));
+        assert_eq!(stats.streak_history.last().map(|run| run.length), Some(2));
+        assert_eq!(stats.current_streak, 2);
+        
+        data.mark_completed("h", today, None);
+        let stats = habit_stats(&data, "h", today).unwrap();
+        assert_eq!(stats.streak_history.last().map(|run| run.end), Some(today));
+        assert_eq!(stats.current_streak, 3);
+    }
+    
+    #[test]
+    fn new_habit_has_nothing_expected_yet() {
+        let today = date(16);
+        let data = data_with_habit(today, today, Schedule::Daily, 7);
+        
+        let stats = habit_stats(&data, "h", today).unwrap();
+        assert!(stats.completion_rates.iter().all(|(_, rate)| rate.is_none()));
+        assert!(stats.weekday_rates.is_empty());
+        assert_eq!(stats.best_weekday(), None);
+        assert!(stats.streak_history.is_empty());
+        assert_eq!(stats.average_per_week, 0.0);
+    }
+    
+    #[test]
+    fn scheduled_days_only() {
+        // Mondays, Wednesdays and Fridays from Monday 3rd; the Wednesdays
+        // were missed and an unscheduled Tuesday was done anyway, which
+        // neither counts nor breaks anything
+        let today = date(16);
+        let schedule = Schedule::Weekdays { days: vec![Weekday::Mon, Weekday::Wed, Weekday::Fri] };
+        let mut data = data_with_habit(today, date(3), schedule, 7);
+        for day in [3, 4, 7, 10, 14] {
+            data.mark_completed("h", date(day), None);
+        }
+        
+        let stats = habit_stats(&data, "h", today).unwrap();
+        assert_eq!(stats.completion_rates[1], (30, Some(4.0 / 6.0)));
+        assert_eq!(stats.weekday_rates, vec![
+            (Weekday::Mon, 1.0),
+            (Weekday::Wed, 0.0),
+            (Weekday::Fri, 1.0),
+        ]);
+        assert_eq!(stats.worst_weekday(), Some((Weekday::Wed, 0.0)));
+        assert_eq!(stats.streak_history.iter().map(|run| run.length).collect::<Vec<_>>(), vec![1, 2, 1]);
+        assert_eq!(stats.current_streak, 1);
+    }
+    
+    #[test]
+    fn weekly_goal_streaks_in_weeks() {
+        // Three days a week: met in the weeks of the 3rd and 10th, and the
+        // current week is under way
+        let today = date(19);
+        let mut data = data_with_habit(today, date(3), Schedule::Daily, 3);
+        for day in [3, 4, 5, 10, 12, 14, 17] {
+            data.mark_completed("h", date(day), None);
+        }
+        
+        let stats = habit_stats(&data, "h", today).unwrap();
+        assert_eq!(stats.streak_history, vec![StreakRun { start: date(3), end: date(16), length: 2 }]);
+        assert_eq!(stats.current_streak, 2);
+        
+        // The 13th to 18th at 3/7 a day, with the 14th and 17th done
+        let rate = stats.completion_rates[0].1.unwrap();
+        assert!((rate - 2.0 / (6.0 * 3.0 / 7.0)).abs() < 1e-9);
+        // Ahead of the goal over the 16 days since the 3rd, capped at fully done
+        assert_eq!(stats.completion_rates[1], (30, Some(1.0)));
+    }
+    
+    #[test]
+    fn weekly_rates_per_habit_and_overall() {
+        // Weeks of the 3rd, 10th and the current week of the 17th
+        let today = date(19);
+        let mut data = data_with_habit(today, date(3), Schedule::Daily, 7);
+        for day in [3, 4, 5, 6, 7, 8, 9, 10, 17, 18] {
+            data.mark_completed("h", date(day), None);
+        }
+        
+        let rates = weekly_rates(&data, Some("h"), today, 4);
+        assert_eq!(rates, vec![
+            (date(3) - Duration::weeks(1), None),
+            (date(3), Some(1.0)),
+            (date(10), Some(1.0 / 7.0)),
+            (date(17), Some(1.0)),
+        ]);
+        
+        // A second habit started on the 10th and done every day since only
+        // counts from its first week on
+        let mut other = data.get_habit_by_id("h").unwrap().clone();
+        other.id = "g".to_string();
+        other.created_at = Utc.from_utc_datetime(&date(10).and_hms_opt(12, 0, 0).unwrap());
+        data.add_habit(other);
+        for day in 10..=18 {
+            data.mark_completed("g", date(day), None);
+        }
+        let rates = weekly_rates(&data, None, today, 3);
+        assert_eq!(rates[0], (date(3), Some(1.0)));
+        assert_eq!(rates[1], (date(10), Some((1.0 / 7.0 + 1.0) / 2.0)));
+    }
+    
+    #[test]
+    fn weekly_amounts_average_counted_days() {
+        let today = date(16);
+        let mut data = data_with_habit(today, date(10), Schedule::Daily, 7);
+        data.get_habit_by_id_mut("h").unwrap().quantity = Some(Quantity {
+            unit: "pages".to_string(),
+            daily_target: 10.0,
+        });
+        data.log_value("h", date(10), 4.0);
+        data.log_value("h", date(11), 10.0);
+        // Partial today doesn't count until the target is reached
+        data.log_value("h", today, 3.0);
+        
+        let amounts = weekly_amounts(&data, "h", today, 2);
+        assert_eq!(amounts, vec![(date(3), None), (date(10), Some(14.0 / 6.0))]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,1025 @@
+//! Reading and writing vault files, and unlocking them into a `Session`.
+
+use crate::encryption::{
+    CipherId, Credentials, CryptoError, Encryption, EncryptedData, KdfParams, KdfProfile,
+    generate_key, generate_keyfile, generate_nonce, generate_recovery_key, generate_salt,
+    generate_vault_id, keyfile_digest,
+};
+use crate::habit::HabitData;
+use crate::lockout::{self, UnlockAttempts};
+use crate::migrations;
+use crate::session_cache::{self, CachedKey};
+use crate::settings::Settings;
+use crate::vault::{self, KeySlot, KeySlotKind, VaultHeader, WrappedKey};
+use chrono::Utc;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Number of previous encrypted versions kept next to the data file.
+const GENERATIONS: usize = 5;
+
+/// Why a vault couldn't be read or written, detailed enough for the UI to
+/// tell a mistyped password from a damaged file.
+#[derive(Debug)]
+pub enum StorageError {
+    /// The credentials don't unlock the slot of this kind.
+    WrongCredentials(KeySlotKind),
+    /// The vault is unlocked with a different kind of credentials.
+    WrongUnlockMethod(KeySlotKind),
+    NoRecoveryKey,
+    /// Unlocking was attempted with no vault on disk.
+    NoVault,
+    /// The file isn't a vault, or its header is truncated or unreadable.
+    Corrupt(String),
+    /// The credentials were right but the payload failed authentication, so
+    /// the header or ciphertext was changed after it was written.
+    Tampered,
+    /// The payload decrypted but doesn't hold habit data this version can
+    /// read.
+    Schema(String),
+    /// The vault format or data schema is newer than this build.
+    NewerVersion(String),
+    /// Too many failed attempts; the next one is allowed after this long.
+    LockedOut(std::time::Duration),
+    /// Too many failed attempts with `wipe_after_failures` set, so all data
+    /// was deleted.
+    Wiped,
+    /// Another process holds the vault lock, see `SecureStorage::lock_vault`.
+    VaultBusy,
+    Keyfile(PathBuf, std::io::Error),
+    Io(std::io::Error),
+    Encoding(serde_json::Error),
+    Crypto(CryptoError),
+    Csv(csv::Error),
+}
+
+impl StorageError {
+    /// Damage to the file rather than a problem with what the user entered.
+    /// Earlier generations or a backup may still be readable. Unusable KDF
+    /// parameters can only come from a damaged header.
+    pub fn is_corruption(&self) -> bool {
+        matches!(
+            self,
+            StorageError::Corrupt(_) | StorageError::Tampered | StorageError::Schema(_)
+                | StorageError::Crypto(CryptoError::InvalidKdfParams(_))
+        )
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::WrongCredentials(kind) => write!(f, "{}", match kind {
+                KeySlotKind::Password => "Incorrect password",
+                KeySlotKind::RecoveryKey => "Incorrect recovery key",
+                KeySlotKind::PasswordAndKeyfile => "Incorrect password or keyfile",
+                KeySlotKind::Keyfile => "Incorrect keyfile",
+            }),
+            StorageError::WrongUnlockMethod(needed) => write!(f, "{}", match needed {
+                KeySlotKind::Password => "This vault is unlocked with a password only",
+                KeySlotKind::PasswordAndKeyfile => "This vault needs both its password and its keyfile",
+                _ => "This vault is unlocked with its keyfile only",
+            }),
+            StorageError::NoRecoveryKey => write!(f, "This vault has no recovery key"),
+            StorageError::NoVault => write!(f, "There is no vault to unlock"),
+            StorageError::Corrupt(e) => write!(f, "The vault file is corrupt: {}", e),
+            StorageError::Tampered => write!(f, "The vault has been tampered with or is corrupt"),
+            StorageError::Schema(e) => write!(f, "The habit data can't be read: {}", e),
+            StorageError::NewerVersion(what) => write!(f, "{} was written by a newer version of HabitVault", what),
+            StorageError::LockedOut(wait) => write!(f, "Too many failed attempts. Try again in {}", lockout::describe_wait(*wait)),
+            StorageError::Wiped => write!(f, "Too many failed attempts. All habit data has been deleted"),
+            StorageError::VaultBusy => write!(f, "The vault is in use by another HabitVault process"),
+            StorageError::Keyfile(path, e) => write!(f, "Can't read keyfile {}: {}", path.display(), e),
+            StorageError::Io(e) => write!(f, "{}", e),
+            StorageError::Encoding(e) => write!(f, "Failed to encode data: {}", e),
+            StorageError::Crypto(e) => write!(f, "{}", e),
+            StorageError::Csv(e) => write!(f, "Invalid CSV: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Keyfile(_, e) | StorageError::Io(e) => Some(e),
+            StorageError::Encoding(e) => Some(e),
+            StorageError::Crypto(e) => Some(e),
+            StorageError::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(e: std::io::Error) -> Self {
+        StorageError::Io(e)
+    }
+}
+
+impl From<CryptoError> for StorageError {
+    fn from(e: CryptoError) -> Self {
+        StorageError::Crypto(e)
+    }
+}
+
+impl From<csv::Error> for StorageError {
+    fn from(e: csv::Error) -> Self {
+        StorageError::Csv(e)
+    }
+}
+
+/// An unlocked vault. Holds the data key and the key slots needed to write
+/// the vault back, so saving never has to run the KDF again. Passwords and
+/// keyfiles are not kept, and the key is wiped when the session is dropped.
+#[derive(Clone)]
+pub struct Session {
+    data_key: Zeroizing<[u8; 32]>,
+    vault_id: Vec<u8>,
+    key_slots: Vec<KeySlot>,
+}
+
+impl Session {
+    /// Starts a new vault unlocked by `credentials`, with a fresh data key.
+    pub fn create(credentials: &Credentials, kdf: &KdfParams) -> Result<Self, StorageError> {
+        let mut session = Self {
+            data_key: generate_key(),
+            vault_id: generate_vault_id().to_vec(),
+            key_slots: Vec::new(),
+        };
+        session.set_credentials(credentials, kdf)?;
+        Ok(session)
+    }
+    
+    fn unlock_slot(&self) -> Option<&KeySlot> {
+        self.key_slots.iter().find(|slot| slot.kind.is_unlock_method())
+    }
+    
+    /// How the vault is unlocked day to day.
+    pub fn unlock_method(&self) -> Option<KeySlotKind> {
+        self.unlock_slot().map(|slot| slot.kind)
+    }
+    
+    /// The KDF parameters protecting the unlock method's slot.
+    pub fn unlock_kdf(&self) -> Option<&KdfParams> {
+        self.unlock_slot().map(|slot| &slot.kdf)
+    }
+    
+    pub fn has_recovery_key(&self) -> bool {
+        self.key_slots.iter().any(|slot| slot.kind == KeySlotKind::RecoveryKey)
+    }
+    
+    /// Wraps the data key under `credentials`. A recovery key replaces the
+    /// previous recovery key; anything else replaces the unlock method, so
+    /// switching from a password to a keyfile drops the password slot.
+    pub fn set_credentials(&mut self, credentials: &Credentials, kdf: &KdfParams) -> Result<(), StorageError> {
+        let salt = generate_salt();
+        let key_encryption = Encryption::new(credentials, &salt, kdf)?;
+        self.wrap_slot(KeySlotKind::of(credentials), &key_encryption, kdf.clone(), salt.to_vec())
+    }
+    
+    /// Replaces the data key with a new one and wraps it under each of
+    /// `credentials` alone, dropping every other key slot. Whoever kept the
+    /// old credentials or the old data key can't open anything saved from now
+    /// on. Save with `SecureStorage::write_rekeyed_vault`, so the versions
+    /// already on disk stop opening with them too.
+    pub fn rekey(&mut self, credentials: &[(&Credentials, &KdfParams)]) -> Result<(), StorageError> {
+        self.data_key = generate_key();
+        self.key_slots.clear();
+        for (credentials, kdf) in credentials {
+            self.set_credentials(credentials, kdf)?;
+        }
+        Ok(())
+    }
+    
+    /// Generates a recovery key that also unlocks this vault, replacing any
+    /// previous one, and returns it formatted for the user to write down.
+    pub fn regenerate_recovery_key(&mut self) -> Result<Zeroizing<String>, StorageError> {
+        let recovery_key = generate_recovery_key();
+        // The key is random rather than chosen by a person, so a slow KDF
+        // adds nothing and the standard profile keeps resets quick
+        self.set_credentials(&Credentials::recovery_key(&recovery_key), &KdfProfile::Standard.params())?;
+        Ok(recovery_key)
+    }
+    
+    /// Checks `credentials` against this session's own key slots, without
+    /// touching the vault on disk.
+    pub fn verify(&self, credentials: &Credentials) -> Result<(), StorageError> {
+        let slot = Self::find_slot(&self.key_slots, KeySlotKind::of(credentials))?;
+        let Some(wrapped_key) = &slot.wrapped_key else {
+            return Err(StorageError::WrongCredentials(slot.kind));
+        };
+        let key_bytes = Self::unwrap_key(slot, wrapped_key, credentials)?;
+        if key_bytes.as_slice() != self.data_key.as_slice() {
+            return Err(StorageError::WrongCredentials(slot.kind));
+        }
+        Ok(())
+    }
+    
+    fn wrap_slot(&mut self, kind: KeySlotKind, key_encryption: &Encryption, kdf: KdfParams, salt: Vec<u8>) -> Result<(), StorageError> {
+        let wrapped = key_encryption.encrypt(self.data_key.as_ref(), &[])?;
+        self.key_slots.retain(|slot| slot.kind.is_unlock_method() != kind.is_unlock_method());
+        self.key_slots.push(KeySlot {
+            kind,
+            kdf,
+            salt,
+            wrapped_key: Some(WrappedKey { data: wrapped.data, nonce: wrapped.nonce }),
+        });
+        Ok(())
+    }
+    
+    fn find_slot(key_slots: &[KeySlot], kind: KeySlotKind) -> Result<&KeySlot, StorageError> {
+        if let Some(slot) = key_slots.iter().find(|slot| slot.kind == kind) {
+            return Ok(slot);
+        }
+        
+        // Say what the vault needs instead, if it is unlocked another way
+        match key_slots.iter().find(|slot| slot.kind.is_unlock_method()) {
+            Some(slot) if kind.is_unlock_method() => Err(StorageError::WrongUnlockMethod(slot.kind)),
+            _ if kind == KeySlotKind::RecoveryKey => Err(StorageError::NoRecoveryKey),
+            _ => Err(StorageError::Corrupt("no key slot to unlock with".to_string())),
+        }
+    }
+    
+    fn unwrap_key(slot: &KeySlot, wrapped_key: &WrappedKey, credentials: &Credentials) -> Result<Zeroizing<[u8; 32]>, StorageError> {
+        let key_encryption = Encryption::new(credentials, &slot.salt, &slot.kdf)?;
+        let key_bytes = key_encryption.decrypt(&EncryptedData {
+            data: wrapped_key.data.clone(),
+            nonce: wrapped_key.nonce.clone(),
+            salt: slot.salt.clone(),
+        }, &[]).map_err(|_| StorageError::WrongCredentials(slot.kind))?;
+        Ok(Zeroizing::new(
+            key_bytes.as_slice().try_into()
+                .map_err(|_| StorageError::Corrupt("data key has the wrong length".to_string()))?
+        ))
+    }
+    
+    /// Opens the vault described by `header` with `credentials`, returning
+    /// the session and the cipher its payload was encrypted with.
+    fn open(header: &VaultHeader, credentials: &Credentials) -> Result<(Self, Encryption), StorageError> {
+        let slot = Self::find_slot(&header.key_slots, KeySlotKind::of(credentials))?;
+        
+        let Some(wrapped_key) = &slot.wrapped_key else {
+            // Vaults from before data keys were introduced are encrypted with
+            // the password key itself; they get a data key on the next save
+            let key_encryption = Encryption::new(credentials, &slot.salt, &slot.kdf)?;
+            let mut session = Self {
+                data_key: generate_key(),
+                vault_id: generate_vault_id().to_vec(),
+                key_slots: Vec::new(),
+            };
+            session.wrap_slot(slot.kind, &key_encryption, slot.kdf.clone(), slot.salt.clone())?;
+            return Ok((session, key_encryption));
+        };
+        
+        let data_key = Self::unwrap_key(slot, wrapped_key, credentials)?;
+        let payload_encryption = Encryption::from_key(&data_key);
+        // Vaults from before format 5 get an id when they are next saved
+        let vault_id = if header.vault_id.is_empty() {
+            generate_vault_id().to_vec()
+        } else {
+            header.vault_id.clone()
+        };
+        let session = Self { data_key, vault_id, key_slots: header.key_slots.clone() };
+        Ok((session, payload_encryption))
+    }
+}
+
+/// The vault file in a data directory, along with its earlier generations,
+/// settings and failed unlock count. Everything that touches the disk goes
+/// through here.
+#[derive(Clone)]
+pub struct SecureStorage {
+    data_path: PathBuf,
+}
+
+impl SecureStorage {
+    /// Storage in the app's directory under the user's data directory, e.g.
+    /// `~/.local/share/rust-gtk-habits`.
+    pub fn new() -> Result<Self, StorageError> {
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "Could not find data directory"))?
+            .join("rust-gtk-habits");
+        
+        Self::with_data_dir(data_dir)
+    }
+    
+    /// Storage in `data_dir`, which is created if it doesn't exist.
+    pub fn with_data_dir(data_dir: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let data_dir = data_dir.into();
+        fs::create_dir_all(&data_dir)?;
+        
+        Ok(Self {
+            data_path: data_dir.join("habits.encrypted"),
+        })
+    }
+    
+    /// Replaces the data file with an already encrypted vault, keeping the
+    /// previous one as a generation. See `encrypt_vault`.
+    pub fn write_vault(&self, vault_bytes: &[u8]) -> Result<(), StorageError> {
+        self.rotate_generations()?;
+        write_atomic(&self.data_path, vault_bytes)?;
+        
+        Ok(())
+    }
+    
+    /// Writes the vault after `session` was made from `previous` with new
+    /// credentials or a new data key. The data file and every earlier
+    /// generation are re-encrypted for `session` as they are rotated, so the
+    /// credentials and key of `previous` open none of them. Generations that
+    /// `previous` can't decrypt either are removed, as they may still open
+    /// with credentials changed even earlier.
+    pub fn write_rekeyed_vault(&self, vault_bytes: &[u8], previous: &Session, session: &Session) -> Result<(), StorageError> {
+        self.rotate_generations()?;
+        for path in self.generation_paths() {
+            if path.exists() {
+                Self::reencrypt_file(&path, previous, session)?;
+            }
+        }
+        write_atomic(&self.data_path, vault_bytes)?;
+        
+        // A cached key of the previous session no longer opens anything
+        if previous.data_key != session.data_key
+            && let Some(path) = session_cache::cache_path(&previous.vault_id) {
+            session_cache::remove(&path)?;
+        }
+        Ok(())
+    }
+    
+    /// Re-encrypts one file from `previous` for `session`, or removes it if
+    /// `previous` doesn't open it.
+    fn reencrypt_file(path: &Path, previous: &Session, session: &Session) -> Result<(), StorageError> {
+        let vault_bytes = fs::read(path)?;
+        let decrypted = vault::decode(&vault_bytes).ok().and_then(|vault| {
+            Encryption::from_key(&previous.data_key).decrypt(&EncryptedData {
+                data: vault.ciphertext,
+                nonce: vault.header.nonce,
+                salt: Vec::new(),
+            }, &vault.associated_data).ok()
+        });
+        match decrypted.map(|json| migrations::from_versioned_json(&json)) {
+            Some(Ok(habit_data)) => write_atomic(path, &encrypt_vault(&habit_data, session)?)?,
+            _ => fs::remove_file(path)?,
+        }
+        Ok(())
+    }
+    
+    /// Unlocks the vault with `credentials`, falling back to the newest
+    /// readable previous generation if the data file is missing or damaged.
+    /// Also returns the generation that was used in that case so the caller
+    /// can warn the user.
+    ///
+    /// Wrong credentials count as a failed attempt. After a few in a row
+    /// further attempts are refused with `LockedOut` until the delay has
+    /// passed, and the vault is deleted once `wipe_after_failures` is
+    /// reached.
+    pub fn unlock_with_recovery(&self, credentials: &Credentials) -> Result<(HabitData, Session, Option<PathBuf>), StorageError> {
+        let mut attempts = self.unlock_attempts();
+        if let Some(wait) = attempts.retry_after(Utc::now()) {
+            return Err(StorageError::LockedOut(wait));
+        }
+        
+        match self.unlock_any_generation(credentials) {
+            Ok(unlocked) => {
+                if attempts.failures > 0 {
+                    self.save_unlock_attempts(&UnlockAttempts::default())?;
+                }
+                Ok(unlocked)
+            }
+            Err(e @ StorageError::WrongCredentials(_)) => {
+                attempts.record_failure(Utc::now());
+                let wipe_after = self.load_settings().wipe_after_failures;
+                if wipe_after > 0 && attempts.failures >= wipe_after {
+                    self.delete_all_data()?;
+                    return Err(StorageError::Wiped);
+                }
+                // Not being able to count the attempt is an error of its own,
+                // or the delay could be dodged
+                self.save_unlock_attempts(&attempts)?;
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+    
+    fn unlock_any_generation(&self, credentials: &Credentials) -> Result<(HabitData, Session, Option<PathBuf>), StorageError> {
+        if !self.exists() {
+            if !KeySlotKind::of(credentials).is_unlock_method() {
+                return Err(StorageError::NoVault);
+            }
+            return Ok((HabitData::new(), Session::create(credentials, &self.target_kdf()?)?, None));
+        }
+        
+        let error = match Self::unlock_file(&self.data_path, credentials) {
+            Ok((habit_data, session)) => return Ok((habit_data, session, None)),
+            // Older generations may be under credentials that have since
+            // been changed, so only look at them when the file is damaged
+            Err(e) if e.is_corruption() || matches!(e, StorageError::Io(_)) => e,
+            Err(e) => return Err(e),
+        };
+        
+        for path in self.generation_paths() {
+            if path.exists()
+                && let Ok((habit_data, session)) = Self::unlock_file(&path, credentials) {
+                return Ok((habit_data, session, Some(path)));
+            }
+        }
+        
+        // Nothing was readable, so report why the current file failed
+        Err(error)
+    }
+    
+    fn unlock_file(path: &Path, credentials: &Credentials) -> Result<(HabitData, Session), StorageError> {
+        let vault_bytes = fs::read(path)?;
+        let (mut habit_data, session) = decrypt_vault(&vault_bytes, credentials)?;
+        
+        // Stored streaks are stale if the app wasn't opened for a while
+        habit_data.recompute_streaks(habit_data.today());
+        Ok((habit_data, session))
+    }
+    
+    /// How the vault on disk is unlocked, read from its plaintext header so
+    /// the unlock dialog knows what to ask for. `None` if there is no vault
+    /// or its header can't be read.
+    pub fn unlock_method(&self) -> Option<KeySlotKind> {
+        let vault_bytes = fs::read(&self.data_path).ok()?;
+        let vault = vault::decode(&vault_bytes).ok()?;
+        vault.header.key_slots.iter()
+            .map(|slot| slot.kind)
+            .find(|kind| kind.is_unlock_method())
+    }
+    
+    /// Keeps the session's data key for `duration`, so that `unlock_cached`
+    /// can open the vault without credentials until then. Used by the
+    /// command-line tool for status bars and scripts.
+    pub fn cache_session(&self, session: &Session, duration: chrono::Duration) -> Result<(), StorageError> {
+        let path = session_cache::cache_path(&session.vault_id).ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "There is no runtime directory to keep the session in",
+        ))?;
+        session_cache::write(&path, &CachedKey {
+            data_key: *session.data_key,
+            expires_at: Utc::now() + duration,
+        })?;
+        Ok(())
+    }
+    
+    /// Opens the vault with a key kept by `cache_session`. `None` if no
+    /// unexpired key is cached for this vault. A key that no longer opens
+    /// it is dropped. Never counts as a failed unlock attempt.
+    pub fn unlock_cached(&self) -> Result<Option<(HabitData, Session)>, StorageError> {
+        if !self.data_path.exists() {
+            return Ok(None);
+        }
+        let vault = vault::decode(&fs::read(&self.data_path)?)?;
+        let Some(path) = session_cache::cache_path(&vault.header.vault_id) else {
+            return Ok(None);
+        };
+        let Some(cached) = session_cache::read(&path)? else {
+            return Ok(None);
+        };
+        
+        let decrypted = Encryption::from_key(&cached.data_key).decrypt(&EncryptedData {
+            data: vault.ciphertext,
+            nonce: vault.header.nonce,
+            salt: Vec::new(),
+        }, &vault.associated_data);
+        let Ok(decrypted_data) = decrypted else {
+            session_cache::remove(&path)?;
+            return Ok(None);
+        };
+        
+        let mut habit_data = migrations::from_versioned_json(&decrypted_data)?;
+        habit_data.recompute_streaks(habit_data.today());
+        let session = Session {
+            data_key: Zeroizing::new(cached.data_key),
+            vault_id: vault.header.vault_id,
+            key_slots: vault.header.key_slots,
+        };
+        Ok(Some((habit_data, session)))
+    }
+    
+    /// Removes the cached key of this vault, if any.
+    pub fn forget_cached_session(&self) -> Result<(), StorageError> {
+        let vault_id = fs::read(&self.data_path).ok()
+            .and_then(|vault_bytes| vault::decode(&vault_bytes).ok())
+            .map(|vault| vault.header.vault_id);
+        if let Some(path) = vault_id.and_then(|vault_id| session_cache::cache_path(&vault_id)) {
+            session_cache::remove(&path)?;
+        }
+        Ok(())
+    }
+    
+    /// Settings are stored in the clear; a missing or unreadable file gives
+    /// the defaults.
+    pub fn load_settings(&self) -> Settings {
+        fs::read(self.settings_path())
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or_default()
+    }
+    
+    pub fn save_settings(&self, settings: &Settings) -> Result<(), StorageError> {
+        let json = serde_json::to_vec_pretty(settings).map_err(StorageError::Encoding)?;
+        write_atomic(&self.settings_path(), &json)?;
+        Ok(())
+    }
+    
+    /// Takes the advisory lock that keeps two processes from changing the
+    /// vault at once, waiting up to `timeout` for another holder to let go.
+    /// The app holds it while unlocked, and the CLI while it reads, changes
+    /// and writes back the data, so neither overwrites the other's changes.
+    pub fn lock_vault(&self, timeout: std::time::Duration) -> Result<VaultLock, StorageError> {
+        let file = File::options().create(true).truncate(false).write(true).open(self.lock_path())?;
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(VaultLock { _file: file }),
+                Err(fs::TryLockError::WouldBlock) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+                Err(fs::TryLockError::WouldBlock) => return Err(StorageError::VaultBusy),
+                Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+    }
+    
+    fn lock_path(&self) -> PathBuf {
+        self.data_path.with_file_name("habits.lock")
+    }
+    
+    fn settings_path(&self) -> PathBuf {
+        self.data_path.with_file_name("settings.json")
+    }
+    
+    /// Failed unlock attempts so far, stored in the clear like the settings.
+    pub fn unlock_attempts(&self) -> UnlockAttempts {
+        fs::read(self.unlock_attempts_path())
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or_default()
+    }
+    
+    fn save_unlock_attempts(&self, attempts: &UnlockAttempts) -> Result<(), StorageError> {
+        let json = serde_json::to_vec_pretty(attempts).map_err(StorageError::Encoding)?;
+        write_atomic(&self.unlock_attempts_path(), &json)?;
+        Ok(())
+    }
+    
+    fn unlock_attempts_path(&self) -> PathBuf {
+        self.data_path.with_file_name("unlock_attempts.json")
+    }
+    
+    /// The KDF parameters new keys should be wrapped with. The first call
+    /// benchmarks the machine to choose a profile, which takes a moment, and
+    /// saves it to the settings.
+    pub fn target_kdf(&self) -> Result<KdfParams, StorageError> {
+        let mut settings = self.load_settings();
+        let profile = match settings.kdf_profile {
+            Some(profile) => profile,
+            None => {
+                let profile = KdfProfile::benchmark();
+                settings.kdf_profile = Some(profile);
+                self.save_settings(&settings)?;
+                profile
+            }
+        };
+        Ok(profile.params())
+    }
+    
+    pub fn exists(&self) -> bool {
+        self.data_path.exists() || self.generation_paths().iter().any(|p| p.exists())
+    }
+    
+    /// Previous versions of the data file, newest first.
+    fn generation_paths(&self) -> Vec<PathBuf> {
+        (1..=GENERATIONS).map(|n| self.generation_path(n)).collect()
+    }
+    
+    fn generation_path(&self, n: usize) -> PathBuf {
+        self.data_path.with_extension(format!("encrypted.{}", n))
+    }
+    
+    /// Shifts every generation back by one, dropping the oldest, and keeps
+    /// the current data file as the newest generation.
+    fn rotate_generations(&self) -> std::io::Result<()> {
+        if !self.data_path.exists() {
+            return Ok(());
+        }
+        
+        for n in (1..GENERATIONS).rev() {
+            let from = self.generation_path(n);
+            if from.exists() {
+                fs::rename(&from, self.generation_path(n + 1))?;
+            }
+        }
+        
+        // A hard link keeps the data file in place until the new version is
+        // renamed over it; copy where the filesystem doesn't support links
+        let newest = self.generation_path(1);
+        if fs::hard_link(&self.data_path, &newest).is_err() {
+            fs::copy(&self.data_path, &newest)?;
+        }
+        
+        Ok(())
+    }
+    
+    pub fn export_backup(&self, data: &HabitData, backup_password: &str, backup_path: &std::path::Path) -> Result<(), StorageError> {
+        let credentials = Credentials::Password(Zeroizing::new(backup_password.to_string()));
+        let backup_session = Session::create(&credentials, &self.target_kdf()?)?;
+        let vault_bytes = encrypt_vault(data, &backup_session)?;
+        write_atomic(backup_path, &vault_bytes)?;
+        
+        Ok(())
+    }
+    
+    /// Decrypts a backup file. Saving it under the current session is left to
+    /// the caller.
+    pub fn read_backup(&self, backup_path: &std::path::Path, backup_password: &str) -> Result<HabitData, StorageError> {
+        let vault_bytes = fs::read(backup_path)?;
+        let credentials = Credentials::Password(Zeroizing::new(backup_password.to_string()));
+        let (mut habit_data, _) = decrypt_vault(&vault_bytes, &credentials)?;
+        habit_data.recompute_streaks(habit_data.today());
+        
+        Ok(habit_data)
+    }
+    
+    pub fn delete_all_data(&self) -> Result<(), StorageError> {
+        self.forget_cached_session()?;
+        
+        let mut paths = vec![
+            self.data_path.clone(),
+            temp_path(&self.data_path),
+            self.unlock_attempts_path(),
+        ];
+        paths.extend(self.generation_paths());
+        
+        for path in paths {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Held lock on a vault, released when dropped.
+#[derive(Debug)]
+pub struct VaultLock {
+    _file: File,
+}
+
+/// Encrypts `data` into a vault file with the session's data key and a fresh
+/// nonce. This is fast, so it can run on the main thread ahead of the write.
+pub fn encrypt_vault(data: &HabitData, session: &Session) -> Result<Vec<u8>, StorageError> {
+    let json_data = migrations::to_versioned_json(data)?;
+    let nonce = generate_nonce();
+    
+    let header = VaultHeader {
+        format_version: vault::FORMAT_VERSION,
+        vault_id: session.vault_id.clone(),
+        cipher: CipherId::Aes256Gcm,
+        nonce: nonce.to_vec(),
+        key_slots: session.key_slots.clone(),
+    };
+    let mut vault_bytes = vault::encode_header(&header)?;
+    let ciphertext = Encryption::from_key(&session.data_key)
+        .encrypt_with_nonce(&nonce, &json_data, &vault_bytes)?;
+    vault_bytes.extend_from_slice(&ciphertext);
+    Ok(vault_bytes)
+}
+
+/// Decrypts a vault file of any format version and upgrades its contents to
+/// the current schema.
+fn decrypt_vault(vault_bytes: &[u8], credentials: &Credentials) -> Result<(HabitData, Session), StorageError> {
+    let vault = vault::decode(vault_bytes)?;
+    let (session, payload_encryption) = Session::open(&vault.header, credentials)?;
+    
+    let decrypted_data = payload_encryption.decrypt(&EncryptedData {
+        data: vault.ciphertext,
+        nonce: vault.header.nonce,
+        salt: Vec::new(),
+    }, &vault.associated_data).map_err(|_| {
+        // Before format 2 the payload was encrypted with the password key
+        // itself, so failing here is how a wrong password shows up. Later
+        // formats have already checked the credentials by unwrapping the
+        // data key, which leaves a changed header or ciphertext
+        if vault.header.format_version < 2 {
+            StorageError::WrongCredentials(KeySlotKind::Password)
+        } else {
+            StorageError::Tampered
+        }
+    })?;
+    
+    Ok((migrations::from_versioned_json(&decrypted_data)?, session))
+}
+
+/// Reads a keyfile into the digest used for key derivation.
+pub fn read_keyfile(path: &Path) -> Result<Zeroizing<[u8; 32]>, StorageError> {
+    let file = File::open(path).map_err(|e| StorageError::Keyfile(path.to_path_buf(), e))?;
+    keyfile_digest(file).map_err(|e| StorageError::Keyfile(path.to_path_buf(), e))
+}
+
+/// Writes a new random keyfile. Refuses to replace an existing file, since
+/// that could be the keyfile of this or another vault.
+pub fn create_keyfile(path: &Path) -> Result<(), StorageError> {
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(generate_keyfile().as_ref())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Writes `contents` to a temporary file next to `path`, flushes it to disk
+/// and renames it into place, so a crash or full disk never leaves a
+/// half-written file behind.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp_path = temp_path(path);
+    
+    let result = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+    
+    // Make the rename itself durable
+    if let Some(dir) = path.parent()
+        && let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    /// Far too weak for real use, but quick enough for tests.
+    fn test_kdf() -> KdfParams {
+        KdfParams::Argon2id { memory_kib: 8, iterations: 1, parallelism: 1 }
+    }
+    
+    fn password(password: &str) -> Credentials {
+        Credentials::Password(Zeroizing::new(password.to_string()))
+    }
+    
+    fn storage() -> (TempDir, SecureStorage) {
+        let dir = TempDir::new().unwrap();
+        let storage = SecureStorage::with_data_dir(dir.path()).unwrap();
+        (dir, storage)
+    }
+    
+    /// Data with one habit, whose name tells versions of a vault apart.
+    fn data_with_habit(name: &str) -> HabitData {
+        let mut data = HabitData::new();
+        data.add_habit(crate::habit::Habit {
+            id: "habit".to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            created_at: Utc::now(),
+            target_days_per_week: 7,
+            schedule: crate::habit::Schedule::Daily,
+            quantity: None,
+            streak: 0,
+            longest_streak: 0,
+        });
+        data
+    }
+    
+    fn habit_name(data: &HabitData) -> &str {
+        &data.habits[0].name
+    }
+    
+    #[test]
+    fn rekeying_locks_old_credentials_out_of_every_generation() {
+        let (_dir, storage) = storage();
+        let previous = Session::create(&password("old"), &test_kdf()).unwrap();
+        let data = HabitData::new();
+        for _ in 0..3 {
+            storage.write_vault(&encrypt_vault(&data, &previous).unwrap()).unwrap();
+        }
+        // A generation from before an even earlier change
+        let older = Session::create(&password("older"), &test_kdf()).unwrap();
+        write_atomic(&storage.generation_path(3), &encrypt_vault(&data, &older).unwrap()).unwrap();
+        
+        let mut session = previous.clone();
+        session.rekey(&[(&password("new"), &test_kdf())]).unwrap();
+        storage.write_rekeyed_vault(&encrypt_vault(&data, &session).unwrap(), &previous, &session).unwrap();
+        
+        let mut paths = vec![storage.data_path.clone()];
+        paths.extend(storage.generation_paths().into_iter().filter(|path| path.exists()));
+        assert_eq!(paths.len(), 4);
+        assert!(!storage.generation_path(4).exists());
+        for path in &paths {
+            assert!(matches!(
+                SecureStorage::unlock_file(path, &password("old")),
+                Err(StorageError::WrongCredentials(_))
+            ));
+            let (_, unlocked) = SecureStorage::unlock_file(path, &password("new")).unwrap();
+            assert_eq!(*unlocked.data_key, *session.data_key);
+        }
+        assert_ne!(*session.data_key, *previous.data_key);
+    }
+    
+    #[test]
+    fn vault_lock_is_exclusive_until_dropped() {
+        let (_dir, storage) = storage();
+        let lock = storage.lock_vault(std::time::Duration::ZERO).unwrap();
+        assert!(matches!(storage.lock_vault(std::time::Duration::ZERO), Err(StorageError::VaultBusy)));
+        
+        drop(lock);
+        assert!(storage.lock_vault(std::time::Duration::ZERO).is_ok());
+    }
+    
+    #[test]
+    fn rotation_keeps_five_generations() {
+        let (_dir, storage) = storage();
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        for version in 1..=8 {
+            storage.write_vault(&encrypt_vault(&data_with_habit(&format!("v{}", version)), &session).unwrap()).unwrap();
+        }
+        
+        assert!(!temp_path(&storage.data_path).exists());
+        assert!(!storage.generation_path(GENERATIONS + 1).exists());
+        let (data, _) = SecureStorage::unlock_file(&storage.data_path, &password("pw")).unwrap();
+        assert_eq!(habit_name(&data), "v8");
+        for n in 1..=GENERATIONS {
+            let (data, _) = SecureStorage::unlock_file(&storage.generation_path(n), &password("pw")).unwrap();
+            assert_eq!(habit_name(&data), format!("v{}", 8 - n));
+        }
+    }
+    
+    #[test]
+    fn corrupt_data_file_falls_back_to_a_generation() {
+        let (_dir, storage) = storage();
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        for version in 1..=3 {
+            storage.write_vault(&encrypt_vault(&data_with_habit(&format!("v{}", version)), &session).unwrap()).unwrap();
+        }
+        
+        fs::write(&storage.data_path, b"HABITVLT\x01").unwrap();
+        let (data, _, recovered_from) = storage.unlock_with_recovery(&password("pw")).unwrap();
+        assert_eq!(habit_name(&data), "v2");
+        assert_eq!(recovered_from, Some(storage.generation_path(1)));
+        
+        // Skips generations that are damaged too
+        fs::write(storage.generation_path(1), b"").unwrap();
+        let (data, _, recovered_from) = storage.unlock_with_recovery(&password("pw")).unwrap();
+        assert_eq!(habit_name(&data), "v1");
+        assert_eq!(recovered_from, Some(storage.generation_path(2)));
+    }
+    
+    #[test]
+    fn missing_data_file_falls_back_to_a_generation() {
+        let (_dir, storage) = storage();
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        for version in 1..=2 {
+            storage.write_vault(&encrypt_vault(&data_with_habit(&format!("v{}", version)), &session).unwrap()).unwrap();
+        }
+        
+        fs::remove_file(&storage.data_path).unwrap();
+        assert!(storage.exists());
+        let (data, _, recovered_from) = storage.unlock_with_recovery(&password("pw")).unwrap();
+        assert_eq!(habit_name(&data), "v1");
+        assert_eq!(recovered_from, Some(storage.generation_path(1)));
+    }
+    
+    #[test]
+    fn wrong_password_does_not_fall_back() {
+        let (_dir, storage) = storage();
+        let old = Session::create(&password("old"), &test_kdf()).unwrap();
+        storage.write_vault(&encrypt_vault(&data_with_habit("old"), &old).unwrap()).unwrap();
+        let new = Session::create(&password("new"), &test_kdf()).unwrap();
+        storage.write_vault(&encrypt_vault(&data_with_habit("new"), &new).unwrap()).unwrap();
+        
+        // The old password still opens the generation, but isn't let near it
+        assert!(SecureStorage::unlock_file(&storage.generation_path(1), &password("old")).is_ok());
+        assert!(matches!(
+            storage.unlock_with_recovery(&password("old")),
+            Err(StorageError::WrongCredentials(KeySlotKind::Password))
+        ));
+        assert_eq!(storage.unlock_attempts().failures, 1);
+    }
+    
+    /// A vault as written before format 5, whose header isn't authenticated.
+    fn encrypt_format_4(data: &HabitData, session: &Session) -> Vec<u8> {
+        let nonce = generate_nonce();
+        let header = VaultHeader {
+            format_version: 4,
+            vault_id: Vec::new(),
+            cipher: CipherId::Aes256Gcm,
+            nonce: nonce.to_vec(),
+            key_slots: session.key_slots.clone(),
+        };
+        let mut vault_bytes = vault::encode_header(&header).unwrap();
+        let json_data = migrations::to_versioned_json(data).unwrap();
+        vault_bytes.extend(Encryption::from_key(&session.data_key).encrypt_with_nonce(&nonce, &json_data, &[]).unwrap());
+        vault_bytes
+    }
+    
+    /// Changes the first digit after `marker` in the header JSON.
+    fn flip_digit_after(bytes: &mut [u8], marker: &str) {
+        let start = bytes.windows(marker.len()).position(|window| window == marker.as_bytes()).unwrap() + marker.len();
+        bytes[start] = if bytes[start] == b'1' { b'2' } else { b'1' };
+    }
+    
+    #[test]
+    fn changed_header_is_corruption_not_wrong_credentials() {
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        let vault_bytes = encrypt_vault(&HabitData::new(), &session).unwrap();
+        assert!(decrypt_vault(&vault_bytes, &password("pw")).is_ok());
+        
+        // The vault id and payload nonce aren't needed to unwrap the data key,
+        // so only the header binding catches changes to them
+        for marker in ["\"vault_id\":[", "\"nonce\":["] {
+            let mut changed = vault_bytes.clone();
+            flip_digit_after(&mut changed, marker);
+            let error = decrypt_vault(&changed, &password("pw")).err().unwrap();
+            assert!(error.is_corruption(), "{}: {}", marker, error);
+        }
+        
+        let mut changed = vault_bytes.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        assert!(matches!(decrypt_vault(&changed, &password("pw")), Err(StorageError::Tampered)));
+        assert!(matches!(
+            decrypt_vault(&vault_bytes[..vault_bytes.len() - 1], &password("pw")),
+            Err(StorageError::Tampered)
+        ));
+    }
+    
+    #[test]
+    fn format_4_vaults_still_open() {
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        let vault_bytes = encrypt_format_4(&data_with_habit("Read"), &session);
+        
+        let (opened, opened_session) = decrypt_vault(&vault_bytes, &password("pw")).unwrap();
+        assert_eq!(opened.habits[0].name, "Read");
+        // It gets an id of its own when next saved
+        assert_eq!(opened_session.vault_id.len(), session.vault_id.len());
+        assert!(matches!(decrypt_vault(&vault_bytes, &password("wrong")), Err(StorageError::WrongCredentials(_))));
+    }
+    
+    #[test]
+    fn only_wrong_credentials_count_as_failed_attempts() {
+        let (_dir, storage) = storage();
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        storage.write_vault(&encrypt_vault(&data_with_habit("v1"), &session).unwrap()).unwrap();
+        
+        fs::write(&storage.data_path, b"not a vault").unwrap();
+        for _ in 0..5 {
+            assert!(matches!(storage.unlock_with_recovery(&password("pw")), Err(StorageError::Corrupt(_))));
+        }
+        assert_eq!(storage.unlock_attempts().failures, 0);
+        
+        storage.write_vault(&encrypt_vault(&data_with_habit("v2"), &session).unwrap()).unwrap();
+        for _ in 0..3 {
+            assert!(matches!(storage.unlock_with_recovery(&password("wrong")), Err(StorageError::WrongCredentials(_))));
+        }
+        assert_eq!(storage.unlock_attempts().failures, 3);
+        // Even the right password waits out the delay
+        assert!(matches!(storage.unlock_with_recovery(&password("pw")), Err(StorageError::LockedOut(_))));
+    }
+    
+    #[test]
+    fn success_resets_the_failures() {
+        let (_dir, storage) = storage();
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        storage.write_vault(&encrypt_vault(&data_with_habit("v1"), &session).unwrap()).unwrap();
+        
+        assert!(storage.unlock_with_recovery(&password("wrong")).is_err());
+        assert_eq!(storage.unlock_attempts().failures, 1);
+        assert!(storage.unlock_with_recovery(&password("pw")).is_ok());
+        assert_eq!(storage.unlock_attempts().failures, 0);
+    }
+    
+    #[test]
+    fn wipe_after_failures_deletes_the_vault() {
+        let (_dir, storage) = storage();
+        let session = Session::create(&password("pw"), &test_kdf()).unwrap();
+        for version in 1..=2 {
+            storage.write_vault(&encrypt_vault(&data_with_habit(&format!("v{}", version)), &session).unwrap()).unwrap();
+        }
+        storage.save_settings(&Settings { wipe_after_failures: 2, ..Settings::default() }).unwrap();
+        
+        assert!(matches!(storage.unlock_with_recovery(&password("wrong")), Err(StorageError::WrongCredentials(_))));
+        assert!(storage.exists());
+        assert!(matches!(storage.unlock_with_recovery(&password("wrong")), Err(StorageError::Wiped)));
+        assert!(!storage.exists());
+        assert_eq!(storage.unlock_attempts().failures, 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,256 @@
+//! The on-disk vault container: a plaintext header with the key slots,
+//! followed by the encrypted habit data.
+
+use crate::encryption::{CipherId, Credentials, EncryptedData, KdfParams};
+use crate::storage::StorageError;
+use serde::{Deserialize, Serialize};
+
+/// Leading bytes of every vault file written in the versioned format.
+pub const MAGIC: &[u8; 8] = b"HABITVLT";
+
+/// Version of the container layout itself. Changes to the habit data are
+/// versioned separately, see `migrations`.
+///
+/// 1. Header with KDF settings, payload encrypted with the password key.
+/// 2. Adds `wrapped_key`: the payload is encrypted with a random data key,
+///    which is stored encrypted with the password key.
+/// 3. Replaces the top-level KDF settings and wrapped key with `key_slots`, so
+///    the data key can be unlocked by more than one secret.
+/// 4. Adds keyfile slot kinds, which older versions can't open.
+/// 5. Adds `vault_id` and authenticates everything ahead of the ciphertext as
+///    associated data of the payload, so any change to the header is caught.
+pub const FORMAT_VERSION: u16 = 5;
+
+/// Everything needed to decrypt the payload, stored in the clear ahead of it.
+///
+/// On disk a vault is `MAGIC`, the format version as a little-endian u16, the
+/// header length as a little-endian u32, the header as JSON, and then the
+/// ciphertext.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultHeader {
+    pub format_version: u16,
+    /// Random and fixed for the life of the vault. Empty before format 5.
+    #[serde(default)]
+    pub vault_id: Vec<u8>,
+    pub cipher: CipherId,
+    pub nonce: Vec<u8>,
+    pub key_slots: Vec<KeySlot>,
+}
+
+/// A vault file split into its parts.
+pub struct DecodedVault {
+    pub header: VaultHeader,
+    /// The header bytes exactly as stored, which the payload authenticates.
+    /// Empty for formats before 5, which didn't bind the header.
+    pub associated_data: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// What a key slot's secret is, which decides where the UI asks for it.
+///
+/// `Password`, `PasswordAndKeyfile` and `Keyfile` are the ways of unlocking a
+/// vault day to day, and a vault has exactly one of them. The recovery key
+/// slot is optional and kept alongside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySlotKind {
+    Password,
+    RecoveryKey,
+    PasswordAndKeyfile,
+    Keyfile,
+}
+
+impl KeySlotKind {
+    pub const UNLOCK_METHODS: [KeySlotKind; 3] = [KeySlotKind::Password, KeySlotKind::PasswordAndKeyfile, KeySlotKind::Keyfile];
+    
+    pub fn of(credentials: &Credentials) -> Self {
+        match credentials {
+            Credentials::Password(_) => KeySlotKind::Password,
+            Credentials::PasswordAndKeyfile(..) => KeySlotKind::PasswordAndKeyfile,
+            Credentials::Keyfile(_) => KeySlotKind::Keyfile,
+            Credentials::RecoveryKey(_) => KeySlotKind::RecoveryKey,
+        }
+    }
+    
+    pub fn is_unlock_method(self) -> bool {
+        self != KeySlotKind::RecoveryKey
+    }
+    
+    pub fn needs_password(self) -> bool {
+        matches!(self, KeySlotKind::Password | KeySlotKind::PasswordAndKeyfile)
+    }
+    
+    pub fn needs_keyfile(self) -> bool {
+        matches!(self, KeySlotKind::PasswordAndKeyfile | KeySlotKind::Keyfile)
+    }
+    
+    pub fn label(self) -> &'static str {
+        match self {
+            KeySlotKind::Password => "Password",
+            KeySlotKind::RecoveryKey => "Recovery key",
+            KeySlotKind::PasswordAndKeyfile => "Password and keyfile",
+            KeySlotKind::Keyfile => "Keyfile only",
+        }
+    }
+}
+
+/// One way of unlocking the vault: the data key, encrypted with a key derived
+/// from a secret of the given kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeySlot {
+    pub kind: KeySlotKind,
+    pub kdf: KdfParams,
+    pub salt: Vec<u8>,
+    /// Absent only in vaults from before format 2, where the derived key
+    /// encrypts the payload directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrapped_key: Option<WrappedKey>,
+}
+
+/// Header layout used by formats 1 and 2, which had a single password.
+#[derive(Deserialize)]
+struct SinglePasswordHeader {
+    format_version: u16,
+    kdf: KdfParams,
+    cipher: CipherId,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    #[serde(default)]
+    wrapped_key: Option<WrappedKey>,
+}
+
+impl From<SinglePasswordHeader> for VaultHeader {
+    fn from(header: SinglePasswordHeader) -> Self {
+        VaultHeader {
+            format_version: header.format_version,
+            vault_id: Vec::new(),
+            cipher: header.cipher,
+            nonce: header.nonce,
+            key_slots: vec![KeySlot {
+                kind: KeySlotKind::Password,
+                kdf: header.kdf,
+                salt: header.salt,
+                wrapped_key: header.wrapped_key,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedKey {
+    pub data: Vec<u8>,
+    pub nonce: Vec<u8>,
+}
+
+/// Everything that goes ahead of the ciphertext in a vault file. The payload
+/// is encrypted with these bytes as associated data, so they are produced
+/// first and the ciphertext is appended to them.
+pub fn encode_header(header: &VaultHeader) -> Result<Vec<u8>, StorageError> {
+    let header_json = serde_json::to_vec(header).map_err(StorageError::Encoding)?;
+    let header_len = u32::try_from(header_json.len())
+        .map_err(|_| StorageError::Corrupt("header is too large".to_string()))?;
+    
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 6 + header_json.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&header.format_version.to_le_bytes());
+    bytes.extend_from_slice(&header_len.to_le_bytes());
+    bytes.extend_from_slice(&header_json);
+    Ok(bytes)
+}
+
+/// Splits a vault file into its header and ciphertext. Files from before the
+/// versioned format (a bare JSON `EncryptedData`) are read as format 0 with
+/// the KDF settings they were written with.
+pub fn decode(bytes: &[u8]) -> Result<DecodedVault, StorageError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        let legacy: EncryptedData = serde_json::from_slice(bytes)
+            .map_err(|e| StorageError::Corrupt(format!("not a HabitVault file ({})", e)))?;
+        let header = SinglePasswordHeader {
+            format_version: 0,
+            kdf: KdfParams::default(),
+            cipher: CipherId::Aes256Gcm,
+            salt: legacy.salt,
+            nonce: legacy.nonce,
+            wrapped_key: None,
+        };
+        return Ok(DecodedVault { header: header.into(), associated_data: Vec::new(), ciphertext: legacy.data });
+    };
+    
+    if rest.len() < 6 {
+        return Err(StorageError::Corrupt("the file is truncated".to_string()));
+    }
+    let format_version = u16::from_le_bytes([rest[0], rest[1]]);
+    if format_version > FORMAT_VERSION {
+        return Err(StorageError::NewerVersion(format!("Vault format {}", format_version)));
+    }
+    
+    let header_len = u32::from_le_bytes([rest[2], rest[3], rest[4], rest[5]]) as usize;
+    let rest = &rest[6..];
+    if rest.len() < header_len {
+        return Err(StorageError::Corrupt("the file is truncated".to_string()));
+    }
+    
+    let header_json = &rest[..header_len];
+    let header: VaultHeader = if format_version < 3 {
+        serde_json::from_slice::<SinglePasswordHeader>(header_json).map(VaultHeader::from)
+    } else {
+        serde_json::from_slice(header_json)
+    }.map_err(|e| StorageError::Corrupt(format!("the header is unreadable ({})", e)))?;
+    if header.format_version != format_version {
+        return Err(StorageError::Corrupt("the header's format version doesn't match".to_string()));
+    }
+    
+    let header_end = bytes.len() - rest.len() + header_len;
+    let associated_data = if format_version >= 5 {
+        bytes[..header_end].to_vec()
+    } else {
+        Vec::new()
+    };
+    
+    Ok(DecodedVault { header, associated_data, ciphertext: bytes[header_end..].to_vec() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn header_bytes(format_version: u16) -> Vec<u8> {
+        encode_header(&VaultHeader {
+            format_version,
+            vault_id: vec![7; 16],
+            cipher: CipherId::Aes256Gcm,
+            nonce: vec![1; 12],
+            key_slots: Vec::new(),
+        }).unwrap()
+    }
+    
+    #[test]
+    fn newer_format_is_refused() {
+        let mut bytes = header_bytes(FORMAT_VERSION + 1);
+        bytes.extend_from_slice(b"ciphertext");
+        assert!(matches!(decode(&bytes), Err(StorageError::NewerVersion(_))));
+    }
+    
+    #[test]
+    fn truncated_files_are_corrupt() {
+        let mut bytes = header_bytes(FORMAT_VERSION);
+        let header_end = bytes.len();
+        bytes.extend_from_slice(b"ciphertext");
+        
+        // Cut anywhere up to the end of the header, including inside MAGIC
+        for len in 0..header_end {
+            assert!(matches!(decode(&bytes[..len]), Err(StorageError::Corrupt(_))), "cut at {}", len);
+        }
+        
+        let vault = decode(&bytes).unwrap();
+        assert_eq!(vault.associated_data, bytes[..header_end]);
+        assert_eq!(vault.ciphertext, b"ciphertext");
+    }
+    
+    #[test]
+    fn header_is_only_bound_from_format_5() {
+        let vault = decode(&header_bytes(4)).unwrap();
+        assert_eq!(vault.header.format_version, 4);
+        assert!(vault.associated_data.is_empty());
+    }
+}
\ No newline at end of file
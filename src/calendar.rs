@@ -1,20 +1,24 @@
 use gtk4::prelude::*;
-use gtk4::{Button, Grid, Label, Orientation};
+use gtk4::{gdk, glib, Button, GestureClick, GestureLongPress, Grid, Label, MenuButton, Orientation};
 use gtk4::Box as GtkBox;
 use chrono::{NaiveDate, Datelike, Duration};
 use std::rc::{Rc, Weak};
 use std::cell::RefCell;
-use crate::habit::HabitData;
-use crate::storage::SecureStorage;
+use habitvault_core::habit::{HabitData, Quantity, Schedule};
+use crate::quantity_editor::build_log_popover;
+use crate::note_editor::{self, build_note_popover};
+use habitvault_core::storage::Session;
+use crate::storage_service::StorageService;
 
 pub struct HabitCalendar {
     widget: GtkBox,
     habit_id: String,
     current_month: NaiveDate,
     habit_data: Rc<RefCell<HabitData>>,
-    storage: SecureStorage,
-    password: Rc<RefCell<Option<String>>>,
+    storage: StorageService,
+    session: Rc<RefCell<Option<Session>>>,
     on_change: Option<Rc<dyn Fn()>>,
+    week_progress_label: Label,
     self_ref: Option<Weak<RefCell<Self>>>,
 }
 
@@ -22,12 +26,15 @@ impl HabitCalendar {
     pub fn new(
         habit_id: String, 
         habit_data: Rc<RefCell<HabitData>>,
-        storage: SecureStorage,
-        password: Rc<RefCell<Option<String>>>,
+        storage: StorageService,
+        session: Rc<RefCell<Option<Session>>>,
         on_change: Option<Rc<dyn Fn()>>
     ) -> Rc<RefCell<Self>> {
         let widget = GtkBox::new(Orientation::Vertical, 5);
-        let current_month = chrono::Utc::now().date_naive().with_day(1).unwrap();
+        let current_month = habit_data.borrow().today().with_day(1).unwrap();
+        
+        let week_progress_label = Label::new(None);
+        week_progress_label.add_css_class("week-progress");
         
         let calendar = Rc::new(RefCell::new(Self {
             widget,
@@ -35,8 +42,9 @@ impl HabitCalendar {
             current_month,
             habit_data,
             storage,
-            password,
+            session,
             on_change,
+            week_progress_label,
             self_ref: None,
         }));
         
@@ -51,6 +59,15 @@ impl HabitCalendar {
         &self.widget
     }
     
+    /// Switches to the month containing `date`.
+    pub fn show_month(&mut self, date: NaiveDate) {
+        let month = date.with_day(1).unwrap();
+        if month != self.current_month {
+            self.current_month = month;
+            self.rebuild_calendar();
+        }
+    }
+    
     fn build_calendar(&self) {
         self.build_calendar_content();
     }
@@ -136,6 +153,30 @@ impl HabitCalendar {
         
         // Add the calendar grid
         self.add_calendar_grid();
+        
+        // Progress toward this week's goal, below the grid
+        Self::update_week_progress(&self.week_progress_label, &self.habit_data.borrow(), &self.habit_id);
+        self.widget.append(&self.week_progress_label);
+    }
+    
+    fn update_week_progress(label: &Label, habit_data: &HabitData, habit_id: &str) {
+        let today = habit_data.today();
+        match habit_data.get_habit_by_id(habit_id) {
+            Some(habit) if habit.is_weekly_goal() => {
+                let done = habit_data.completions_in_week(habit_id, today);
+                label.set_text(&format!("This week: {}/{} days", done, habit.target_days_per_week));
+                label.set_visible(true);
+            }
+            Some(habit) => match habit.schedule {
+                Schedule::TimesPerMonth { times } => {
+                    let done = habit_data.completions_in_month(habit_id, today);
+                    label.set_text(&format!("This month: {}/{} days", done, times));
+                    label.set_visible(true);
+                }
+                _ => label.set_visible(false),
+            },
+            None => label.set_visible(false),
+        }
     }
     
     fn add_calendar_grid(&self) {
@@ -192,15 +233,111 @@ impl HabitCalendar {
         self.widget.append(&grid);
     }
     
-    fn create_day_button(&self, date: NaiveDate) -> Button {
-        let day_str = date.day().to_string();
-        let button = Button::with_label(&day_str);
+    fn create_day_button(&self, date: NaiveDate) -> gtk4::Widget {
+        let is_current_month = date.month() == self.current_month.month();
+        let quantity = self.habit_data.borrow()
+            .get_habit_by_id(&self.habit_id)
+            .and_then(|habit| habit.quantity.clone());
+        
+        let button: gtk4::Widget = match quantity {
+            Some(quantity) if is_current_month => self.create_log_button(date, &quantity).upcast(),
+            _ => self.create_toggle_button(date).upcast(),
+        };
+        if is_current_month {
+            self.connect_note_popover(&button, date);
+        }
+        button
+    }
+    
+    /// The day's note and mood for its tooltip, if it has either.
+    fn note_tooltip(&self, date: NaiveDate) -> Option<String> {
+        let data = self.habit_data.borrow();
+        let completion = data.completion_on_date(&self.habit_id, date)?;
+        note_editor::describe_note(completion.notes.as_deref(), completion.mood)
+    }
+    
+    /// Opens the note popover for `date` on right-click or long-press. The
+    /// popover is built when opened so it shows the current note.
+    fn connect_note_popover(&self, button: &gtk4::Widget, date: NaiveDate) {
+        let habit_data = self.habit_data.clone();
+        let habit_id = self.habit_id.clone();
+        let storage = self.storage.clone();
+        let session = self.session.clone();
+        let self_ref = self.self_ref.clone();
+        let show_popover = Rc::new(move |button: &gtk4::Widget| {
+            let (notes, mood) = match habit_data.borrow().completion_on_date(&habit_id, date) {
+                Some(completion) => (completion.notes.clone(), completion.mood),
+                None => (None, None),
+            };
+            
+            let habit_data = habit_data.clone();
+            let habit_id = habit_id.clone();
+            let storage = storage.clone();
+            let session = session.clone();
+            let self_ref = self_ref.clone();
+            let popover = build_note_popover(date, notes.as_deref(), mood, move |notes, mood| {
+                habit_data.borrow_mut().set_day_note(&habit_id, date, notes, mood);
+                
+                if let Some(ref session) = *session.borrow() {
+                    storage.save(&habit_data.borrow(), session);
+                }
+                
+                // Rebuild once the popover parented to this button is gone
+                let self_ref = self_ref.clone();
+                glib::idle_add_local_once(move || {
+                    if let Some(cal) = self_ref.as_ref().and_then(|weak| weak.upgrade()) {
+                        cal.borrow().rebuild_calendar();
+                    }
+                });
+            });
+            popover.set_parent(button);
+            popover.connect_closed(|popover| {
+                let popover = popover.clone();
+                glib::idle_add_local_once(move || popover.unparent());
+            });
+            popover.popup();
+        });
+        
+        let right_click = GestureClick::new();
+        right_click.set_button(gdk::BUTTON_SECONDARY);
+        let show_on_click = show_popover.clone();
+        right_click.connect_pressed(move |gesture, _, _, _| {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            if let Some(button) = gesture.widget() {
+                show_on_click(&button);
+            }
+        });
+        button.add_controller(right_click);
+        
+        // Claiming the sequence keeps the release from also toggling the day
+        let long_press = GestureLongPress::new();
+        long_press.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        long_press.connect_pressed(move |gesture, _, _| {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            if let Some(button) = gesture.widget() {
+                show_popover(&button);
+            }
+        });
+        button.add_controller(long_press);
+    }
+    
+    fn style_day_button(&self, button: &impl IsA<gtk4::Widget>, date: NaiveDate) {
         button.set_size_request(30, 30);
         button.add_css_class("calendar-day-button");
         
         let is_current_month = date.month() == self.current_month.month();
-        let is_completed = self.habit_data.borrow().is_completed_on_date(&self.habit_id, date);
-        let is_today = date == chrono::Utc::now().date_naive();
+        let (is_completed, is_missed, is_scheduled, progress) = {
+            let data = self.habit_data.borrow();
+            let is_scheduled = data.get_habit_by_id(&self.habit_id)
+                .is_none_or(|habit| habit.schedule.is_scheduled(date));
+            (
+                data.is_completed_on_date(&self.habit_id, date),
+                data.is_missed_on_date(&self.habit_id, date),
+                is_scheduled,
+                data.progress_on_date(&self.habit_id, date),
+            )
+        };
+        let is_today = date == self.habit_data.borrow().today();
         
         // Style the button based on state
         if !is_current_month {
@@ -208,16 +345,94 @@ impl HabitCalendar {
             button.set_sensitive(false);
         } else if is_completed {
             button.add_css_class("calendar-completed");
+        } else if progress > 0.0 {
+            // Partial amount logged for a quantitative habit
+            button.add_css_class("calendar-partial");
+            button.add_css_class(if progress < 1.0 / 3.0 {
+                "calendar-partial-low"
+            } else if progress < 2.0 / 3.0 {
+                "calendar-partial-mid"
+            } else {
+                "calendar-partial-high"
+            });
         } else if is_today {
             button.add_css_class("calendar-today");
+        } else if is_missed {
+            button.add_css_class("calendar-missed");
+        }
+        
+        if is_current_month && self.note_tooltip(date).is_some() {
+            button.add_css_class("calendar-has-note");
+        }
+        
+        // Days outside the schedule stay clickable but are dimmed
+        if is_current_month && !is_scheduled {
+            button.add_css_class("calendar-unscheduled");
+        }
+    }
+    
+    /// Day cell for quantitative habits: clicking opens a popover to log the
+    /// amount done that day.
+    fn create_log_button(&self, date: NaiveDate, quantity: &Quantity) -> MenuButton {
+        let button = MenuButton::new();
+        button.set_label(&date.day().to_string());
+        self.style_day_button(&button, date);
+        
+        let current = self.habit_data.borrow().value_on_date(&self.habit_id, date);
+        let progress = quantity.describe_progress(current);
+        button.set_tooltip_text(Some(&match self.note_tooltip(date) {
+            Some(note) => format!("{}\n{}", progress, note),
+            None => progress,
+        }));
+        
+        let habit_data_clone = self.habit_data.clone();
+        let habit_id_clone = self.habit_id.clone();
+        let storage_clone = self.storage.clone();
+        let session_clone = self.session.clone();
+        let on_change_clone = self.on_change.clone();
+        let self_ref = self.self_ref.clone();
+        
+        let popover = build_log_popover(quantity, current, move |value| {
+            habit_data_clone.borrow_mut().log_value(&habit_id_clone, date, value);
+            
+            // Save data after change
+            if let Some(ref session) = *session_clone.borrow() {
+                storage_clone.save(&habit_data_clone.borrow(), session);
+            }
+            
+            // Partial progress can't be patched in place, so rebuild the grid
+            // once the popover owned by this button is gone
+            let self_ref = self_ref.clone();
+            let on_change = on_change_clone.clone();
+            glib::idle_add_local_once(move || {
+                if let Some(cal) = self_ref.as_ref().and_then(|weak| weak.upgrade()) {
+                    cal.borrow().rebuild_calendar();
+                }
+                if let Some(ref callback) = on_change {
+                    callback();
+                }
+            });
+        });
+        button.set_popover(Some(&popover));
+        
+        button
+    }
+    
+    fn create_toggle_button(&self, date: NaiveDate) -> Button {
+        let day_str = date.day().to_string();
+        let button = Button::with_label(&day_str);
+        self.style_day_button(&button, date);
+        if date.month() == self.current_month.month() {
+            button.set_tooltip_text(self.note_tooltip(date).as_deref());
         }
         
         // Add click handler
         let habit_data_clone = self.habit_data.clone();
         let habit_id_clone = self.habit_id.clone();
         let storage_clone = self.storage.clone();
-        let password_clone = self.password.clone();
+        let session_clone = self.session.clone();
         let on_change_clone = self.on_change.clone();
+        let week_progress_label = self.week_progress_label.clone();
         
         button.connect_clicked(move |btn| {
             let is_completed = {
@@ -235,18 +450,21 @@ impl HabitCalendar {
                 }
             }
             
-            // Update button appearance immediately
+            // Update button appearance immediately; the note stays
             if is_completed {
                 btn.remove_css_class("calendar-completed");
+                if habit_data_clone.borrow().is_missed_on_date(&habit_id_clone, date) {
+                    btn.add_css_class("calendar-missed");
+                }
             } else {
                 btn.add_css_class("calendar-completed");
+                btn.remove_css_class("calendar-missed");
             }
+            Self::update_week_progress(&week_progress_label, &habit_data_clone.borrow(), &habit_id_clone);
             
             // Save data after change
-            if let Some(ref pass) = *password_clone.borrow() {
-                if let Err(e) = storage_clone.save(&habit_data_clone.borrow(), pass) {
-                    eprintln!("Failed to save data: {}", e);
-                }
+            if let Some(ref session) = *session_clone.borrow() {
+                storage_clone.save(&habit_data_clone.borrow(), session);
             }
             
             // Call the change callback to refresh main UI (streak counter)
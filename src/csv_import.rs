@@ -0,0 +1,256 @@
+use gtk4::prelude::*;
+use gtk4::{ApplicationWindow, DialogFlags, DropDown, Entry, Grid, Label, MessageDialog, Orientation, ResponseType, ScrolledWindow, Widget};
+use gtk4::Box as GtkBox;
+use habitvault_core::csv_io::{ColumnMapping, CsvTable, DateFormat, ImportPlan, plan_import};
+use habitvault_core::habit::{HabitData, Quantity};
+use crate::note_editor::mood_label;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Rows shown in the preview. The summary above it covers the whole file.
+const PREVIEW_ROWS: usize = 50;
+
+/// Column pickers for the import dialog. Optional columns list "None" first,
+/// and the habit column "One habit for all rows" with a name entry.
+#[derive(Clone)]
+struct MappingForm {
+    widget: Grid,
+    habit_dropdown: DropDown,
+    habit_entry: Entry,
+    date_dropdown: DropDown,
+    format_dropdown: DropDown,
+    value_dropdown: DropDown,
+    notes_dropdown: DropDown,
+    mood_dropdown: DropDown,
+}
+
+impl MappingForm {
+    fn new(headers: &[String], mapping: &ColumnMapping) -> Self {
+        let headers: Vec<&str> = headers.iter().map(String::as_str).collect();
+        let optional = |first: &str, column: Option<usize>| {
+            let labels: Vec<&str> = std::iter::once(first).chain(headers.iter().copied()).collect();
+            let dropdown = DropDown::from_strings(&labels);
+            dropdown.set_selected(column.map_or(0, |column| column as u32 + 1));
+            dropdown
+        };
+        
+        let habit_dropdown = optional("One habit for all rows", mapping.habit);
+        let habit_entry = Entry::new();
+        habit_entry.set_placeholder_text(Some("Habit name"));
+        habit_entry.set_visible(mapping.habit.is_none());
+        
+        let date_dropdown = DropDown::from_strings(&headers);
+        date_dropdown.set_selected(mapping.date as u32);
+        
+        let format_labels: Vec<&str> = DateFormat::ALL.iter().map(|format| format.label()).collect();
+        let format_dropdown = DropDown::from_strings(&format_labels);
+        let format_index = DateFormat::ALL.iter().position(|format| *format == mapping.date_format).unwrap_or(0);
+        format_dropdown.set_selected(format_index as u32);
+        
+        let value_dropdown = optional("None", mapping.value);
+        value_dropdown.set_tooltip_text(Some("Amount for habits that track one. For other habits 0 or \"no\" skips the day"));
+        let notes_dropdown = optional("None", mapping.notes);
+        let mood_dropdown = optional("None", mapping.mood);
+        mood_dropdown.set_tooltip_text(Some("A number from 1 to 5"));
+        
+        let widget = Grid::new();
+        widget.set_row_spacing(5);
+        widget.set_column_spacing(10);
+        let rows: [(&str, &Widget); 6] = [
+            ("Habit:", habit_dropdown.upcast_ref()),
+            ("Date:", date_dropdown.upcast_ref()),
+            ("Date format:", format_dropdown.upcast_ref()),
+            ("Amount:", value_dropdown.upcast_ref()),
+            ("Notes:", notes_dropdown.upcast_ref()),
+            ("Mood:", mood_dropdown.upcast_ref()),
+        ];
+        for (row, (label, field)) in rows.into_iter().enumerate() {
+            let label = Label::new(Some(label));
+            label.set_xalign(0.0);
+            widget.attach(&label, 0, row as i32, 1, 1);
+            widget.attach(field, 1, row as i32, 1, 1);
+        }
+        widget.attach(&habit_entry, 2, 0, 1, 1);
+        
+        Self {
+            widget,
+            habit_dropdown,
+            habit_entry,
+            date_dropdown,
+            format_dropdown,
+            value_dropdown,
+            notes_dropdown,
+            mood_dropdown,
+        }
+    }
+    
+    fn mapping(&self) -> ColumnMapping {
+        let optional = |dropdown: &DropDown| dropdown.selected().checked_sub(1).map(|column| column as usize);
+        ColumnMapping {
+            habit: optional(&self.habit_dropdown),
+            habit_name: self.habit_entry.text().to_string(),
+            date: self.date_dropdown.selected() as usize,
+            date_format: DateFormat::ALL.get(self.format_dropdown.selected() as usize).copied().unwrap_or_default(),
+            value: optional(&self.value_dropdown),
+            notes: optional(&self.notes_dropdown),
+            mood: optional(&self.mood_dropdown),
+        }
+    }
+    
+    fn connect_changed(&self, on_changed: impl Fn() + 'static) {
+        let on_changed = Rc::new(on_changed);
+        for dropdown in [&self.habit_dropdown, &self.date_dropdown, &self.format_dropdown, &self.value_dropdown, &self.notes_dropdown, &self.mood_dropdown] {
+            let on_changed = on_changed.clone();
+            dropdown.connect_selected_notify(move |_| on_changed());
+        }
+        
+        let habit_entry = self.habit_entry.clone();
+        self.habit_dropdown.connect_selected_notify(move |dropdown| {
+            habit_entry.set_visible(dropdown.selected() == 0);
+        });
+        self.habit_entry.connect_changed(move |_| on_changed());
+    }
+}
+
+/// Asks which columns of `table` hold what and previews the rows as they
+/// would be imported. Nothing changes until Import is pressed, which hands
+/// the plan to `on_import`.
+pub fn show_csv_import_dialog(table: CsvTable, habit_data: &Rc<RefCell<HabitData>>, on_import: impl Fn(ImportPlan) + 'static) {
+    let dialog = MessageDialog::new(
+        None::<&ApplicationWindow>,
+        DialogFlags::MODAL,
+        gtk4::MessageType::Other,
+        gtk4::ButtonsType::None,
+        "Import CSV",
+    );
+    dialog.set_secondary_text(Some("Choose which columns hold what. Habits that don't exist yet are created as daily habits."));
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    dialog.add_button("Import", ResponseType::Accept);
+    dialog.set_default_size(700, -1);
+    
+    let content_area = dialog.content_area();
+    let vbox = GtkBox::new(Orientation::Vertical, 10);
+    vbox.set_margin_start(20);
+    vbox.set_margin_end(20);
+    
+    let form = MappingForm::new(&table.headers, &ColumnMapping::guess(&table));
+    vbox.append(&form.widget);
+    
+    let summary_label = Label::new(None);
+    summary_label.set_wrap(true);
+    summary_label.set_xalign(0.0);
+    vbox.append(&summary_label);
+    
+    let preview = ScrolledWindow::new();
+    preview.set_min_content_height(250);
+    preview.set_vexpand(true);
+    vbox.append(&preview);
+    
+    content_area.append(&vbox);
+    
+    let plan = Rc::new(RefCell::new(ImportPlan::default()));
+    let update = {
+        let form = form.clone();
+        let habit_data = habit_data.clone();
+        let plan = plan.clone();
+        let dialog = dialog.downgrade();
+        move || {
+            let new_plan = plan_import(&habit_data.borrow(), &table, &form.mapping());
+            summary_label.set_text(&describe_plan(&new_plan));
+            preview.set_child(Some(&build_preview(&new_plan, &habit_data.borrow())));
+            if let Some(dialog) = dialog.upgrade() {
+                dialog.set_response_sensitive(ResponseType::Accept, new_plan.imported_rows().next().is_some());
+            }
+            plan.replace(new_plan);
+        }
+    };
+    update();
+    form.connect_changed(update);
+    
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept {
+            on_import(plan.take());
+        }
+        dialog.close();
+    });
+    
+    dialog.show();
+}
+
+fn describe_plan(plan: &ImportPlan) -> String {
+    let imported = plan.imported_rows().count();
+    let mut summary = match imported {
+        1 => "1 day will be imported".to_string(),
+        count => format!("{} days will be imported", count),
+    };
+    match plan.new_habits.as_slice() {
+        [] => {}
+        [name] => summary.push_str(&format!(", creating the habit {}", name)),
+        names => summary.push_str(&format!(", creating {} habits: {}", names.len(), names.join(", "))),
+    }
+    match plan.skipped_rows() {
+        0 => {}
+        1 => summary.push_str(". 1 row is skipped"),
+        count => summary.push_str(&format!(". {} rows are skipped", count)),
+    }
+    summary.push('.');
+    summary
+}
+
+/// The first `PREVIEW_ROWS` rows as a table, with the reason next to each
+/// row that is skipped.
+fn build_preview(plan: &ImportPlan, habit_data: &HabitData) -> Grid {
+    let grid = Grid::new();
+    grid.set_row_spacing(3);
+    grid.set_column_spacing(15);
+    
+    for (column, title) in ["Line", "Habit", "Date", "Amount", "Mood", "Notes", ""].into_iter().enumerate() {
+        let label = Label::new(Some(title));
+        label.add_css_class("heading");
+        label.set_xalign(0.0);
+        grid.attach(&label, column as i32, 0, 1, 1);
+    }
+    
+    for (index, row) in plan.rows.iter().take(PREVIEW_ROWS).enumerate() {
+        let is_new = plan.new_habits.iter().any(|name| name.trim().eq_ignore_ascii_case(row.habit.trim()));
+        let status = match &row.skipped {
+            Some(reason) => reason.clone(),
+            None if is_new => "New habit".to_string(),
+            None => String::new(),
+        };
+        let is_quantity = habit_data.habits.iter()
+            .any(|habit| habit.quantity.is_some() && habit.name.trim().eq_ignore_ascii_case(row.habit.trim()));
+        let amount = match row.value {
+            Some(value) if is_quantity => Quantity::format_amount(value),
+            _ => String::new(),
+        };
+        let cells = [
+            row.line.to_string(),
+            row.habit.clone(),
+            row.date.map(|date| date.to_string()).unwrap_or_default(),
+            amount,
+            row.mood.map(|mood| mood_label(mood).to_string()).unwrap_or_default(),
+            row.notes.clone().unwrap_or_default(),
+            status,
+        ];
+        for (column, text) in cells.into_iter().enumerate() {
+            let label = Label::new(Some(&text));
+            label.set_xalign(0.0);
+            label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+            label.set_max_width_chars(30);
+            if row.skipped.is_some() {
+                label.add_css_class(if column == 6 { "error" } else { "dim-label" });
+            }
+            grid.attach(&label, column as i32, index as i32 + 1, 1, 1);
+        }
+    }
+    
+    if plan.rows.len() > PREVIEW_ROWS {
+        let more = Label::new(Some(&format!("… and {} more rows", plan.rows.len() - PREVIEW_ROWS)));
+        more.add_css_class("dim-label");
+        more.set_xalign(0.0);
+        grid.attach(&more, 0, PREVIEW_ROWS as i32 + 1, 7, 1);
+    }
+    
+    grid
+}
\ No newline at end of file
@@ -0,0 +1,200 @@
+use gtk4::prelude::*;
+use gtk4::{Button, Grid, Label, Orientation, PolicyType, ScrolledWindow};
+use gtk4::Box as GtkBox;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::rc::Rc;
+use std::cell::RefCell;
+use habitvault_core::habit::{week_start, HabitData};
+use crate::note_editor;
+
+/// Weeks shown, enough to cover the last 12 months.
+const WEEKS: i64 = 53;
+
+/// Year-at-a-glance grid with one cell per day, coloured by how much was
+/// done. Shows a single habit, or the average across all habits. Clicking a
+/// day calls `on_select` with its date.
+#[derive(Clone)]
+pub struct HabitHeatmap {
+    widget: ScrolledWindow,
+    content: GtkBox,
+    habit_data: Rc<RefCell<HabitData>>,
+    habit_id: Option<String>,
+    on_select: Rc<dyn Fn(NaiveDate)>,
+}
+
+impl HabitHeatmap {
+    pub fn new(
+        habit_data: Rc<RefCell<HabitData>>,
+        habit_id: Option<String>,
+        on_select: impl Fn(NaiveDate) + 'static,
+    ) -> Self {
+        let content = GtkBox::new(Orientation::Vertical, 5);
+        content.add_css_class("heatmap");
+        
+        // A year of weeks is wider than a narrow window
+        let widget = ScrolledWindow::new();
+        widget.set_policy(PolicyType::Automatic, PolicyType::Never);
+        widget.set_propagate_natural_height(true);
+        widget.set_child(Some(&content));
+        
+        Self {
+            widget,
+            content,
+            habit_data,
+            habit_id,
+            on_select: Rc::new(on_select),
+        }
+    }
+    
+    pub fn widget(&self) -> &ScrolledWindow {
+        &self.widget
+    }
+    
+    /// Rebuilds the grid from the current data. The heatmap is empty until
+    /// this is called and doesn't follow changes by itself, so call it
+    /// whenever it is shown.
+    pub fn refresh(&self) {
+        while let Some(child) = self.content.first_child() {
+            self.content.remove(&child);
+        }
+        
+        let today = self.habit_data.borrow().today();
+        let first_week = week_start(today) - Duration::weeks(WEEKS - 1);
+        
+        let grid = Grid::new();
+        grid.set_row_spacing(2);
+        grid.set_column_spacing(2);
+        grid.set_halign(gtk4::Align::Center);
+        
+        for (row, day) in ["Mon", "", "Wed", "", "Fri", "", ""].iter().enumerate() {
+            let label = Label::new(Some(day));
+            label.add_css_class("heatmap-label");
+            label.set_xalign(0.0);
+            grid.attach(&label, 0, row as i32 + 1, 1, 1);
+        }
+        
+        for week in 0..WEEKS {
+            let monday = first_week + Duration::weeks(week);
+            let column = week as i32 + 1;
+            
+            // Month names over the first week starting in that month
+            if week == 0 || monday.month() != (monday - Duration::weeks(1)).month() {
+                let label = Label::new(Some(&monday.format("%b").to_string()));
+                label.add_css_class("heatmap-label");
+                label.set_xalign(0.0);
+                grid.attach(&label, column, 0, 4, 1);
+            }
+            
+            for weekday in 0..7 {
+                let date = monday + Duration::days(weekday);
+                if date > today {
+                    break;
+                }
+                grid.attach(&self.create_cell(date), column, weekday as i32 + 1, 1, 1);
+            }
+        }
+        
+        self.content.append(&grid);
+        self.content.append(&Self::legend());
+    }
+    
+    /// Average progress of the habits that count on `date`, and how many of
+    /// them were fully done out of how many counted.
+    fn day_summary(&self, date: NaiveDate) -> (Option<f64>, usize, usize) {
+        let data = self.habit_data.borrow();
+        let rates: Vec<f64> = match &self.habit_id {
+            Some(habit_id) => data.rate_on_date(habit_id, date).into_iter().collect(),
+            None => data.habits.iter()
+                .filter_map(|habit| data.rate_on_date(&habit.id, date))
+                .collect(),
+        };
+        if rates.is_empty() {
+            return (None, 0, 0);
+        }
+        let done = rates.iter().filter(|rate| **rate >= 1.0).count();
+        (Some(rates.iter().sum::<f64>() / rates.len() as f64), done, rates.len())
+    }
+    
+    fn create_cell(&self, date: NaiveDate) -> Button {
+        let cell = Button::new();
+        cell.set_size_request(12, 12);
+        cell.add_css_class("heatmap-cell");
+        
+        let (rate, done, counted) = self.day_summary(date);
+        cell.add_css_class(match rate {
+            None => "heatmap-level-0",
+            Some(rate) if rate <= 0.0 => "heatmap-level-0",
+            Some(rate) if rate < 0.5 => "heatmap-level-1",
+            Some(rate) if rate < 0.75 => "heatmap-level-2",
+            Some(rate) if rate < 1.0 => "heatmap-level-3",
+            Some(_) => "heatmap-level-4",
+        });
+        if rate.is_none() {
+            cell.add_css_class("heatmap-not-due");
+        }
+        
+        cell.set_tooltip_text(Some(&self.describe_day(date, rate, done, counted)));
+        
+        let on_select = self.on_select.clone();
+        cell.connect_clicked(move |_| on_select(date));
+        
+        cell
+    }
+    
+    fn describe_day(&self, date: NaiveDate, rate: Option<f64>, done: usize, counted: usize) -> String {
+        let data = self.habit_data.borrow();
+        let mut lines = vec![date.format("%a, %b %-d, %Y").to_string()];
+        
+        match &self.habit_id {
+            Some(habit_id) => {
+                let quantity = data.get_habit_by_id(habit_id).and_then(|habit| habit.quantity.as_ref());
+                lines.push(match (rate, quantity) {
+                    (None, _) => "Not due".to_string(),
+                    (Some(_), Some(quantity)) => quantity.describe_progress(data.value_on_date(habit_id, date)),
+                    (Some(_), None) if done > 0 => "Done".to_string(),
+                    (Some(_), None) => "Not done".to_string(),
+                });
+                if let Some(note) = data.completion_on_date(habit_id, date)
+                    .and_then(|c| note_editor::describe_note(c.notes.as_deref(), c.mood)) {
+                    lines.push(note);
+                }
+            }
+            None => {
+                lines.push(if counted == 0 {
+                    "Nothing due".to_string()
+                } else {
+                    format!("{}/{} habits done", done, counted)
+                });
+                for habit in &data.habits {
+                    if let Some(note) = data.completion_on_date(&habit.id, date)
+                        .and_then(|c| note_editor::describe_note(c.notes.as_deref(), c.mood)) {
+                        lines.push(format!("{}: {}", habit.name, note));
+                    }
+                }
+            }
+        }
+        
+        lines.join("\n")
+    }
+    
+    fn legend() -> GtkBox {
+        let legend = GtkBox::new(Orientation::Horizontal, 2);
+        legend.set_halign(gtk4::Align::End);
+        
+        let less = Label::new(Some("Less"));
+        less.add_css_class("heatmap-label");
+        legend.append(&less);
+        for level in 0..=4 {
+            let swatch = GtkBox::new(Orientation::Horizontal, 0);
+            swatch.set_size_request(12, 12);
+            swatch.add_css_class("heatmap-cell");
+            swatch.add_css_class(&format!("heatmap-level-{}", level));
+            legend.append(&swatch);
+        }
+        let more = Label::new(Some("More"));
+        more.add_css_class("heatmap-label");
+        legend.append(&more);
+        
+        legend
+    }
+}
\ No newline at end of file
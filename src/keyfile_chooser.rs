@@ -0,0 +1,143 @@
+use gtk4::prelude::*;
+use gtk4::{Button, FileChooserAction, FileChooserDialog, Label, Orientation, ResponseType, Window};
+use gtk4::Box as GtkBox;
+use crate::storage_service::StorageService;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Row for picking a keyfile, shared by the unlock dialog and settings. Only
+/// the path is held; the file is read when the form is submitted.
+#[derive(Clone)]
+pub struct KeyfileChooser {
+    widget: GtkBox,
+    path: Rc<RefCell<Option<PathBuf>>>,
+    path_label: Label,
+    clear_button: Button,
+    error_label: Label,
+}
+
+impl KeyfileChooser {
+    /// With `storage`, also offers to create a new random keyfile.
+    pub fn new(path: Option<PathBuf>, storage: Option<&StorageService>) -> Self {
+        let widget = GtkBox::new(Orientation::Vertical, 5);
+        
+        let row = GtkBox::new(Orientation::Horizontal, 5);
+        let path_label = Label::new(None);
+        path_label.set_hexpand(true);
+        path_label.set_xalign(0.0);
+        path_label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+        
+        let choose_button = Button::with_label("Choose Keyfile…");
+        let clear_button = Button::from_icon_name("edit-clear-symbolic");
+        clear_button.set_tooltip_text(Some("Don't use a keyfile"));
+        
+        row.append(&path_label);
+        row.append(&clear_button);
+        row.append(&choose_button);
+        
+        let error_label = Label::new(None);
+        error_label.add_css_class("error");
+        error_label.set_wrap(true);
+        error_label.set_visible(false);
+        
+        widget.append(&row);
+        widget.append(&error_label);
+        
+        let chooser = Self {
+            widget,
+            path: Rc::new(RefCell::new(None)),
+            path_label,
+            clear_button,
+            error_label,
+        };
+        chooser.set_path(path);
+        
+        let chooser_clone = chooser.clone();
+        choose_button.connect_clicked(move |_| {
+            chooser_clone.run_file_dialog("Choose Keyfile", FileChooserAction::Open, "Open", |chooser, path| {
+                chooser.set_path(Some(path));
+            });
+        });
+        
+        let chooser_clone = chooser.clone();
+        chooser.clear_button.connect_clicked(move |_| {
+            chooser_clone.set_path(None);
+        });
+        
+        if let Some(storage) = storage {
+            let create_button = Button::with_label("Create…");
+            create_button.set_tooltip_text(Some("Create a new random keyfile, for example on a USB stick"));
+            row.append(&create_button);
+            
+            let chooser_clone = chooser.clone();
+            let storage = storage.clone();
+            create_button.connect_clicked(move |_| {
+                let storage = storage.clone();
+                chooser_clone.run_file_dialog("Create Keyfile", FileChooserAction::Save, "Create", move |chooser, path| {
+                    let chooser = chooser.clone();
+                    storage.create_keyfile(path.clone(), move |result| match result {
+                        Ok(()) => chooser.set_path(Some(path)),
+                        Err(e) => chooser.show_error(&format!("Can't create keyfile: {}", e)),
+                    });
+                });
+            });
+        }
+        
+        chooser
+    }
+    
+    pub fn widget(&self) -> &GtkBox {
+        &self.widget
+    }
+    
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path.borrow().clone()
+    }
+    
+    pub fn set_path(&self, path: Option<PathBuf>) {
+        match &path {
+            Some(path) => {
+                self.path_label.set_text(&path.display().to_string());
+                self.path_label.remove_css_class("dim-label");
+            }
+            None => {
+                self.path_label.set_text("No keyfile selected");
+                self.path_label.add_css_class("dim-label");
+            }
+        }
+        self.clear_button.set_sensitive(path.is_some());
+        self.error_label.set_visible(false);
+        self.path.replace(path);
+    }
+    
+    fn show_error(&self, message: &str) {
+        self.error_label.set_text(message);
+        self.error_label.set_visible(true);
+    }
+    
+    fn run_file_dialog(&self, title: &str, action: FileChooserAction, accept_label: &str, on_path: impl Fn(&Self, PathBuf) + 'static) {
+        let parent = self.widget.root().and_downcast::<Window>();
+        let file_chooser = FileChooserDialog::new(
+            Some(title),
+            parent.as_ref(),
+            action,
+            &[("Cancel", ResponseType::Cancel), (accept_label, ResponseType::Accept)]
+        );
+        file_chooser.set_modal(true);
+        if action == FileChooserAction::Save {
+            file_chooser.set_current_name("habitvault.key");
+        }
+        
+        let chooser = self.clone();
+        file_chooser.connect_response(move |dialog, response| {
+            if response == ResponseType::Accept
+                && let Some(path) = dialog.file().and_then(|file| file.path()) {
+                on_path(&chooser, path);
+            }
+            dialog.close();
+        });
+        
+        file_chooser.show();
+    }
+}
\ No newline at end of file
@@ -1,8 +1,15 @@
-mod encryption;
-mod habit;
-mod storage;
+mod storage_service;
 mod ui;
 mod calendar;
+mod heatmap;
+mod stats_view;
+mod trend_chart;
+mod trends_view;
+mod schedule_editor;
+mod quantity_editor;
+mod note_editor;
+mod keyfile_chooser;
+mod csv_import;
 
 use libadwaita::prelude::*;
 use libadwaita::Application;
@@ -16,6 +23,9 @@ fn main() {
     let app = Application::builder()
         .application_id(APP_ID)
         .build();
+    
+    // Needed for screensaver-active, which auto-lock watches
+    app.set_register_session(true);
 
     app.connect_activate(|app| {
         match HabitApp::new(app) {
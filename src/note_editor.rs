@@ -0,0 +1,101 @@
+use gtk4::prelude::*;
+use gtk4::{Button, Label, Orientation, Popover, ScrolledWindow, TextView, ToggleButton, WrapMode};
+use gtk4::Box as GtkBox;
+use chrono::NaiveDate;
+use std::rc::Rc;
+
+/// Mood choices from 1 (worst) to 5 (best), as stored in
+/// `HabitCompletion::mood`.
+pub const MOODS: [&str; 5] = ["😞", "🙁", "😐", "🙂", "😄"];
+
+pub fn mood_label(mood: u8) -> &'static str {
+    MOODS[(mood.clamp(1, 5) - 1) as usize]
+}
+
+/// Tooltip text for a day's note and mood, if it has either.
+pub fn describe_note(notes: Option<&str>, mood: Option<u8>) -> Option<String> {
+    match (mood, notes) {
+        (Some(mood), Some(notes)) => Some(format!("{} {}", mood_label(mood), notes)),
+        (Some(mood), None) => Some(mood_label(mood).to_string()),
+        (None, Some(notes)) => Some(notes.to_string()),
+        (None, None) => None,
+    }
+}
+
+/// Popover for viewing and editing the note and mood of one calendar day,
+/// whether or not the habit was done that day.
+pub fn build_note_popover(
+    date: NaiveDate,
+    notes: Option<&str>,
+    mood: Option<u8>,
+    on_save: impl Fn(Option<String>, Option<u8>) + 'static,
+) -> Popover {
+    let popover = Popover::new();
+    
+    let vbox = GtkBox::new(Orientation::Vertical, 5);
+    vbox.set_margin_top(5);
+    vbox.set_margin_bottom(5);
+    vbox.set_margin_start(5);
+    vbox.set_margin_end(5);
+    
+    let title = Label::new(Some(&date.format("%A, %B %-d").to_string()));
+    title.add_css_class("heading");
+    vbox.append(&title);
+    
+    // Toggles rather than a radio group so the active mood can be cleared
+    let mood_row = GtkBox::new(Orientation::Horizontal, 2);
+    mood_row.set_halign(gtk4::Align::Center);
+    let mood_buttons: Rc<Vec<ToggleButton>> = Rc::new(MOODS.iter()
+        .map(|label| {
+            let button = ToggleButton::with_label(label);
+            button.add_css_class("flat");
+            mood_row.append(&button);
+            button
+        })
+        .collect());
+    if let Some(mood) = mood {
+        mood_buttons[(mood.clamp(1, 5) - 1) as usize].set_active(true);
+    }
+    for button in mood_buttons.iter() {
+        let mood_buttons = Rc::downgrade(&mood_buttons);
+        button.connect_toggled(move |button| {
+            let Some(mood_buttons) = mood_buttons.upgrade() else {
+                return;
+            };
+            if button.is_active() {
+                for other in mood_buttons.iter().filter(|other| *other != button) {
+                    other.set_active(false);
+                }
+            }
+        });
+    }
+    vbox.append(&mood_row);
+    
+    let text_view = TextView::new();
+    text_view.set_wrap_mode(WrapMode::WordChar);
+    text_view.buffer().set_text(notes.unwrap_or(""));
+    let scrolled = ScrolledWindow::new();
+    scrolled.set_size_request(220, 80);
+    scrolled.set_child(Some(&text_view));
+    vbox.append(&scrolled);
+    
+    let save_button = Button::with_label("Save");
+    save_button.add_css_class("suggested-action");
+    vbox.append(&save_button);
+    
+    popover.set_child(Some(&vbox));
+    
+    let popover_clone = popover.clone();
+    save_button.connect_clicked(move |_| {
+        let buffer = text_view.buffer();
+        let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+        let notes = Some(text.trim().to_string()).filter(|notes| !notes.is_empty());
+        let mood = mood_buttons.iter()
+            .position(ToggleButton::is_active)
+            .map(|index| index as u8 + 1);
+        popover_clone.popdown();
+        on_save(notes, mood);
+    });
+    
+    popover
+}
\ No newline at end of file
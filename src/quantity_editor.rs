@@ -0,0 +1,122 @@
+use gtk4::prelude::*;
+use gtk4::{Button, Entry, Label, Orientation, Popover, SpinButton, Switch};
+use gtk4::Box as GtkBox;
+use habitvault_core::habit::Quantity;
+
+/// Form section for turning a habit into an amount-based one, shared by the
+/// add and edit dialogs.
+pub struct QuantityEditor {
+    widget: GtkBox,
+    enabled_switch: Switch,
+    unit_entry: Entry,
+    target_spin: SpinButton,
+    error_label: Label,
+}
+
+impl QuantityEditor {
+    pub fn new(quantity: Option<&Quantity>) -> Self {
+        let widget = GtkBox::new(Orientation::Vertical, 5);
+        
+        let switch_row = GtkBox::new(Orientation::Horizontal, 10);
+        switch_row.append(&Label::new(Some("Measure an amount")));
+        let enabled_switch = Switch::new();
+        enabled_switch.set_active(quantity.is_some());
+        switch_row.append(&enabled_switch);
+        
+        let details = GtkBox::new(Orientation::Horizontal, 5);
+        let target_spin = SpinButton::with_range(0.5, 100000.0, 1.0);
+        target_spin.set_digits(1);
+        target_spin.set_value(quantity.map_or(1.0, |q| q.daily_target));
+        let unit_entry = Entry::new();
+        unit_entry.set_placeholder_text(Some("Unit, e.g. glasses"));
+        if let Some(quantity) = quantity {
+            unit_entry.set_text(&quantity.unit);
+        }
+        details.append(&Label::new(Some("Daily target:")));
+        details.append(&target_spin);
+        details.append(&unit_entry);
+        details.set_visible(quantity.is_some());
+        
+        let error_label = Label::new(None);
+        error_label.add_css_class("error");
+        error_label.set_visible(false);
+        
+        let details_clone = details.clone();
+        let error_label_clone = error_label.clone();
+        enabled_switch.connect_active_notify(move |switch| {
+            details_clone.set_visible(switch.is_active());
+            error_label_clone.set_visible(false);
+        });
+        
+        widget.append(&switch_row);
+        widget.append(&details);
+        widget.append(&error_label);
+        
+        Self {
+            widget,
+            enabled_switch,
+            unit_entry,
+            target_spin,
+            error_label,
+        }
+    }
+    
+    pub fn widget(&self) -> &GtkBox {
+        &self.widget
+    }
+    
+    /// The quantity described by the form, `Some(None)` for a plain done/not
+    /// done habit, or `None` after showing an inline error.
+    pub fn quantity(&self) -> Option<Option<Quantity>> {
+        if !self.enabled_switch.is_active() {
+            return Some(None);
+        }
+        
+        let unit = self.unit_entry.text().trim().to_string();
+        if unit.is_empty() {
+            self.error_label.set_text("Enter a unit for the amount");
+            self.error_label.set_visible(true);
+            return None;
+        }
+        
+        self.error_label.set_visible(false);
+        Some(Some(Quantity {
+            unit,
+            daily_target: self.target_spin.value(),
+        }))
+    }
+}
+
+/// Popover for logging the amount done on one day. `on_log` receives the new
+/// total after the popover has been closed.
+pub fn build_log_popover(quantity: &Quantity, current: f64, on_log: impl Fn(f64) + 'static) -> Popover {
+    let popover = Popover::new();
+    
+    let vbox = GtkBox::new(Orientation::Vertical, 5);
+    vbox.set_margin_top(5);
+    vbox.set_margin_bottom(5);
+    vbox.set_margin_start(5);
+    vbox.set_margin_end(5);
+    
+    let row = GtkBox::new(Orientation::Horizontal, 5);
+    let value_spin = SpinButton::with_range(0.0, 100000.0, 1.0);
+    value_spin.set_digits(if quantity.daily_target.fract() == 0.0 { 0 } else { 1 });
+    value_spin.set_value(current);
+    row.append(&value_spin);
+    row.append(&Label::new(Some(&format!("/ {} {}", Quantity::format_amount(quantity.daily_target), quantity.unit))));
+    
+    let log_button = Button::with_label("Log");
+    log_button.add_css_class("suggested-action");
+    
+    vbox.append(&row);
+    vbox.append(&log_button);
+    popover.set_child(Some(&vbox));
+    
+    let popover_clone = popover.clone();
+    log_button.connect_clicked(move |_| {
+        popover_clone.popdown();
+        on_log(value_spin.value());
+    });
+    
+    popover
+}
\ No newline at end of file
@@ -0,0 +1,205 @@
+use gtk4::prelude::*;
+use gtk4::{DropDown, Entry, Label, Orientation, SpinButton, ToggleButton};
+use gtk4::Box as GtkBox;
+use chrono::{NaiveDate, Weekday};
+use habitvault_core::habit::Schedule;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu,
+    Weekday::Fri, Weekday::Sat, Weekday::Sun,
+];
+
+/// Form section for choosing a habit's schedule, shared by the add and edit dialogs.
+pub struct ScheduleEditor {
+    widget: GtkBox,
+    kind_dropdown: DropDown,
+    target_spin: SpinButton,
+    weekday_buttons: Vec<ToggleButton>,
+    interval_spin: SpinButton,
+    times_spin: SpinButton,
+    start_entry: Entry,
+    end_entry: Entry,
+    error_label: Label,
+    anchor: NaiveDate,
+}
+
+impl ScheduleEditor {
+    pub fn new(schedule: &Schedule, target_days_per_week: u8, today: NaiveDate) -> Self {
+        let widget = GtkBox::new(Orientation::Vertical, 5);
+        
+        let kind_dropdown = DropDown::from_strings(&[
+            "Every day",
+            "Specific weekdays",
+            "Every N days",
+            "N times per month",
+            "Date range",
+        ]);
+        
+        // Every day: optional weekly goal
+        let target_box = GtkBox::new(Orientation::Horizontal, 5);
+        let target_spin = SpinButton::with_range(1.0, 7.0, 1.0);
+        target_spin.set_value(target_days_per_week as f64);
+        target_spin.set_tooltip_text(Some("Habits with a target below 7 keep a weekly streak"));
+        target_box.append(&Label::new(Some("Target days per week:")));
+        target_box.append(&target_spin);
+        
+        // Specific weekdays
+        let weekdays_box = GtkBox::new(Orientation::Horizontal, 2);
+        let weekday_buttons: Vec<ToggleButton> = WEEKDAYS.iter()
+            .map(|day| {
+                let button = ToggleButton::with_label(&day.to_string());
+                weekdays_box.append(&button);
+                button
+            })
+            .collect();
+        
+        // Every N days
+        let interval_box = GtkBox::new(Orientation::Horizontal, 5);
+        let interval_spin = SpinButton::with_range(2.0, 365.0, 1.0);
+        interval_box.append(&Label::new(Some("Every")));
+        interval_box.append(&interval_spin);
+        interval_box.append(&Label::new(Some("days")));
+        
+        // N times per month
+        let times_box = GtkBox::new(Orientation::Horizontal, 5);
+        let times_spin = SpinButton::with_range(1.0, 31.0, 1.0);
+        times_box.append(&times_spin);
+        times_box.append(&Label::new(Some("times per month")));
+        
+        // Date range
+        let range_box = GtkBox::new(Orientation::Horizontal, 5);
+        let start_entry = Entry::new();
+        start_entry.set_placeholder_text(Some("Start (YYYY-MM-DD)"));
+        let end_entry = Entry::new();
+        end_entry.set_placeholder_text(Some("End (YYYY-MM-DD)"));
+        range_box.append(&start_entry);
+        range_box.append(&end_entry);
+        
+        let error_label = Label::new(None);
+        error_label.add_css_class("error");
+        error_label.set_visible(false);
+        
+        let mut anchor = today;
+        
+        // Populate from the current schedule
+        let selected = match schedule {
+            Schedule::Daily => 0,
+            Schedule::Weekdays { days } => {
+                for (day, button) in WEEKDAYS.iter().zip(&weekday_buttons) {
+                    button.set_active(days.contains(day));
+                }
+                1
+            }
+            Schedule::EveryNDays { interval, anchor: existing_anchor } => {
+                interval_spin.set_value(*interval as f64);
+                anchor = *existing_anchor;
+                2
+            }
+            Schedule::TimesPerMonth { times } => {
+                times_spin.set_value(*times as f64);
+                3
+            }
+            Schedule::DateRange { start, end } => {
+                start_entry.set_text(&start.to_string());
+                end_entry.set_text(&end.to_string());
+                4
+            }
+        };
+        kind_dropdown.set_selected(selected);
+        
+        let sections = [target_box, weekdays_box, interval_box, times_box, range_box];
+        for (i, section) in sections.iter().enumerate() {
+            section.set_visible(i as u32 == selected);
+        }
+        
+        // Only show the options for the selected schedule kind
+        let sections_clone = sections.clone();
+        let error_label_clone = error_label.clone();
+        kind_dropdown.connect_selected_notify(move |dropdown| {
+            for (i, section) in sections_clone.iter().enumerate() {
+                section.set_visible(i as u32 == dropdown.selected());
+            }
+            error_label_clone.set_visible(false);
+        });
+        
+        widget.append(&Label::new(Some("Repeat:")));
+        widget.append(&kind_dropdown);
+        for section in &sections {
+            widget.append(section);
+        }
+        widget.append(&error_label);
+        
+        Self {
+            widget,
+            kind_dropdown,
+            target_spin,
+            weekday_buttons,
+            interval_spin,
+            times_spin,
+            start_entry,
+            end_entry,
+            error_label,
+            anchor,
+        }
+    }
+    
+    pub fn widget(&self) -> &GtkBox {
+        &self.widget
+    }
+    
+    pub fn target_days_per_week(&self) -> u8 {
+        if self.kind_dropdown.selected() == 0 {
+            self.target_spin.value_as_int() as u8
+        } else {
+            7
+        }
+    }
+    
+    /// The schedule described by the form, or `None` after showing an
+    /// inline error when the input is incomplete.
+    pub fn schedule(&self) -> Option<Schedule> {
+        let result = match self.kind_dropdown.selected() {
+            1 => {
+                let days: Vec<Weekday> = WEEKDAYS.iter()
+                    .zip(&self.weekday_buttons)
+                    .filter(|(_, button)| button.is_active())
+                    .map(|(day, _)| *day)
+                    .collect();
+                if days.is_empty() {
+                    Err("Select at least one weekday")
+                } else {
+                    Ok(Schedule::Weekdays { days })
+                }
+            }
+            2 => Ok(Schedule::EveryNDays {
+                interval: self.interval_spin.value_as_int() as u32,
+                anchor: self.anchor,
+            }),
+            3 => Ok(Schedule::TimesPerMonth {
+                times: self.times_spin.value_as_int() as u32,
+            }),
+            4 => {
+                let start = NaiveDate::parse_from_str(self.start_entry.text().trim(), "%Y-%m-%d");
+                let end = NaiveDate::parse_from_str(self.end_entry.text().trim(), "%Y-%m-%d");
+                match (start, end) {
+                    (Ok(start), Ok(end)) if start <= end => Ok(Schedule::DateRange { start, end }),
+                    (Ok(_), Ok(_)) => Err("The end date must not be before the start date"),
+                    _ => Err("Enter dates as YYYY-MM-DD"),
+                }
+            }
+            _ => Ok(Schedule::Daily),
+        };
+        
+        match result {
+            Ok(schedule) => {
+                self.error_label.set_visible(false);
+                Some(schedule)
+            }
+            Err(message) => {
+                self.error_label.set_text(message);
+                self.error_label.set_visible(true);
+                None
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,136 @@
+use gtk4::prelude::*;
+use gtk4::{Grid, Label, Orientation};
+use gtk4::Box as GtkBox;
+use chrono::Weekday;
+use std::rc::Rc;
+use std::cell::RefCell;
+use habitvault_core::habit::{Habit, HabitData};
+use habitvault_core::stats::{self, StreakRun};
+
+/// Streaks listed in the history, most recent first.
+const HISTORY_LENGTH: usize = 5;
+
+/// Statistics tab of an expanded habit.
+#[derive(Clone)]
+pub struct HabitStatsView {
+    widget: GtkBox,
+    habit_data: Rc<RefCell<HabitData>>,
+    habit_id: String,
+}
+
+impl HabitStatsView {
+    pub fn new(habit_data: Rc<RefCell<HabitData>>, habit_id: String) -> Self {
+        let widget = GtkBox::new(Orientation::Vertical, 10);
+        widget.add_css_class("habit-stats");
+        Self { widget, habit_data, habit_id }
+    }
+    
+    pub fn widget(&self) -> &GtkBox {
+        &self.widget
+    }
+    
+    /// Recomputes the statistics. Like the heatmap, the view is empty until
+    /// this is called and should be refreshed whenever it is shown.
+    pub fn refresh(&self) {
+        while let Some(child) = self.widget.first_child() {
+            self.widget.remove(&child);
+        }
+        
+        let data = self.habit_data.borrow();
+        let Some(habit) = data.get_habit_by_id(&self.habit_id) else {
+            return;
+        };
+        let Some(stats) = stats::habit_stats(&data, &self.habit_id, data.today()) else {
+            return;
+        };
+        
+        let rates: Vec<(String, String)> = stats.completion_rates.iter()
+            .map(|(days, rate)| (format!("Last {} days", days), format_rate(*rate)))
+            .collect();
+        self.widget.append(&Self::section("Completion rate", &rates));
+        
+        let describe_weekday = |day: Option<(Weekday, f64)>| match day {
+            Some((weekday, rate)) => format!("{} ({})", weekday_name(weekday), format_rate(Some(rate))),
+            None => "–".to_string(),
+        };
+        self.widget.append(&Self::section("Overview", &[
+            ("Total completions".to_string(), stats.total_completions.to_string()),
+            ("Average per week".to_string(), format!("{:.1} days", stats.average_per_week)),
+            ("Best day".to_string(), describe_weekday(stats.best_weekday())),
+            ("Worst day".to_string(), describe_weekday(stats.worst_weekday())),
+        ]));
+        
+        let mut streaks = vec![
+            ("Current streak".to_string(), streak_length(habit, stats.current_streak)),
+            ("Longest streak".to_string(), streak_length(habit, stats.longest_streak)),
+        ];
+        streaks.extend(stats.streak_history.iter()
+            .rev()
+            .take(HISTORY_LENGTH)
+            .map(|run| (describe_run(run), streak_length(habit, run.length))));
+        self.widget.append(&Self::section("Streaks", &streaks));
+    }
+    
+    fn section(title: &str, rows: &[(String, String)]) -> GtkBox {
+        let section = GtkBox::new(Orientation::Vertical, 5);
+        let heading = Label::new(Some(title));
+        heading.add_css_class("heading");
+        heading.set_xalign(0.0);
+        section.append(&heading);
+        
+        let grid = Grid::new();
+        grid.set_row_spacing(2);
+        grid.set_column_spacing(20);
+        for (row, (name, value)) in rows.iter().enumerate() {
+            let name_label = Label::new(Some(name));
+            name_label.set_xalign(0.0);
+            name_label.add_css_class("dim-label");
+            let value_label = Label::new(Some(value));
+            value_label.set_xalign(0.0);
+            grid.attach(&name_label, 0, row as i32, 1, 1);
+            grid.attach(&value_label, 1, row as i32, 1, 1);
+        }
+        section.append(&grid);
+        
+        section
+    }
+}
+
+fn format_rate(rate: Option<f64>) -> String {
+    match rate {
+        Some(rate) => format!("{:.0}%", rate * 100.0),
+        None => "–".to_string(),
+    }
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+/// e.g. "6 days", or weeks and months for goals.
+fn streak_length(habit: &Habit, length: u32) -> String {
+    let unit = if habit.is_weekly_goal() {
+        "week"
+    } else if habit.is_monthly_goal() {
+        "month"
+    } else {
+        "day"
+    };
+    format!("{} {}{}", length, unit, if length == 1 { "" } else { "s" })
+}
+
+fn describe_run(run: &StreakRun) -> String {
+    if run.start == run.end {
+        run.start.format("%b %-d, %Y").to_string()
+    } else {
+        format!("{} – {}", run.start.format("%b %-d"), run.end.format("%b %-d, %Y"))
+    }
+}
\ No newline at end of file
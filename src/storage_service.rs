@@ -0,0 +1,447 @@
+use habitvault_core::csv_io::{self, CsvTable};
+use habitvault_core::encryption::{Credentials, KdfProfile};
+use habitvault_core::habit::HabitData;
+use habitvault_core::lockout::UnlockAttempts;
+use habitvault_core::settings::Settings;
+use habitvault_core::storage::{self, SecureStorage, Session, StorageError, VaultLock, encrypt_vault};
+use habitvault_core::vault::KeySlotKind;
+use gtk4::{gio, glib};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// How long unlocking waits for a command line write to finish.
+const LOCK_WAIT: Duration = Duration::from_secs(5);
+
+/// Runs `SecureStorage` work on background threads so the UI never blocks on
+/// disk I/O or key derivation. Results are delivered back on the main thread.
+///
+/// Writes go through a queue that only ever keeps the newest pending version,
+/// so a burst of edits results in at most one write in flight and one queued.
+#[derive(Clone)]
+pub struct StorageService {
+    storage: SecureStorage,
+    state: Rc<ServiceState>,
+}
+
+struct ServiceState {
+    jobs: RefCell<VecDeque<Job>>,
+    running: Cell<bool>,
+    /// Counts locks, so work started on the unlocked vault can tell that it
+    /// was locked before the work finished.
+    lock_generation: Cell<u64>,
+    /// Held while the vault is unlocked, so the command line tool doesn't
+    /// write changes that the next save here would overwrite.
+    vault_lock: RefCell<Option<VaultLock>>,
+    /// Set when the vault was locked with saves still queued, which release
+    /// `vault_lock` once they are written.
+    release_when_saved: Cell<bool>,
+    /// Descriptions of the operations in progress, oldest first.
+    activity: RefCell<Vec<&'static str>>,
+    on_activity: Box<dyn Fn(Option<&'static str>)>,
+    on_error: Box<dyn Fn(String)>,
+}
+
+enum Job {
+    Write(Vec<u8>),
+    /// A write whose session has new credentials or a new data key, along
+    /// with the session it replaces. See `SecureStorage::write_rekeyed_vault`.
+    WriteRekeyed(Vec<u8>, Box<Session>, Box<Session>),
+    Delete(Box<dyn FnOnce(Result<(), StorageError>)>),
+}
+
+/// A password and keyfile as entered in a form. The keyfile is only read on
+/// the worker thread, since it can be any file of any size.
+pub struct CredentialInput {
+    /// Empty when only a keyfile is used.
+    pub password: Zeroizing<String>,
+    pub keyfile: Option<PathBuf>,
+}
+
+impl CredentialInput {
+    fn read(self) -> Result<Credentials, StorageError> {
+        let keyfile = match &self.keyfile {
+            Some(path) => Some(storage::read_keyfile(path)?),
+            None => None,
+        };
+        Ok(Credentials::new(self.password, keyfile)?)
+    }
+}
+
+/// Everything a successful unlock produces.
+pub struct Unlocked {
+    pub data: HabitData,
+    pub session: Session,
+    /// The previous generation the data was recovered from, if the data file
+    /// itself was unreadable.
+    pub recovered_from: Option<PathBuf>,
+    /// Set when the unlock method's KDF was weaker than the configured
+    /// profile and was re-wrapped. The caller should save to persist it.
+    pub upgraded_kdf: bool,
+    /// The session as it was unlocked, when `session` has since been given a
+    /// new data key. The caller saves with `save_rekeyed` to persist it.
+    pub rekeyed_from: Option<Session>,
+}
+
+impl StorageService {
+    /// `on_activity` is called with a short description of what is running,
+    /// or `None` once everything has finished. `on_error` receives messages
+    /// for failures that have no other caller to report to, i.e. saves.
+    pub fn new(
+        storage: SecureStorage,
+        on_activity: impl Fn(Option<&'static str>) + 'static,
+        on_error: impl Fn(String) + 'static,
+    ) -> Self {
+        Self {
+            storage,
+            state: Rc::new(ServiceState {
+                jobs: RefCell::new(VecDeque::new()),
+                running: Cell::new(false),
+                lock_generation: Cell::new(0),
+                vault_lock: RefCell::new(None),
+                release_when_saved: Cell::new(false),
+                activity: RefCell::new(Vec::new()),
+                on_activity: Box::new(on_activity),
+                on_error: Box::new(on_error),
+            }),
+        }
+    }
+    
+    pub fn exists(&self) -> bool {
+        self.storage.exists()
+    }
+    
+    /// Encrypts a snapshot of `data` right away and queues it to be written.
+    pub fn save(&self, data: &HabitData, session: &Session) {
+        let vault_bytes = match encrypt_vault(data, session) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                (self.state.on_error)(format!("Failed to save data: {}", e));
+                return;
+            }
+        };
+        
+        {
+            let mut jobs = self.state.jobs.borrow_mut();
+            // A queued write that hasn't started yet is superseded by this one
+            if let Some(Job::Write(pending) | Job::WriteRekeyed(pending, ..)) = jobs.back_mut() {
+                *pending = vault_bytes;
+            } else {
+                jobs.push_back(Job::Write(vault_bytes));
+            }
+        }
+        self.run_queue();
+    }
+    
+    /// Like `save`, after `session` was given new credentials or a new data
+    /// key. Earlier versions on disk are re-encrypted for `session` too.
+    pub fn save_rekeyed(&self, data: &HabitData, previous: &Session, session: &Session) {
+        let vault_bytes = match encrypt_vault(data, session) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                (self.state.on_error)(format!("Failed to save data: {}", e));
+                return;
+            }
+        };
+        
+        {
+            let mut jobs = self.state.jobs.borrow_mut();
+            // The re-encryption must happen even if a later save supersedes this one
+            if let Some(Job::Write(_)) = jobs.back() {
+                jobs.pop_back();
+            }
+            jobs.push_back(Job::WriteRekeyed(vault_bytes, Box::new(previous.clone()), Box::new(session.clone())));
+        }
+        self.run_queue();
+    }
+    
+    /// Deletes the vault once any queued writes have finished, dropping those
+    /// that haven't started.
+    pub fn delete_all_data(&self, done: impl FnOnce(Result<(), StorageError>) + 'static) {
+        {
+            let mut jobs = self.state.jobs.borrow_mut();
+            jobs.retain(|job| !matches!(job, Job::Write(_) | Job::WriteRekeyed(..)));
+            jobs.push_back(Job::Delete(Box::new(done)));
+        }
+        self.run_queue();
+    }
+    
+    /// Drops the results of work started before now that would write data or
+    /// a session back, as the vault they belong to has been locked. Queued
+    /// saves still go through, after which other processes may write to the
+    /// vault.
+    pub fn lock(&self) {
+        self.state.lock_generation.set(self.state.lock_generation.get() + 1);
+        if self.state.running.get() {
+            self.state.release_when_saved.set(true);
+        } else {
+            self.state.vault_lock.take();
+        }
+    }
+    
+    /// See `SecureStorage::unlock_method`.
+    pub fn unlock_method(&self) -> Option<KeySlotKind> {
+        self.storage.unlock_method()
+    }
+    
+    pub fn unlock_attempts(&self) -> UnlockAttempts {
+        self.storage.unlock_attempts()
+    }
+    
+    pub fn unlock(&self, input: CredentialInput, done: impl FnOnce(Result<Unlocked, StorageError>) + 'static) {
+        self.spawn_locking("Unlocking…", move |storage| {
+            let credentials = input.read()?;
+            let (data, mut session, recovered_from) = storage.unlock_with_recovery(&credentials)?;
+            
+            // Re-wrap under the configured strength while the credentials are at hand
+            let target = storage.target_kdf()?;
+            let upgraded_kdf = session.unlock_kdf().is_some_and(|kdf| kdf.is_weaker_than(&target));
+            if upgraded_kdf {
+                session.set_credentials(&credentials, &target)?;
+            }
+            
+            Ok(Unlocked { data, session, recovered_from, upgraded_kdf, rekeyed_from: None })
+        }, done);
+    }
+    
+    pub fn create_session(&self, password: Zeroizing<String>, done: impl FnOnce(Result<Session, StorageError>) + 'static) {
+        self.spawn_locking("Setting up encryption…", move |storage| {
+            Session::create(&Credentials::Password(password), &storage.target_kdf()?)
+        }, done);
+    }
+    
+    /// Checks `current` against `session` and gives a copy of it a new data
+    /// key wrapped under `new`, which may switch between password and keyfile
+    /// unlocking. The old credentials stop working entirely. A recovery key
+    /// can't be carried over without itself, so a vault that had one gets a
+    /// new one, returned for the user to write down. The caller saves with
+    /// `save_rekeyed` to finish.
+    pub fn change_credentials(&self, session: Session, current: CredentialInput, new: CredentialInput, done: impl FnOnce(Result<(Session, Option<Zeroizing<String>>), StorageError>) + 'static) {
+        self.spawn_unlocked("Changing password…", move |storage| {
+            let current = current.read()?;
+            session.verify(&current)?;
+            let new = new.read()?;
+            
+            let mut new_session = session.clone();
+            new_session.rekey(&[(&new, &storage.target_kdf()?)])?;
+            let recovery_key = if session.has_recovery_key() {
+                Some(new_session.regenerate_recovery_key()?)
+            } else {
+                None
+            };
+            Ok((new_session, recovery_key))
+        }, done);
+    }
+    
+    /// Writes a new random keyfile to `path`.
+    pub fn create_keyfile(&self, path: PathBuf, done: impl FnOnce(Result<(), StorageError>) + 'static) {
+        self.spawn("Creating keyfile…", move |_| {
+            storage::create_keyfile(&path)
+        }, done);
+    }
+    
+    /// Unlocks the vault with its recovery key and makes `new_password` the
+    /// unlock method, in place of a forgotten password or lost keyfile. The
+    /// data key is replaced, so the old password or keyfile stops working
+    /// entirely, in case it was forgotten because it leaked. The recovery key
+    /// keeps working. The caller saves with `save_rekeyed` to finish.
+    pub fn reset_password(&self, recovery_key: Zeroizing<String>, new_password: Zeroizing<String>, done: impl FnOnce(Result<Unlocked, StorageError>) + 'static) {
+        self.spawn_locking("Resetting password…", move |storage| {
+            let recovery_key = Credentials::recovery_key(&recovery_key);
+            let (data, previous, recovered_from) = storage.unlock_with_recovery(&recovery_key)?;
+            let mut session = previous.clone();
+            session.rekey(&[
+                (&Credentials::Password(new_password), &storage.target_kdf()?),
+                (&recovery_key, &KdfProfile::Standard.params()),
+            ])?;
+            
+            Ok(Unlocked { data, session, recovered_from, upgraded_kdf: false, rekeyed_from: Some(previous) })
+        }, done);
+    }
+    
+    /// Adds a new recovery key to a copy of `session`, replacing any previous
+    /// one. The caller saves with `save_rekeyed` before showing the key, so
+    /// the previous recovery key doesn't open earlier versions either.
+    pub fn regenerate_recovery_key(&self, mut session: Session, done: impl FnOnce(Result<(Session, Zeroizing<String>), StorageError>) + 'static) {
+        self.spawn_unlocked("Generating recovery key…", move |_| {
+            let recovery_key = session.regenerate_recovery_key()?;
+            Ok((session, recovery_key))
+        }, done);
+    }
+    
+    pub fn export_backup(&self, data: HabitData, backup_password: Zeroizing<String>, backup_path: PathBuf, done: impl FnOnce(Result<(), StorageError>) + 'static) {
+        self.spawn("Exporting backup…", move |storage| {
+            storage.export_backup(&data, &backup_password, &backup_path)
+        }, done);
+    }
+    
+    pub fn read_backup(&self, backup_path: PathBuf, backup_password: Zeroizing<String>, done: impl FnOnce(Result<HabitData, StorageError>) + 'static) {
+        self.spawn_unlocked("Importing backup…", move |storage| {
+            storage.read_backup(&backup_path, &backup_password)
+        }, done);
+    }
+    
+    /// Writes the habits and completions as unencrypted CSV files into `dir`.
+    pub fn export_csv(&self, data: HabitData, dir: PathBuf, done: impl FnOnce(Result<(), StorageError>) + 'static) {
+        self.spawn("Exporting CSV…", move |_| {
+            csv_io::export_to_dir(&data, &dir)
+        }, done);
+    }
+    
+    pub fn read_csv(&self, path: PathBuf, done: impl FnOnce(Result<CsvTable, StorageError>) + 'static) {
+        self.spawn_unlocked("Reading CSV…", move |_| {
+            csv_io::read_table(File::open(&path)?)
+        }, done);
+    }
+    
+    /// Times key derivation on this machine and recommends a profile.
+    pub fn benchmark_kdf(&self, done: impl FnOnce(Result<KdfProfile, StorageError>) + 'static) {
+        self.spawn("Measuring…", |_| Ok(KdfProfile::benchmark()), done);
+    }
+    
+    pub fn load_settings(&self) -> Settings {
+        self.storage.load_settings()
+    }
+    
+    pub fn save_settings(&self, settings: &Settings) -> Result<(), StorageError> {
+        self.storage.save_settings(settings)
+    }
+    
+    /// Runs `task` on a worker thread and hands its result to `done` on the
+    /// main thread.
+    fn spawn<T: Send + 'static>(
+        &self,
+        activity: &'static str,
+        task: impl FnOnce(SecureStorage) -> Result<T, StorageError> + Send + 'static,
+        done: impl FnOnce(Result<T, StorageError>) + 'static,
+    ) {
+        let service = self.clone();
+        let storage = self.storage.clone();
+        service.begin(activity);
+        glib::spawn_future_local(async move {
+            let result = gio::spawn_blocking(move || task(storage)).await;
+            service.end(activity);
+            done(result.unwrap_or_else(|_| Err(task_panicked())));
+        });
+    }
+    
+    /// Like `spawn`, for work whose result is written back to the unlocked
+    /// vault. If the vault is locked before `task` finishes, the result is
+    /// dropped and `done` is not called.
+    fn spawn_unlocked<T: Send + 'static>(
+        &self,
+        activity: &'static str,
+        task: impl FnOnce(SecureStorage) -> Result<T, StorageError> + Send + 'static,
+        done: impl FnOnce(Result<T, StorageError>) + 'static,
+    ) {
+        let state = self.state.clone();
+        let generation = state.lock_generation.get();
+        self.spawn(activity, task, move |result| {
+            if state.lock_generation.get() == generation {
+                done(result);
+            } else {
+                (state.on_error)("The vault locked before this finished, so nothing was changed".to_string());
+            }
+        });
+    }
+    
+    /// Like `spawn`, for work that unlocks the vault. The vault lock is taken
+    /// before `task` reads anything and kept if it succeeds.
+    fn spawn_locking<T: Send + 'static>(
+        &self,
+        activity: &'static str,
+        task: impl FnOnce(SecureStorage) -> Result<T, StorageError> + Send + 'static,
+        done: impl FnOnce(Result<T, StorageError>) + 'static,
+    ) {
+        if self.state.vault_lock.borrow().is_some() && !self.state.release_when_saved.get() {
+            self.spawn(activity, task, done);
+            return;
+        }
+        
+        let state = self.state.clone();
+        self.spawn(activity, move |storage| {
+            let lock = storage.lock_vault(LOCK_WAIT)?;
+            Ok((task(storage)?, lock))
+        }, move |result| {
+            done(result.map(|(value, lock)| {
+                state.vault_lock.replace(Some(lock));
+                value
+            }));
+        });
+    }
+    
+    fn run_queue(&self) {
+        if self.state.running.replace(true) {
+            return;
+        }
+        
+        let service = self.clone();
+        service.begin("Saving…");
+        glib::spawn_future_local(async move {
+            loop {
+                let job = service.state.jobs.borrow_mut().pop_front();
+                let Some(job) = job else { break };
+                
+                let storage = service.storage.clone();
+                match job {
+                    Job::Write(vault_bytes) => {
+                        let result = gio::spawn_blocking(move || {
+                            storage.write_vault(&vault_bytes)
+                        }).await;
+                        if let Err(e) = result.unwrap_or_else(|_| Err(task_panicked())) {
+                            (service.state.on_error)(format!("Failed to save data: {}", e));
+                        }
+                    }
+                    Job::WriteRekeyed(vault_bytes, previous, session) => {
+                        let result = gio::spawn_blocking(move || {
+                            storage.write_rekeyed_vault(&vault_bytes, &previous, &session)
+                        }).await;
+                        if let Err(e) = result.unwrap_or_else(|_| Err(task_panicked())) {
+                            (service.state.on_error)(format!("Failed to save data: {}", e));
+                        }
+                    }
+                    Job::Delete(done) => {
+                        let result = gio::spawn_blocking(move || {
+                            storage.delete_all_data()
+                        }).await;
+                        done(result.unwrap_or_else(|_| Err(task_panicked())));
+                    }
+                }
+            }
+            
+            service.state.running.set(false);
+            if service.state.release_when_saved.take() {
+                service.state.vault_lock.take();
+            }
+            service.end("Saving…");
+        });
+    }
+    
+    fn begin(&self, activity: &'static str) {
+        self.state.activity.borrow_mut().push(activity);
+        self.notify_activity();
+    }
+    
+    fn end(&self, activity: &'static str) {
+        {
+            let mut activities = self.state.activity.borrow_mut();
+            if let Some(index) = activities.iter().position(|a| *a == activity) {
+                activities.remove(index);
+            }
+        }
+        self.notify_activity();
+    }
+    
+    fn notify_activity(&self) {
+        let current = self.state.activity.borrow().last().copied();
+        (self.state.on_activity)(current);
+    }
+}
+
+fn task_panicked() -> StorageError {
+    StorageError::Io(std::io::Error::other("Background task panicked"))
+}
\ No newline at end of file
@@ -0,0 +1,192 @@
+use gtk4::prelude::*;
+use gtk4::{cairo, DrawingArea};
+use chrono::{Datelike, NaiveDate};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+const MARGIN_LEFT: f64 = 40.0;
+const MARGIN_RIGHT: f64 = 8.0;
+const MARGIN_TOP: f64 = 8.0;
+const MARGIN_BOTTOM: f64 = 18.0;
+
+/// Colours matching the calendar and heatmap greens.
+const VALUE_COLOR: (f64, f64, f64) = (0.180, 0.761, 0.494);
+const REFERENCE_COLOR: (f64, f64, f64) = (0.898, 0.647, 0.039);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartStyle {
+    Line,
+    Bar,
+}
+
+/// What a `TrendChart` plots: one value per week, keyed by Monday, with
+/// gaps where there is no value.
+#[derive(Debug, Clone, Default)]
+struct ChartData {
+    points: Vec<(NaiveDate, Option<f64>)>,
+    max: f64,
+    /// Horizontal line for a target such as a daily amount.
+    reference: Option<f64>,
+}
+
+/// Weekly trend drawn with cairo, as a line or bars. Hovering a week shows
+/// its value.
+#[derive(Clone)]
+pub struct TrendChart {
+    area: DrawingArea,
+    data: Rc<RefCell<ChartData>>,
+}
+
+impl TrendChart {
+    pub fn new(style: ChartStyle, format_value: fn(f64) -> String) -> Self {
+        let area = DrawingArea::new();
+        area.set_content_height(140);
+        area.set_content_width(300);
+        area.set_hexpand(true);
+        area.add_css_class("trend-chart");
+        
+        let data = Rc::new(RefCell::new(ChartData::default()));
+        
+        let data_draw = data.clone();
+        area.set_draw_func(move |area, cr, width, height| {
+            let fg = area.style_context().color();
+            let fg = (fg.red() as f64, fg.green() as f64, fg.blue() as f64);
+            Self::draw(cr, width as f64, height as f64, style, &data_draw.borrow(), format_value, fg);
+        });
+        
+        area.set_has_tooltip(true);
+        let data_tooltip = data.clone();
+        area.connect_query_tooltip(move |area, x, _, _, tooltip| {
+            let data = data_tooltip.borrow();
+            let Some(index) = Self::point_at(area.width() as f64, data.points.len(), x as f64) else {
+                return false;
+            };
+            let (monday, value) = data.points[index];
+            tooltip.set_text(Some(&format!(
+                "Week of {}: {}",
+                monday.format("%b %-d"),
+                value.map_or("–".to_string(), format_value)
+            )));
+            true
+        });
+        
+        Self { area, data }
+    }
+    
+    pub fn widget(&self) -> &DrawingArea {
+        &self.area
+    }
+    
+    /// Replaces the plotted values. The y axis runs from 0 to `max`.
+    pub fn set_points(&self, points: Vec<(NaiveDate, Option<f64>)>, max: f64, reference: Option<f64>) {
+        self.data.replace(ChartData { points, max, reference });
+        self.area.queue_draw();
+    }
+    
+    /// Index of the week under `x`, if any.
+    fn point_at(width: f64, count: usize, x: f64) -> Option<usize> {
+        let plot_width = width - MARGIN_LEFT - MARGIN_RIGHT;
+        if count == 0 || plot_width <= 0.0 || x < MARGIN_LEFT {
+            return None;
+        }
+        let index = ((x - MARGIN_LEFT) / (plot_width / count as f64)) as usize;
+        (index < count).then_some(index)
+    }
+    
+    fn draw(
+        cr: &cairo::Context,
+        width: f64,
+        height: f64,
+        style: ChartStyle,
+        data: &ChartData,
+        format_value: fn(f64) -> String,
+        fg: (f64, f64, f64),
+    ) {
+        let plot_width = width - MARGIN_LEFT - MARGIN_RIGHT;
+        let plot_height = height - MARGIN_TOP - MARGIN_BOTTOM;
+        if data.points.is_empty() || plot_width <= 0.0 || plot_height <= 0.0 || data.max <= 0.0 {
+            return;
+        }
+        
+        let slot = plot_width / data.points.len() as f64;
+        let x_at = |index: usize| MARGIN_LEFT + slot * (index as f64 + 0.5);
+        let y_at = |value: f64| MARGIN_TOP + plot_height * (1.0 - (value / data.max).clamp(0.0, 1.0));
+        
+        cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Normal);
+        cr.set_font_size(10.0);
+        cr.set_line_width(1.0);
+        
+        // Grid lines with their values
+        for fraction in [0.0, 0.5, 1.0] {
+            let value = data.max * fraction;
+            let y = y_at(value).round() + 0.5;
+            cr.set_source_rgba(fg.0, fg.1, fg.2, 0.15);
+            cr.move_to(MARGIN_LEFT, y);
+            cr.line_to(width - MARGIN_RIGHT, y);
+            let _ = cr.stroke();
+            
+            cr.set_source_rgba(fg.0, fg.1, fg.2, 0.7);
+            cr.move_to(2.0, y + 3.0);
+            let _ = cr.show_text(&format_value(value));
+        }
+        
+        // Month names under the first week of each month, skipped where
+        // they would overlap
+        let mut last_label_x = f64::NEG_INFINITY;
+        for (index, (monday, _)) in data.points.iter().enumerate() {
+            let starts_month = index == 0 || data.points[index - 1].0.month() != monday.month();
+            let x = x_at(index) - slot / 2.0;
+            if starts_month && x - last_label_x >= 30.0 {
+                cr.move_to(x, height - 4.0);
+                let _ = cr.show_text(&monday.format("%b").to_string());
+                last_label_x = x;
+            }
+        }
+        
+        cr.set_source_rgb(VALUE_COLOR.0, VALUE_COLOR.1, VALUE_COLOR.2);
+        match style {
+            ChartStyle::Bar => {
+                let bar_width = (slot * 0.7).max(1.0);
+                for (index, (_, value)) in data.points.iter().enumerate() {
+                    if let Some(value) = value {
+                        let top = y_at(*value);
+                        cr.rectangle(x_at(index) - bar_width / 2.0, top, bar_width, MARGIN_TOP + plot_height - top);
+                    }
+                }
+                let _ = cr.fill();
+            }
+            ChartStyle::Line => {
+                // Weeks without a value break the line
+                cr.set_line_width(2.0);
+                let mut drawing = false;
+                for (index, (_, value)) in data.points.iter().enumerate() {
+                    match value {
+                        Some(value) if drawing => cr.line_to(x_at(index), y_at(*value)),
+                        Some(value) => cr.move_to(x_at(index), y_at(*value)),
+                        None => {}
+                    }
+                    drawing = value.is_some();
+                }
+                let _ = cr.stroke();
+                
+                for (index, (_, value)) in data.points.iter().enumerate() {
+                    if let Some(value) = value {
+                        cr.arc(x_at(index), y_at(*value), 2.5, 0.0, std::f64::consts::TAU);
+                        let _ = cr.fill();
+                    }
+                }
+            }
+        }
+        
+        if let Some(reference) = data.reference {
+            let y = y_at(reference).round() + 0.5;
+            cr.set_source_rgb(REFERENCE_COLOR.0, REFERENCE_COLOR.1, REFERENCE_COLOR.2);
+            cr.set_line_width(1.0);
+            cr.set_dash(&[4.0, 3.0], 0.0);
+            cr.move_to(MARGIN_LEFT, y);
+            cr.line_to(width - MARGIN_RIGHT, y);
+            let _ = cr.stroke();
+            cr.set_dash(&[], 0.0);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+use gtk4::prelude::*;
+use gtk4::{DropDown, Label, Orientation};
+use gtk4::Box as GtkBox;
+use std::rc::Rc;
+use std::cell::RefCell;
+use habitvault_core::habit::{HabitData, Quantity};
+use habitvault_core::stats::{self, TREND_RANGES};
+use crate::trend_chart::{ChartStyle, TrendChart};
+
+/// Range selected when a trends view is first shown.
+const DEFAULT_RANGE: usize = 1;
+
+/// Weekly completion rate chart, plus the average amount logged for
+/// quantitative habits, over a selectable number of weeks. Covers one habit,
+/// or all habits together without a habit id. Refreshes itself whenever it
+/// is shown.
+#[derive(Clone)]
+pub struct TrendsView {
+    widget: GtkBox,
+    habit_data: Rc<RefCell<HabitData>>,
+    habit_id: Option<String>,
+    range_dropdown: DropDown,
+    rate_chart: TrendChart,
+    amount_label: Label,
+    amount_chart: TrendChart,
+}
+
+impl TrendsView {
+    pub fn new(habit_data: Rc<RefCell<HabitData>>, habit_id: Option<String>) -> Self {
+        let widget = GtkBox::new(Orientation::Vertical, 5);
+        widget.add_css_class("trends");
+        
+        let header = GtkBox::new(Orientation::Horizontal, 10);
+        let rate_label = Label::new(Some("Weekly completion rate"));
+        rate_label.add_css_class("heading");
+        rate_label.set_hexpand(true);
+        rate_label.set_xalign(0.0);
+        let range_labels: Vec<String> = TREND_RANGES.iter()
+            .map(|weeks| match weeks {
+                26 => "6 months".to_string(),
+                52 => "1 year".to_string(),
+                n => format!("{} weeks", n),
+            })
+            .collect();
+        let range_labels: Vec<&str> = range_labels.iter().map(String::as_str).collect();
+        let range_dropdown = DropDown::from_strings(&range_labels);
+        range_dropdown.set_selected(DEFAULT_RANGE as u32);
+        header.append(&rate_label);
+        header.append(&range_dropdown);
+        widget.append(&header);
+        
+        let rate_chart = TrendChart::new(ChartStyle::Line, |rate| format!("{:.0}%", rate * 100.0));
+        widget.append(rate_chart.widget());
+        
+        let amount_label = Label::new(None);
+        amount_label.add_css_class("heading");
+        amount_label.set_xalign(0.0);
+        widget.append(&amount_label);
+        let amount_chart = TrendChart::new(ChartStyle::Bar, Quantity::format_amount);
+        widget.append(amount_chart.widget());
+        
+        let view = Self {
+            widget,
+            habit_data,
+            habit_id,
+            range_dropdown,
+            rate_chart,
+            amount_label,
+            amount_chart,
+        };
+        
+        let view_range = view.clone();
+        view.range_dropdown.connect_selected_notify(move |_| view_range.refresh());
+        let view_map = view.clone();
+        view.widget.connect_map(move |_| view_map.refresh());
+        
+        view
+    }
+    
+    pub fn widget(&self) -> &GtkBox {
+        &self.widget
+    }
+    
+    /// Redraws the charts from the current data.
+    pub fn refresh(&self) {
+        let weeks = TREND_RANGES.get(self.range_dropdown.selected() as usize)
+            .copied()
+            .unwrap_or(TREND_RANGES[DEFAULT_RANGE]);
+        let data = self.habit_data.borrow();
+        let today = data.today();
+        
+        self.rate_chart.set_points(stats::weekly_rates(&data, self.habit_id.as_deref(), today, weeks), 1.0, None);
+        
+        let quantity = self.habit_id.as_deref()
+            .and_then(|habit_id| data.get_habit_by_id(habit_id))
+            .and_then(|habit| habit.quantity.clone());
+        let (Some(habit_id), Some(quantity)) = (self.habit_id.as_deref(), quantity) else {
+            self.amount_label.set_visible(false);
+            self.amount_chart.widget().set_visible(false);
+            return;
+        };
+        
+        let amounts = stats::weekly_amounts(&data, habit_id, today, weeks);
+        // Leave headroom above the target line
+        let max = amounts.iter()
+            .filter_map(|(_, amount)| *amount)
+            .fold(quantity.daily_target, f64::max) * 1.1;
+        self.amount_label.set_text(&format!("Average {} per day", quantity.unit));
+        self.amount_label.set_visible(true);
+        self.amount_chart.set_points(amounts, max, Some(quantity.daily_target));
+        self.amount_chart.widget().set_visible(true);
+    }
+}
\ No newline at end of file
@@ -1,27 +1,86 @@
 use libadwaita::prelude::*;
-use libadwaita::{Application, ApplicationWindow, HeaderBar, Toast, ToastOverlay, StyleManager};
-use gtk4::{Button, Entry, Label, ListBox, ScrolledWindow, Orientation, MessageDialog, Dialog, DialogFlags, ResponseType, CssProvider, Switch, FileChooserDialog, FileChooserAction, FileFilter};
+use libadwaita::{Application, ApplicationWindow, HeaderBar, StatusPage, Toast, ToastOverlay, StyleManager};
+use gtk4::{Button, Entry, Label, ListBox, MenuButton, ScrolledWindow, Orientation, MessageDialog, Dialog, DialogFlags, ResponseType, CssProvider, DropDown, Spinner, Switch, FileChooserDialog, FileChooserAction, FileFilter};
+use gtk4::{EventControllerKey, EventControllerMotion, Expander, GestureClick, Stack, StackSwitcher};
 use gtk4::Box as GtkBox;
-use gtk4::glib;
-use crate::habit::{Habit, HabitData};
-use crate::storage::SecureStorage;
+use gtk4::{gdk, glib};
+use habitvault_core::habit::{Habit, HabitData, Schedule};
+use habitvault_core::encryption::KdfProfile;
+use habitvault_core::settings::{AUTO_LOCK_CHOICES, WIPE_AFTER_CHOICES};
+use habitvault_core::storage::{SecureStorage, Session, StorageError};
+use crate::storage_service::{CredentialInput, StorageService, Unlocked};
+use crate::keyfile_chooser::KeyfileChooser;
+use crate::csv_import::show_csv_import_dialog;
+use habitvault_core::csv_io;
+use habitvault_core::lockout;
+use habitvault_core::vault::KeySlotKind;
 use crate::calendar::HabitCalendar;
+use crate::heatmap::HabitHeatmap;
+use crate::stats_view::HabitStatsView;
+use crate::trends_view::TrendsView;
+use crate::schedule_editor::ScheduleEditor;
+use crate::quantity_editor::{QuantityEditor, build_log_popover};
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::cell::RefCell;
-use chrono::Utc;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+use chrono::{NaiveDate, Utc};
+use zeroize::Zeroizing;
 
+#[derive(Clone)]
 pub struct HabitApp {
     window: ApplicationWindow,
-    storage: SecureStorage,
+    storage: StorageService,
     habit_data: Rc<RefCell<HabitData>>,
-    password: Rc<RefCell<Option<String>>>,
+    session: Rc<RefCell<Option<Session>>>,
     habit_list: ListBox,
+    /// Switches between the habit list and the lock screen.
+    content_stack: Stack,
+    last_activity: Rc<Cell<Instant>>,
     toast_overlay: ToastOverlay,
     style_manager: StyleManager,
     add_button: Button,
     settings_button: Button,
+    auto_lock: Rc<AutoLock>,
 }
 
+/// The auto-lock settings, kept in memory so the idle check doesn't read the
+/// settings file. The settings dialog updates them as it saves.
+struct AutoLock {
+    minutes: Cell<u32>,
+    when_hidden: Cell<bool>,
+}
+
+/// Today's amount for quantitative habits, then progress toward this week's
+/// or month's goal or a summary of the schedule for habits that aren't done
+/// every day.
+fn get_schedule_status_text(habit: &Habit, habit_data: &HabitData) -> Option<String> {
+    let today = habit_data.today();
+    let amount = habit.quantity.as_ref().map(|quantity| {
+        format!("{} today", quantity.describe_progress(habit_data.value_on_date(&habit.id, today)))
+    });
+    
+    let schedule = if habit.is_weekly_goal() {
+        let done = habit_data.completions_in_week(&habit.id, today);
+        Some(format!("{}/{} this week", done, habit.target_days_per_week))
+    } else if let Schedule::TimesPerMonth { times } = habit.schedule {
+        let done = habit_data.completions_in_month(&habit.id, today);
+        Some(format!("{}/{} this month", done, times))
+    } else if habit.schedule != Schedule::Daily {
+        Some(habit.schedule.describe())
+    } else {
+        None
+    };
+    
+    match (amount, schedule) {
+        (Some(amount), Some(schedule)) => Some(format!("{} · {}", amount, schedule)),
+        (amount, schedule) => amount.or(schedule),
+    }
+}
+
+/// Expands a habit's row and shows its monthly calendar at a day.
+type OpenAtDay = Rc<dyn Fn(NaiveDate)>;
+
 fn get_streak_emoji(streak: u32) -> &'static str {
     match streak {
         0..=2 => "😞",  // Unhappy face for 0-2 days
@@ -32,7 +91,7 @@ fn get_streak_emoji(streak: u32) -> &'static str {
 
 impl HabitApp {
     pub fn new(app: &Application) -> Result<Self, std::boxed::Box<dyn std::error::Error>> {
-        let storage = SecureStorage::new()?;
+        let secure_storage = SecureStorage::new()?;
         
         let window = ApplicationWindow::builder()
             .application(app)
@@ -49,10 +108,20 @@ impl HabitApp {
         let settings_button = Button::with_label("⚙️");
         settings_button.set_tooltip_text(Some("Settings"));
         settings_button.add_css_class("header-icon-button");
-        settings_button.set_sensitive(true); // Always enable settings access
+        settings_button.set_sensitive(false); // Disable until authenticated
         header_bar.pack_end(&settings_button);
         header_bar.pack_end(&add_button);
         
+        // Shown while saves or key derivation run in the background
+        let activity_box = GtkBox::new(Orientation::Horizontal, 6);
+        activity_box.add_css_class("activity-indicator");
+        activity_box.set_visible(false);
+        let activity_spinner = Spinner::new();
+        let activity_label = Label::new(None);
+        activity_box.append(&activity_spinner);
+        activity_box.append(&activity_label);
+        header_bar.pack_start(&activity_box);
+        
         // For AdwApplicationWindow, use set_content instead of set_titlebar
         // The header_bar will be added to the main content structure
 
@@ -84,32 +153,82 @@ impl HabitApp {
         habit_list.set_selection_mode(gtk4::SelectionMode::None);
         scrolled_window.set_child(Some(&habit_list));
         
-        main_box.append(&scrolled_window);
+        // Shown before the first unlock and whenever the vault locks itself
+        let unlock_button = Button::with_label("Unlock");
+        unlock_button.add_css_class("suggested-action");
+        unlock_button.add_css_class("pill");
+        unlock_button.set_halign(gtk4::Align::Center);
+        let lock_page = StatusPage::builder()
+            .icon_name("system-lock-screen-symbolic")
+            .title("HabitVault is locked")
+            .description("Enter your password to see your habits")
+            .child(&unlock_button)
+            .build();
+        
+        let content_stack = Stack::new();
+        content_stack.add_named(&lock_page, Some("locked"));
+        content_stack.add_named(&scrolled_window, Some("habits"));
+        content_stack.set_visible_child_name("locked");
+        
+        main_box.append(&content_stack);
         content_box.append(&main_box);
         toast_overlay.set_child(Some(&content_box));
         window.set_content(Some(&toast_overlay));
+        
+        let toast_overlay_errors = toast_overlay.clone();
+        let storage = StorageService::new(
+            secure_storage,
+            move |activity| {
+                activity_box.set_visible(activity.is_some());
+                activity_spinner.set_spinning(activity.is_some());
+                activity_label.set_text(activity.unwrap_or_default());
+            },
+            move |message| {
+                toast_overlay_errors.add_toast(Toast::new(&message));
+            },
+        );
 
         let style_manager = StyleManager::default();
+        let settings = storage.load_settings();
 
         let app = Self {
             window,
             storage,
             habit_data: Rc::new(RefCell::new(HabitData::new())),
-            password: Rc::new(RefCell::new(None)),
+            session: Rc::new(RefCell::new(None)),
             habit_list,
+            content_stack,
+            last_activity: Rc::new(Cell::new(Instant::now())),
             toast_overlay,
             style_manager,
             add_button: add_button.clone(),
             settings_button: settings_button.clone(),
+            auto_lock: Rc::new(AutoLock {
+                minutes: Cell::new(settings.auto_lock_minutes),
+                when_hidden: Cell::new(settings.lock_when_hidden),
+            }),
         };
 
+        let app_unlock = app.clone();
+        unlock_button.connect_clicked(move |_| {
+            if let Err(e) = app_unlock.authenticate_user() {
+                eprintln!("Failed to show unlock dialog: {}", e);
+            }
+        });
+
         app.setup_events(add_button, settings_button);
+        app.setup_midnight_rollover();
+        app.setup_auto_lock();
         app.authenticate_user()?;
         
-        // Fallback: enable add button after a short delay if authentication doesn't complete
+        // Fallback: enable add button after a short delay if authentication
+        // completed without enabling it, but never while still locked
         let add_button_fallback = app.add_button.clone();
+        let session_fallback = app.session.clone();
         glib::timeout_add_seconds_local(3, move || {
-            add_button_fallback.set_sensitive(true);
+            if session_fallback.borrow().is_some() {
+                add_button_fallback.set_sensitive(true);
+            }
             glib::ControlFlow::Break
         });
         
@@ -119,26 +238,158 @@ impl HabitApp {
     fn setup_events(&self, add_button: Button, settings_button: Button) {
         let habit_data = self.habit_data.clone();
         let storage = self.storage.clone();
-        let password = self.password.clone();
+        let session = self.session.clone();
         let habit_list = self.habit_list.clone();
         let toast_overlay = self.toast_overlay.clone();
         
         add_button.connect_clicked(move |_| {
-            Self::show_add_habit_dialog(&habit_data, storage.clone(), &password, &habit_list, &toast_overlay);
+            if session.borrow().is_none() {
+                return;
+            }
+            Self::show_add_habit_dialog(&habit_data, storage.clone(), &session, &habit_list, &toast_overlay);
         });
 
         let storage_clone = self.storage.clone();
-        let password_clone = self.password.clone();
+        let session_clone = self.session.clone();
         let habit_data_clone = self.habit_data.clone();
         let habit_list_clone = self.habit_list.clone();
         let toast_overlay_clone = self.toast_overlay.clone();
         
         let style_manager_clone = self.style_manager.clone();
+        let auto_lock = self.auto_lock.clone();
         settings_button.connect_clicked(move |_| {
-            Self::show_settings_dialog(&storage_clone, &password_clone, &habit_data_clone, &habit_list_clone, &toast_overlay_clone, &style_manager_clone);
+            if session_clone.borrow().is_none() {
+                return;
+            }
+            Self::show_settings_dialog(&storage_clone, &session_clone, &habit_data_clone, &habit_list_clone, &toast_overlay_clone, &style_manager_clone, &auto_lock);
+        });
+        
+        // Drop the session when the window closes so the data key is wiped
+        // rather than left in memory until the process exits
+        let session_close = self.session.clone();
+        self.window.connect_close_request(move |_| {
+            session_close.replace(None);
+            glib::Propagation::Proceed
         });
     }
 
+    /// Streaks, "today" highlights and missed days all depend on the date, so
+    /// recompute and redraw when it changes while the app is open.
+    fn setup_midnight_rollover(&self) {
+        let habit_data = self.habit_data.clone();
+        let storage = self.storage.clone();
+        let session = self.session.clone();
+        let habit_list = self.habit_list.clone();
+        let last_date = std::cell::Cell::new(self.habit_data.borrow().today());
+        
+        // Polling rather than a single timer also copes with suspend/resume
+        // and with the local time zone changing while the app is open
+        glib::timeout_add_seconds_local(60, move || {
+            let today = habit_data.borrow().today();
+            if today != last_date.get() {
+                last_date.set(today);
+                habit_data.borrow_mut().recompute_streaks(today);
+                if session.borrow().is_some() {
+                    Self::refresh_habit_list(&habit_list, &habit_data, &storage, &session);
+                }
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+    
+    /// Tracks input on the window and locks the vault once it has been idle
+    /// for the configured time, or when it is minimized or the screen locks
+    /// if that is enabled.
+    fn setup_auto_lock(&self) {
+        let key_controller = EventControllerKey::new();
+        key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        let last_activity = self.last_activity.clone();
+        key_controller.connect_key_pressed(move |_, _, _, _| {
+            last_activity.set(Instant::now());
+            glib::Propagation::Proceed
+        });
+        self.window.add_controller(key_controller);
+        
+        let motion_controller = EventControllerMotion::new();
+        let last_activity = self.last_activity.clone();
+        motion_controller.connect_motion(move |_, _, _| {
+            last_activity.set(Instant::now());
+        });
+        self.window.add_controller(motion_controller);
+        
+        let click_gesture = GestureClick::new();
+        click_gesture.set_button(0);
+        click_gesture.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        let last_activity = self.last_activity.clone();
+        click_gesture.connect_pressed(move |_, _, _, _| {
+            last_activity.set(Instant::now());
+        });
+        self.window.add_controller(click_gesture);
+        
+        let app = self.clone();
+        glib::timeout_add_seconds_local(15, move || {
+            if app.session.borrow().is_some() {
+                let minutes = app.auto_lock.minutes.get();
+                let timeout = Duration::from_secs(minutes as u64 * 60);
+                if minutes > 0 && app.last_activity.get().elapsed() >= timeout {
+                    app.lock();
+                }
+            }
+            glib::ControlFlow::Continue
+        });
+        
+        // The toplevel surface only exists once the window is realized
+        let app = self.clone();
+        self.window.connect_realize(move |window| {
+            let Some(toplevel) = window.surface().and_downcast::<gdk::Toplevel>() else {
+                return;
+            };
+            let app = app.clone();
+            toplevel.connect_state_notify(move |toplevel| {
+                if toplevel.state().contains(gdk::ToplevelState::MINIMIZED)
+                    && app.auto_lock.when_hidden.get() {
+                    app.lock();
+                }
+            });
+        });
+        
+        if let Some(application) = self.window.application() {
+            let app = self.clone();
+            application.connect_screensaver_active_notify(move |application| {
+                if application.is_screensaver_active() && app.auto_lock.when_hidden.get() {
+                    app.lock();
+                }
+            });
+        }
+    }
+    
+    /// Forgets the decrypted data and key and shows the lock screen. Edits
+    /// already made have been queued for saving, so nothing is lost.
+    fn lock(&self) {
+        if self.session.replace(None).is_none() {
+            return;
+        }
+        self.storage.lock();
+        self.habit_data.replace(HabitData::new());
+        
+        // Calendars and dialogs hold on to the data they were opened with
+        for window in gtk4::Window::list_toplevels() {
+            if let Ok(window) = window.downcast::<gtk4::Window>()
+                && window != *self.window.upcast_ref::<gtk4::Window>() {
+                window.close();
+            }
+        }
+        
+        while let Some(child) = self.habit_list.first_child() {
+            self.habit_list.remove(&child);
+        }
+        self.content_stack.set_visible_child_name("locked");
+        self.add_button.set_sensitive(false);
+        self.settings_button.set_sensitive(false);
+        
+        self.show_unlock_dialog_again();
+    }
+    
     fn authenticate_user(&self) -> Result<(), std::boxed::Box<dyn std::error::Error>> {
         if !self.storage.exists() {
             self.show_password_setup_dialog()?;
@@ -163,35 +414,54 @@ impl HabitApp {
         entry.set_placeholder_text(Some("Enter password"));
         content_area.append(&entry);
 
-        let password = self.password.clone();
+        let session = self.session.clone();
         let storage = self.storage.clone();
         let habit_data = self.habit_data.clone();
+        let toast_overlay = self.toast_overlay.clone();
         
         // Add Enter key support
         let dialog_clone = dialog.clone();
         entry.connect_activate(move |entry| {
-            let pass = entry.text().to_string();
-            if !pass.is_empty() {
+            if !entry.text().is_empty() {
                 dialog_clone.response(ResponseType::Ok);
             }
         });
         
         let add_button = self.add_button.clone();
         let settings_button = self.settings_button.clone();
+        let content_stack = self.content_stack.clone();
+        let last_activity = self.last_activity.clone();
         
         dialog.connect_response(move |dialog, response| {
             if response == ResponseType::Ok {
-                let pass = entry.text().to_string();
+                let pass = Zeroizing::new(entry.text().to_string());
+                entry.set_text("");
                 if !pass.is_empty() {
-                    password.replace(Some(pass.clone()));
-                    
-                    if let Err(e) = storage.save(&habit_data.borrow(), &pass) {
-                        eprintln!("Failed to save initial data: {}", e);
-                    }
-                    
-                    // Enable UI after successful authentication
-                    add_button.set_sensitive(true);
-                    settings_button.set_sensitive(true);
+                    let session = session.clone();
+                    let storage_inner = storage.clone();
+                    let habit_data = habit_data.clone();
+                    let toast_overlay = toast_overlay.clone();
+                    let add_button = add_button.clone();
+                    let settings_button = settings_button.clone();
+                    let content_stack = content_stack.clone();
+                    let last_activity = last_activity.clone();
+                    storage.create_session(pass, move |result| match result {
+                        Ok(new_session) => {
+                            storage_inner.save(&habit_data.borrow(), &new_session);
+                            session.replace(Some(new_session));
+                            Self::offer_recovery_key(&storage_inner, &session, &habit_data, &toast_overlay);
+                            
+                            // Enable UI after successful authentication
+                            add_button.set_sensitive(true);
+                            settings_button.set_sensitive(true);
+                            content_stack.set_visible_child_name("habits");
+                            last_activity.set(Instant::now());
+                        }
+                        Err(e) => {
+                            let error_toast = Toast::new(&format!("Failed to set up encryption: {}", e));
+                            toast_overlay.add_toast(error_toast);
+                        }
+                    });
                 }
             }
             dialog.close();
@@ -202,56 +472,103 @@ impl HabitApp {
     }
 
     fn show_password_entry_dialog(&self) -> Result<(), std::boxed::Box<dyn std::error::Error>> {
+        // Ask for whatever the vault's header says it needs, or for both if
+        // the header can't be read
+        let unlock_method = self.storage.unlock_method();
+        let needs_password = unlock_method.is_none_or(KeySlotKind::needs_password);
+        let needs_keyfile = unlock_method.is_none_or(KeySlotKind::needs_keyfile);
+        let prompt = match unlock_method {
+            Some(KeySlotKind::Keyfile) => "Choose your keyfile to access habit data:",
+            Some(KeySlotKind::PasswordAndKeyfile) => "Enter your password and choose your keyfile to access habit data:",
+            _ => "Enter your password to access habit data:",
+        };
+        
         let dialog = MessageDialog::new(
             Some(&self.window),
             DialogFlags::MODAL,
             gtk4::MessageType::Question,
             gtk4::ButtonsType::OkCancel,
-            "Enter your password to access habit data:",
+            prompt,
         );
+        dialog.add_button("Forgot Password?", ResponseType::Help);
 
         let content_area = dialog.content_area();
         let entry = Entry::new();
         entry.set_visibility(false);
         entry.set_placeholder_text(Some("Enter password"));
+        entry.set_visible(needs_password);
         content_area.append(&entry);
-
-        let password = self.password.clone();
-        let storage = self.storage.clone();
-        let habit_data = self.habit_data.clone();
-        let habit_list = self.habit_list.clone();
+        
+        let keyfile_path = needs_keyfile.then(|| self.storage.load_settings().keyfile_path).flatten();
+        let keyfile_chooser = KeyfileChooser::new(keyfile_path, None);
+        keyfile_chooser.widget().set_visible(needs_keyfile);
+        content_area.append(keyfile_chooser.widget());
+        
+        // Count down any delay left from earlier failed attempts before
+        // allowing another one
+        if let Some(wait) = self.storage.unlock_attempts().retry_after(Utc::now()) {
+            let ready_at = Instant::now() + wait;
+            let wait_label = Label::new(None);
+            wait_label.set_wrap(true);
+            wait_label.add_css_class("error");
+            content_area.append(&wait_label);
+            
+            let update_wait = move |dialog: &MessageDialog| {
+                let remaining = ready_at.saturating_duration_since(Instant::now());
+                let waiting = !remaining.is_zero();
+                wait_label.set_text(&format!("Too many failed attempts. Try again in {}", lockout::describe_wait(remaining)));
+                wait_label.set_visible(waiting);
+                dialog.set_response_sensitive(ResponseType::Ok, !waiting);
+                if waiting { glib::ControlFlow::Continue } else { glib::ControlFlow::Break }
+            };
+            update_wait(&dialog);
+            
+            let dialog_weak = dialog.downgrade();
+            glib::timeout_add_seconds_local(1, move || match dialog_weak.upgrade() {
+                Some(dialog) => update_wait(&dialog),
+                None => glib::ControlFlow::Break,
+            });
+        }
         
         // Add Enter key support
         let dialog_clone = dialog.clone();
         entry.connect_activate(move |entry| {
-            let pass = entry.text().to_string();
-            if !pass.is_empty() {
+            let ok_sensitive = dialog_clone.widget_for_response(ResponseType::Ok)
+                .is_some_and(|button| button.is_sensitive());
+            if !entry.text().is_empty() && ok_sensitive {
                 dialog_clone.response(ResponseType::Ok);
             }
         });
         
-        let add_button = self.add_button.clone();
-        let settings_button = self.settings_button.clone();
-        
+        let app = self.clone();
         dialog.connect_response(move |dialog, response| {
             if response == ResponseType::Ok {
-                let pass = entry.text().to_string();
-                if !pass.is_empty() {
-                    match storage.load(&pass) {
-                        Ok(data) => {
-                            password.replace(Some(pass));
-                            habit_data.replace(data);
-                            Self::refresh_habit_list(&habit_list, &habit_data, &storage, &password);
-                            
-                            // Enable UI after successful authentication
-                            add_button.set_sensitive(true);
-                            settings_button.set_sensitive(true);
-                        }
-                        Err(_) => {
-                            eprintln!("Failed to decrypt data. Wrong password?");
+                let input = CredentialInput {
+                    password: Zeroizing::new(entry.text().to_string()),
+                    keyfile: keyfile_chooser.path(),
+                };
+                entry.set_text("");
+                if !input.password.is_empty() || input.keyfile.is_some() {
+                    let used_keyfile = input.keyfile.clone();
+                    let app_inner = app.clone();
+                    app.storage.unlock(input, move |result| match result {
+                        Ok(unlocked) => {
+                            if unlocked.upgraded_kdf {
+                                app_inner.storage.save(&unlocked.data, &unlocked.session);
+                                let info_toast = Toast::new("Upgraded your vault to the current encryption strength");
+                                app_inner.toast_overlay.add_toast(info_toast);
+                            }
+                            if used_keyfile.is_some() {
+                                Self::remember_keyfile(&app_inner.storage, &app_inner.toast_overlay, used_keyfile);
+                            }
+                            app_inner.finish_unlock(unlocked);
                         }
-                    }
+                        Err(e) => app_inner.show_unlock_error(e),
+                    });
                 }
+            } else if response == ResponseType::Help {
+                entry.set_text("");
+                app.show_password_reset_dialog();
             }
             dialog.close();
         });
@@ -259,14 +576,279 @@ impl HabitApp {
         dialog.show();
         Ok(())
     }
+    
+    /// Explains why unlocking failed. Mistakes in what was entered get the
+    /// unlock dialog again; a damaged vault gets the option of restoring from
+    /// a backup file.
+    fn show_unlock_error(&self, error: StorageError) {
+        if error.is_corruption() {
+            let dialog = MessageDialog::new(
+                Some(&self.window),
+                DialogFlags::MODAL,
+                gtk4::MessageType::Error,
+                gtk4::ButtonsType::Close,
+                "Your habit data can't be read",
+            );
+            dialog.set_secondary_text(Some(&format!(
+                "{}. None of the earlier versions kept automatically could be read either. You can restore your habits from a backup file.",
+                error
+            )));
+            dialog.add_button("Restore from Backup…", ResponseType::Accept);
+            
+            let app = self.clone();
+            dialog.connect_response(move |dialog, response| {
+                dialog.close();
+                if response == ResponseType::Accept {
+                    app.restore_from_backup();
+                }
+            });
+            dialog.show();
+            return;
+        }
+        
+        let message = match &error {
+            StorageError::WrongCredentials(_) => {
+                let wipe_after = self.storage.load_settings().wipe_after_failures;
+                let failures = self.storage.unlock_attempts().failures;
+                match wipe_after.saturating_sub(failures) {
+                    0 => format!("{}. Please try again", error),
+                    1 => format!("{}. All data will be deleted after one more failed attempt", error),
+                    left => format!("{}. All data will be deleted after {} more failed attempts", error, left),
+                }
+            }
+            StorageError::WrongUnlockMethod(_) | StorageError::Keyfile(..) | StorageError::VaultBusy => {
+                format!("{}. Please try again", error)
+            }
+            StorageError::NewerVersion(_) => format!("{}. Update HabitVault to open it", error),
+            StorageError::LockedOut(_) | StorageError::Wiped => error.to_string(),
+            _ => format!("Failed to unlock: {}", error),
+        };
+        let error_toast = Toast::new(&message);
+        self.toast_overlay.add_toast(error_toast);
+        
+        match error {
+            StorageError::WrongCredentials(_)
+            | StorageError::WrongUnlockMethod(_)
+            | StorageError::Keyfile(..)
+            | StorageError::VaultBusy
+            | StorageError::LockedOut(_) => self.show_unlock_dialog_again(),
+            StorageError::Wiped => {
+                if let Err(e) = self.show_password_setup_dialog() {
+                    eprintln!("Failed to show setup dialog: {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    /// Replaces an unreadable vault with the contents of a backup file. The
+    /// restored vault is protected by the backup's password from then on.
+    /// The damaged file is kept as the newest earlier version.
+    fn restore_from_backup(&self) {
+        let file_chooser = FileChooserDialog::new(
+            Some("Restore from Backup"),
+            Some(&self.window),
+            FileChooserAction::Open,
+            &[("Cancel", ResponseType::Cancel), ("Open", ResponseType::Accept)]
+        );
+        
+        let filter = FileFilter::new();
+        filter.add_pattern("*.encrypted");
+        filter.set_name(Some("Encrypted backup files"));
+        file_chooser.add_filter(&filter);
+        
+        let app = self.clone();
+        file_chooser.connect_response(move |dialog, response| {
+            let backup_path = dialog.file().and_then(|file| file.path());
+            dialog.close();
+            let (ResponseType::Accept, Some(backup_path)) = (response, backup_path) else {
+                return;
+            };
+            
+            let password_dialog = MessageDialog::new(
+                Some(&app.window),
+                DialogFlags::MODAL,
+                gtk4::MessageType::Question,
+                gtk4::ButtonsType::OkCancel,
+                "Enter the password of the backup file:",
+            );
+            let backup_password_entry = Entry::new();
+            backup_password_entry.set_visibility(false);
+            backup_password_entry.set_placeholder_text(Some("Backup password"));
+            password_dialog.content_area().append(&backup_password_entry);
+            
+            let app = app.clone();
+            password_dialog.connect_response(move |dialog, response| {
+                let backup_password = Zeroizing::new(backup_password_entry.text().to_string());
+                backup_password_entry.set_text("");
+                dialog.close();
+                if response != ResponseType::Ok || backup_password.is_empty() {
+                    return;
+                }
+                
+                let app = app.clone();
+                let storage = app.storage.clone();
+                let vault_password = backup_password.clone();
+                storage.read_backup(backup_path.clone(), backup_password, move |result| {
+                    let data = match result {
+                        Ok(data) => data,
+                        Err(e) => {
+                            let error_toast = Toast::new(&format!("Failed to restore backup: {}", e));
+                            app.toast_overlay.add_toast(error_toast);
+                            return;
+                        }
+                    };
+                    
+                    let app_inner = app.clone();
+                    app.storage.create_session(vault_password, move |result| match result {
+                        Ok(session) => {
+                            app_inner.storage.save(&data, &session);
+                            app_inner.finish_unlock(Unlocked { data, session, recovered_from: None, upgraded_kdf: false, rekeyed_from: None });
+                            let success_toast = Toast::new("Restored from backup. Unlock with the backup's password from now on");
+                            app_inner.toast_overlay.add_toast(success_toast);
+                        }
+                        Err(e) => {
+                            let error_toast = Toast::new(&format!("Failed to restore backup: {}", e));
+                            app_inner.toast_overlay.add_toast(error_toast);
+                        }
+                    });
+                });
+            });
+            password_dialog.show();
+        });
+        
+        file_chooser.show();
+    }
+    
+    /// Offers `keyfile_path` at the next unlock.
+    fn remember_keyfile(storage: &StorageService, toast_overlay: &ToastOverlay, keyfile_path: Option<PathBuf>) {
+        let mut settings = storage.load_settings();
+        if settings.keyfile_path != keyfile_path {
+            settings.keyfile_path = keyfile_path;
+            if let Err(e) = storage.save_settings(&settings) {
+                let error_toast = Toast::new(&format!("Failed to save settings: {}", e));
+                toast_overlay.add_toast(error_toast);
+            }
+        }
+    }
+    
+    /// Asks for the recovery key and a new password, for when the current
+    /// password has been forgotten. The habit history is kept, and the old
+    /// password or keyfile is revoked.
+    fn show_password_reset_dialog(&self) {
+        let dialog = MessageDialog::new(
+            Some(&self.window),
+            DialogFlags::MODAL,
+            gtk4::MessageType::Question,
+            gtk4::ButtonsType::OkCancel,
+            "Enter your recovery key and choose a new password:",
+        );
+
+        let content_area = dialog.content_area();
+        let recovery_key_entry = Entry::new();
+        recovery_key_entry.set_placeholder_text(Some("Recovery key"));
+        recovery_key_entry.add_css_class("monospace");
+        
+        let new_password_entry = Entry::new();
+        new_password_entry.set_visibility(false);
+        new_password_entry.set_placeholder_text(Some("New password"));
+        
+        let confirm_password_entry = Entry::new();
+        confirm_password_entry.set_visibility(false);
+        confirm_password_entry.set_placeholder_text(Some("Confirm new password"));
+        
+        content_area.append(&recovery_key_entry);
+        content_area.append(&new_password_entry);
+        content_area.append(&confirm_password_entry);
+        
+        let app = self.clone();
+        dialog.connect_response(move |dialog, response| {
+            let recovery_key = Zeroizing::new(recovery_key_entry.text().to_string());
+            let new_pass = Zeroizing::new(new_password_entry.text().to_string());
+            let confirm_pass = Zeroizing::new(confirm_password_entry.text().to_string());
+            recovery_key_entry.set_text("");
+            new_password_entry.set_text("");
+            confirm_password_entry.set_text("");
+            dialog.close();
+            
+            if response != ResponseType::Ok {
+                app.show_unlock_dialog_again();
+                return;
+            }
+            
+            if recovery_key.is_empty() || new_pass.is_empty() {
+                let error_toast = Toast::new("Please enter your recovery key and a new password");
+                app.toast_overlay.add_toast(error_toast);
+                app.show_unlock_dialog_again();
+                return;
+            }
+            
+            if new_pass != confirm_pass {
+                let error_toast = Toast::new("New passwords do not match");
+                app.toast_overlay.add_toast(error_toast);
+                app.show_unlock_dialog_again();
+                return;
+            }
+            
+            let app_inner = app.clone();
+            app.storage.reset_password(recovery_key, new_pass, move |result| match result {
+                Ok(unlocked) => {
+                    match &unlocked.rekeyed_from {
+                        Some(previous) => app_inner.storage.save_rekeyed(&unlocked.data, previous, &unlocked.session),
+                        None => app_inner.storage.save(&unlocked.data, &unlocked.session),
+                    }
+                    app_inner.finish_unlock(unlocked);
+                    let success_toast = Toast::new("Password reset. The old password no longer works; your recovery key still does");
+                    app_inner.toast_overlay.add_toast(success_toast);
+                }
+                Err(e @ StorageError::Wiped) => app_inner.show_unlock_error(e),
+                Err(e) => {
+                    let error_toast = Toast::new(&format!("Failed to reset password: {}", e));
+                    app_inner.toast_overlay.add_toast(error_toast);
+                    app_inner.show_unlock_dialog_again();
+                }
+            });
+        });
+
+        dialog.show();
+    }
+    
+    fn show_unlock_dialog_again(&self) {
+        if let Err(e) = self.show_password_entry_dialog() {
+            eprintln!("Failed to show unlock dialog: {}", e);
+        }
+    }
+    
+    /// Takes over the data and session of a successful unlock and shows the
+    /// habit list.
+    fn finish_unlock(&self, unlocked: Unlocked) {
+        self.session.replace(Some(unlocked.session));
+        self.habit_data.replace(unlocked.data);
+        Self::refresh_habit_list(&self.habit_list, &self.habit_data, &self.storage, &self.session);
+        
+        if let Some(path) = unlocked.recovered_from {
+            let warning_toast = Toast::new(&format!(
+                "Your data file was damaged. Recovered the last readable save ({})",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            self.toast_overlay.add_toast(warning_toast);
+        }
+        
+        // Enable UI after successful authentication
+        self.add_button.set_sensitive(true);
+        self.settings_button.set_sensitive(true);
+        self.content_stack.set_visible_child_name("habits");
+        self.last_activity.set(Instant::now());
+    }
 
     fn show_settings_dialog(
-        storage: &SecureStorage,
-        password: &Rc<RefCell<Option<String>>>,
+        storage: &StorageService,
+        session: &Rc<RefCell<Option<Session>>>,
         habit_data: &Rc<RefCell<HabitData>>,
         habit_list: &ListBox,
         toast_overlay: &ToastOverlay,
         style_manager: &StyleManager,
+        auto_lock: &Rc<AutoLock>,
     ) {
         let dialog = MessageDialog::new(
             None::<&ApplicationWindow>,
@@ -299,13 +881,32 @@ impl HabitApp {
         separator.set_margin_bottom(10);
         settings_box.append(&separator);
         
-        // Password change section
+        // Password and keyfile section
         let password_section = GtkBox::new(Orientation::Vertical, 5);
-        password_section.append(&Label::new(Some("Change Encryption Password")));
+        password_section.append(&Label::new(Some("Password & Keyfile")));
+        
+        let current_method = session.borrow().as_ref()
+            .and_then(Session::unlock_method)
+            .unwrap_or(KeySlotKind::Password);
+        let remembered_keyfile = current_method.needs_keyfile()
+            .then(|| storage.load_settings().keyfile_path)
+            .flatten();
         
         let current_password_entry = Entry::new();
         current_password_entry.set_visibility(false);
         current_password_entry.set_placeholder_text(Some("Current password"));
+        current_password_entry.set_visible(current_method.needs_password());
+        
+        let current_keyfile_label = Label::new(Some("Current keyfile:"));
+        current_keyfile_label.set_xalign(0.0);
+        let current_keyfile_chooser = KeyfileChooser::new(remembered_keyfile.clone(), None);
+        current_keyfile_label.set_visible(current_method.needs_keyfile());
+        current_keyfile_chooser.widget().set_visible(current_method.needs_keyfile());
+        
+        let method_labels: Vec<&str> = KeySlotKind::UNLOCK_METHODS.iter().map(|kind| kind.label()).collect();
+        let method_dropdown = DropDown::from_strings(&method_labels);
+        let method_index = KeySlotKind::UNLOCK_METHODS.iter().position(|kind| *kind == current_method).unwrap_or(0);
+        method_dropdown.set_selected(method_index as u32);
         
         let new_password_entry = Entry::new();
         new_password_entry.set_visibility(false);
@@ -315,16 +916,172 @@ impl HabitApp {
         confirm_password_entry.set_visibility(false);
         confirm_password_entry.set_placeholder_text(Some("Confirm new password"));
         
+        let new_keyfile_label = Label::new(Some("New keyfile:"));
+        new_keyfile_label.set_xalign(0.0);
+        let new_keyfile_chooser = KeyfileChooser::new(remembered_keyfile, Some(storage));
+        
+        let keyfile_hint = Label::new(Some("A keyfile can be any file that never changes, kept for example on a USB stick. Without it a keyfile-protected vault can only be opened with the recovery key."));
+        keyfile_hint.set_wrap(true);
+        keyfile_hint.set_xalign(0.0);
+        keyfile_hint.add_css_class("dim-label");
+        
         let change_password_button = Button::with_label("Change Password");
         change_password_button.add_css_class("suggested-action");
         
+        // Only show the new-credential fields the chosen method uses
+        let update_method_fields = {
+            let new_password_entry = new_password_entry.clone();
+            let confirm_password_entry = confirm_password_entry.clone();
+            let new_keyfile_label = new_keyfile_label.clone();
+            let new_keyfile_chooser = new_keyfile_chooser.clone();
+            let keyfile_hint = keyfile_hint.clone();
+            let change_password_button = change_password_button.clone();
+            move |method: KeySlotKind| {
+                new_password_entry.set_visible(method.needs_password());
+                confirm_password_entry.set_visible(method.needs_password());
+                new_keyfile_label.set_visible(method.needs_keyfile());
+                new_keyfile_chooser.widget().set_visible(method.needs_keyfile());
+                keyfile_hint.set_visible(method.needs_keyfile());
+                change_password_button.set_label(if method == KeySlotKind::Password {
+                    "Change Password"
+                } else {
+                    "Update Unlock Method"
+                });
+            }
+        };
+        update_method_fields(current_method);
+        method_dropdown.connect_selected_notify(move |dropdown| {
+            if let Some(method) = KeySlotKind::UNLOCK_METHODS.get(dropdown.selected() as usize).copied() {
+                update_method_fields(method);
+            }
+        });
+        
         password_section.append(&current_password_entry);
+        password_section.append(&current_keyfile_label);
+        password_section.append(current_keyfile_chooser.widget());
+        password_section.append(&method_dropdown);
         password_section.append(&new_password_entry);
         password_section.append(&confirm_password_entry);
+        password_section.append(&new_keyfile_label);
+        password_section.append(new_keyfile_chooser.widget());
+        password_section.append(&keyfile_hint);
         password_section.append(&change_password_button);
         
         settings_box.append(&password_section);
         
+        // Key derivation strength section
+        let strength_section = GtkBox::new(Orientation::Vertical, 5);
+        strength_section.append(&Label::new(Some("Encryption Strength")));
+        
+        let strength_row = GtkBox::new(Orientation::Horizontal, 10);
+        let profile_labels: Vec<&str> = KdfProfile::ALL.iter().map(|profile| profile.label()).collect();
+        let strength_dropdown = DropDown::from_strings(&profile_labels);
+        strength_dropdown.set_hexpand(true);
+        let current_profile = storage.load_settings().kdf_profile.unwrap_or(KdfProfile::Standard);
+        let current_index = KdfProfile::ALL.iter().position(|profile| *profile == current_profile).unwrap_or(0);
+        strength_dropdown.set_selected(current_index as u32);
+        
+        let benchmark_button = Button::with_label("Benchmark");
+        benchmark_button.set_tooltip_text(Some("Pick the strongest setting that unlocks in about a second on this computer"));
+        
+        strength_row.append(&strength_dropdown);
+        strength_row.append(&benchmark_button);
+        strength_section.append(&strength_row);
+        
+        let strength_hint = Label::new(Some("Stronger settings make password guessing slower but take longer to unlock. Applied the next time you unlock or change your password."));
+        strength_hint.set_wrap(true);
+        strength_hint.set_xalign(0.0);
+        strength_hint.add_css_class("dim-label");
+        strength_section.append(&strength_hint);
+        
+        settings_box.append(&strength_section);
+        
+        // Add separator
+        let separator_strength = gtk4::Separator::new(Orientation::Horizontal);
+        separator_strength.set_margin_top(10);
+        separator_strength.set_margin_bottom(10);
+        settings_box.append(&separator_strength);
+        
+        // Auto-lock section
+        let lock_section = GtkBox::new(Orientation::Vertical, 5);
+        lock_section.append(&Label::new(Some("Auto-Lock")));
+        
+        let settings = storage.load_settings();
+        let lock_labels: Vec<String> = AUTO_LOCK_CHOICES.iter()
+            .map(|minutes| match minutes {
+                0 => "Never".to_string(),
+                1 => "After 1 minute of inactivity".to_string(),
+                n => format!("After {} minutes of inactivity", n),
+            })
+            .collect();
+        let lock_labels: Vec<&str> = lock_labels.iter().map(String::as_str).collect();
+        let lock_dropdown = DropDown::from_strings(&lock_labels);
+        let lock_index = AUTO_LOCK_CHOICES.iter().position(|m| *m == settings.auto_lock_minutes).unwrap_or(0);
+        lock_dropdown.set_selected(lock_index as u32);
+        lock_section.append(&lock_dropdown);
+        
+        let hidden_row = GtkBox::new(Orientation::Horizontal, 10);
+        let hidden_label = Label::new(Some("Lock when minimized or the screen locks"));
+        hidden_label.set_hexpand(true);
+        hidden_label.set_xalign(0.0);
+        let hidden_switch = Switch::new();
+        hidden_switch.set_active(settings.lock_when_hidden);
+        hidden_row.append(&hidden_label);
+        hidden_row.append(&hidden_switch);
+        lock_section.append(&hidden_row);
+        
+        let wipe_row = GtkBox::new(Orientation::Horizontal, 10);
+        let wipe_label = Label::new(Some("Delete all data after failed unlock attempts"));
+        wipe_label.set_hexpand(true);
+        wipe_label.set_xalign(0.0);
+        let wipe_labels: Vec<String> = WIPE_AFTER_CHOICES.iter()
+            .map(|failures| match failures {
+                0 => "Never".to_string(),
+                n => format!("After {} in a row", n),
+            })
+            .collect();
+        let wipe_labels: Vec<&str> = wipe_labels.iter().map(String::as_str).collect();
+        let wipe_dropdown = DropDown::from_strings(&wipe_labels);
+        let wipe_index = WIPE_AFTER_CHOICES.iter().position(|n| *n == settings.wipe_after_failures).unwrap_or(0);
+        wipe_dropdown.set_selected(wipe_index as u32);
+        wipe_row.append(&wipe_label);
+        wipe_row.append(&wipe_dropdown);
+        lock_section.append(&wipe_row);
+        
+        let wipe_hint = Label::new(Some("Repeated failed attempts always add a growing delay. Deleted data can only be brought back from a backup file."));
+        wipe_hint.set_wrap(true);
+        wipe_hint.set_xalign(0.0);
+        wipe_hint.add_css_class("dim-label");
+        lock_section.append(&wipe_hint);
+        
+        settings_box.append(&lock_section);
+        
+        // Add separator
+        let separator_lock = gtk4::Separator::new(Orientation::Horizontal);
+        separator_lock.set_margin_top(10);
+        separator_lock.set_margin_bottom(10);
+        settings_box.append(&separator_lock);
+        
+        // Recovery key section
+        let recovery_section = GtkBox::new(Orientation::Vertical, 5);
+        recovery_section.append(&Label::new(Some("Recovery Key")));
+        
+        let has_recovery_key = session.borrow().as_ref().is_some_and(Session::has_recovery_key);
+        let recovery_hint = Label::new(Some(if has_recovery_key {
+            "A recovery key can reset your password if you forget it. Generating a new one replaces the old key."
+        } else {
+            "You have no recovery key. Without one, a forgotten password means losing your data."
+        }));
+        recovery_hint.set_wrap(true);
+        recovery_hint.set_xalign(0.0);
+        recovery_hint.add_css_class("dim-label");
+        recovery_section.append(&recovery_hint);
+        
+        let recovery_button = Button::with_label("Generate New Recovery Key");
+        recovery_section.append(&recovery_button);
+        
+        settings_box.append(&recovery_section);
+        
         // Add separator
         let separator2 = gtk4::Separator::new(Orientation::Horizontal);
         separator2.set_margin_top(10);
@@ -349,6 +1106,19 @@ impl HabitApp {
         backup_restore_box.append(&import_button);
         
         backup_section.append(&backup_restore_box);
+        
+        let csv_box = GtkBox::new(Orientation::Horizontal, 10);
+        
+        let export_csv_button = Button::with_label("📊 Export CSV");
+        export_csv_button.set_tooltip_text(Some("Export habits and completions as unencrypted CSV files for spreadsheets"));
+        
+        let import_csv_button = Button::with_label("📥 Import CSV");
+        import_csv_button.set_tooltip_text(Some("Add completions from a CSV file, such as an export from another habit tracker"));
+        
+        csv_box.append(&export_csv_button);
+        csv_box.append(&import_csv_button);
+        
+        backup_section.append(&csv_box);
         settings_box.append(&backup_section);
         
         // Add separator
@@ -380,59 +1150,195 @@ impl HabitApp {
             }
         });
 
+        let storage_strength = storage.clone();
+        let toast_overlay_strength = toast_overlay.clone();
+        strength_dropdown.connect_selected_notify(move |dropdown| {
+            let Some(profile) = KdfProfile::ALL.get(dropdown.selected() as usize).copied() else {
+                return;
+            };
+            let mut settings = storage_strength.load_settings();
+            if settings.kdf_profile == Some(profile) {
+                return;
+            }
+            settings.kdf_profile = Some(profile);
+            if let Err(e) = storage_strength.save_settings(&settings) {
+                let error_toast = Toast::new(&format!("Failed to save settings: {}", e));
+                toast_overlay_strength.add_toast(error_toast);
+            }
+        });
+        
+        let storage_lock = storage.clone();
+        let toast_overlay_lock = toast_overlay.clone();
+        let auto_lock_minutes = auto_lock.clone();
+        lock_dropdown.connect_selected_notify(move |dropdown| {
+            let Some(minutes) = AUTO_LOCK_CHOICES.get(dropdown.selected() as usize).copied() else {
+                return;
+            };
+            auto_lock_minutes.minutes.set(minutes);
+            let mut settings = storage_lock.load_settings();
+            settings.auto_lock_minutes = minutes;
+            if let Err(e) = storage_lock.save_settings(&settings) {
+                let error_toast = Toast::new(&format!("Failed to save settings: {}", e));
+                toast_overlay_lock.add_toast(error_toast);
+            }
+        });
+        
+        let storage_hidden = storage.clone();
+        let toast_overlay_hidden = toast_overlay.clone();
+        let auto_lock_hidden = auto_lock.clone();
+        hidden_switch.connect_active_notify(move |switch| {
+            auto_lock_hidden.when_hidden.set(switch.is_active());
+            let mut settings = storage_hidden.load_settings();
+            settings.lock_when_hidden = switch.is_active();
+            if let Err(e) = storage_hidden.save_settings(&settings) {
+                let error_toast = Toast::new(&format!("Failed to save settings: {}", e));
+                toast_overlay_hidden.add_toast(error_toast);
+            }
+        });
+        
+        let storage_wipe = storage.clone();
+        let toast_overlay_wipe = toast_overlay.clone();
+        wipe_dropdown.connect_selected_notify(move |dropdown| {
+            let Some(failures) = WIPE_AFTER_CHOICES.get(dropdown.selected() as usize).copied() else {
+                return;
+            };
+            let mut settings = storage_wipe.load_settings();
+            settings.wipe_after_failures = failures;
+            if let Err(e) = storage_wipe.save_settings(&settings) {
+                let error_toast = Toast::new(&format!("Failed to save settings: {}", e));
+                toast_overlay_wipe.add_toast(error_toast);
+            }
+        });
+        
+        let storage_benchmark = storage.clone();
+        let toast_overlay_benchmark = toast_overlay.clone();
+        benchmark_button.connect_clicked(move |button| {
+            button.set_sensitive(false);
+            let button = button.clone();
+            let strength_dropdown = strength_dropdown.clone();
+            let toast_overlay = toast_overlay_benchmark.clone();
+            storage_benchmark.benchmark_kdf(move |result| {
+                button.set_sensitive(true);
+                match result {
+                    Ok(profile) => {
+                        let index = KdfProfile::ALL.iter().position(|p| *p == profile).unwrap_or(0);
+                        strength_dropdown.set_selected(index as u32);
+                        let toast = Toast::new(&format!("Recommended for this computer: {}", profile.label()));
+                        toast_overlay.add_toast(toast);
+                    }
+                    Err(e) => {
+                        let error_toast = Toast::new(&format!("Benchmark failed: {}", e));
+                        toast_overlay.add_toast(error_toast);
+                    }
+                }
+            });
+        });
+        
         let storage_clone = storage.clone();
-        let password_clone = password.clone();
+        let session_clone = session.clone();
         let habit_data_clone = habit_data.clone();
         let toast_overlay_clone = toast_overlay.clone();
         
         change_password_button.connect_clicked(move |_| {
-            let current_pass = current_password_entry.text().to_string();
-            let new_pass = new_password_entry.text().to_string();
-            let confirm_pass = confirm_password_entry.text().to_string();
+            let Some(current_session) = session_clone.borrow().clone() else {
+                return;
+            };
+            let current_method = current_session.unlock_method().unwrap_or(KeySlotKind::Password);
+            let Some(new_method) = KeySlotKind::UNLOCK_METHODS.get(method_dropdown.selected() as usize).copied() else {
+                return;
+            };
+            
+            let current_pass = Zeroizing::new(current_password_entry.text().to_string());
+            let new_pass = Zeroizing::new(new_password_entry.text().to_string());
+            let confirm_pass = Zeroizing::new(confirm_password_entry.text().to_string());
+            current_password_entry.set_text("");
+            new_password_entry.set_text("");
+            confirm_password_entry.set_text("");
             
-            if current_pass.is_empty() || new_pass.is_empty() || confirm_pass.is_empty() {
+            let current_keyfile = current_method.needs_keyfile().then(|| current_keyfile_chooser.path()).flatten();
+            let new_keyfile = new_method.needs_keyfile().then(|| new_keyfile_chooser.path()).flatten();
+            
+            if (current_method.needs_password() && current_pass.is_empty())
+                || (new_method.needs_password() && (new_pass.is_empty() || confirm_pass.is_empty())) {
                 let error_toast = Toast::new("Please fill in all password fields");
                 toast_overlay_clone.add_toast(error_toast);
                 return;
             }
             
+            if (current_method.needs_keyfile() && current_keyfile.is_none())
+                || (new_method.needs_keyfile() && new_keyfile.is_none()) {
+                let error_toast = Toast::new("Please choose a keyfile");
+                toast_overlay_clone.add_toast(error_toast);
+                return;
+            }
+            
             if new_pass != confirm_pass {
                 let error_toast = Toast::new("New passwords do not match");
                 toast_overlay_clone.add_toast(error_toast);
                 return;
             }
             
-            // Verify current password by trying to load data
-            match storage_clone.load(&current_pass) {
-                Ok(_) => {
-                    // Current password is correct, now save with new password
-                    match storage_clone.save(&habit_data_clone.borrow(), &new_pass) {
-                        Ok(_) => {
-                            password_clone.replace(Some(new_pass));
-                            let success_toast = Toast::new("Password changed successfully");
-                            toast_overlay_clone.add_toast(success_toast);
-                            
-                            // Clear the input fields
-                            current_password_entry.set_text("");
-                            new_password_entry.set_text("");
-                            confirm_password_entry.set_text("");
-                        }
-                        Err(e) => {
-                            let error_toast = Toast::new(&format!("Failed to save with new password: {}", e));
-                            toast_overlay_clone.add_toast(error_toast);
-                        }
+            let current = CredentialInput {
+                password: if current_method.needs_password() { current_pass } else { Zeroizing::new(String::new()) },
+                keyfile: current_keyfile,
+            };
+            let new = CredentialInput {
+                password: if new_method.needs_password() { new_pass } else { Zeroizing::new(String::new()) },
+                keyfile: new_keyfile.clone(),
+            };
+            
+            // Verify the current credentials and derive the new key off the main thread
+            let session_inner = session_clone.clone();
+            let storage_inner = storage_clone.clone();
+            let habit_data_inner = habit_data_clone.clone();
+            let toast_overlay_inner = toast_overlay_clone.clone();
+            let current_password_entry = current_password_entry.clone();
+            let current_keyfile_label = current_keyfile_label.clone();
+            let current_keyfile_chooser = current_keyfile_chooser.clone();
+            let previous_session = current_session.clone();
+            storage_clone.change_credentials(current_session, current, new, move |result| match result {
+                Ok((new_session, recovery_key)) => {
+                    storage_inner.save_rekeyed(&habit_data_inner.borrow(), &previous_session, &new_session);
+                    session_inner.replace(Some(new_session));
+                    Self::remember_keyfile(&storage_inner, &toast_overlay_inner, new_keyfile.clone());
+                    
+                    // What is current has changed for the next change
+                    current_password_entry.set_visible(new_method.needs_password());
+                    current_keyfile_label.set_visible(new_method.needs_keyfile());
+                    current_keyfile_chooser.widget().set_visible(new_method.needs_keyfile());
+                    current_keyfile_chooser.set_path(new_keyfile);
+                    
+                    let success_toast = Toast::new(if new_method == current_method && new_method == KeySlotKind::Password {
+                        "Password changed successfully"
+                    } else {
+                        "Unlock method updated"
+                    });
+                    toast_overlay_inner.add_toast(success_toast);
+                    
+                    // The old recovery key was tied to the replaced data key
+                    if let Some(recovery_key) = recovery_key {
+                        Self::show_recovery_key_dialog(&recovery_key, "Your old recovery key no longer works. ");
                     }
                 }
-                Err(_) => {
-                    let error_toast = Toast::new("Current password is incorrect");
-                    toast_overlay_clone.add_toast(error_toast);
+                Err(e) => {
+                    let error_toast = Toast::new(&e.to_string());
+                    toast_overlay_inner.add_toast(error_toast);
                 }
-            }
+            });
+        });
+
+        let storage_recovery = storage.clone();
+        let session_recovery = session.clone();
+        let habit_data_recovery = habit_data.clone();
+        let toast_overlay_recovery = toast_overlay.clone();
+        recovery_button.connect_clicked(move |_| {
+            Self::create_recovery_key(&storage_recovery, &session_recovery, &habit_data_recovery, &toast_overlay_recovery);
         });
 
         // Export backup button event handler
         let storage_export = storage.clone();
-        let password_export = password.clone();
+        let session_export = session.clone();
+        let habit_data_export = habit_data.clone();
         let toast_overlay_export = toast_overlay.clone();
         
         export_button.connect_clicked(move |_| {
@@ -451,12 +1357,14 @@ impl HabitApp {
             content_area.append(&backup_password_entry);
 
             let storage_export_inner = storage_export.clone();
-            let password_export_inner = password_export.clone();
+            let session_export_inner = session_export.clone();
+            let habit_data_export_inner = habit_data_export.clone();
             let toast_overlay_export_inner = toast_overlay_export.clone();
 
             password_dialog.connect_response(move |dialog, response| {
                 if response == ResponseType::Ok {
-                    let backup_password = backup_password_entry.text().to_string();
+                    let backup_password = Zeroizing::new(backup_password_entry.text().to_string());
+                    backup_password_entry.set_text("");
                     if !backup_password.is_empty() {
                         let file_chooser = FileChooserDialog::new(
                             Some("Export Habits Backup"),
@@ -473,24 +1381,27 @@ impl HabitApp {
                         file_chooser.add_filter(&filter);
                         
                         let storage_export_inner2 = storage_export_inner.clone();
-                        let password_export_inner2 = password_export_inner.clone();
+                        let session_export_inner2 = session_export_inner.clone();
+                        let habit_data_export_inner2 = habit_data_export_inner.clone();
                         let toast_overlay_export_inner2 = toast_overlay_export_inner.clone();
                         
                         file_chooser.connect_response(move |dialog, response| {
                             if response == ResponseType::Accept {
                                 if let Some(file) = dialog.file() {
                                     if let Some(path) = file.path() {
-                                        if let Some(ref current_pass) = *password_export_inner2.borrow() {
-                                            match storage_export_inner2.export_backup(current_pass, &backup_password, &path) {
+                                        if session_export_inner2.borrow().is_some() {
+                                            let toast_overlay_export_inner3 = toast_overlay_export_inner2.clone();
+                                            let data = habit_data_export_inner2.borrow().clone();
+                                            storage_export_inner2.export_backup(data, backup_password.clone(), path, move |result| match result {
                                                 Ok(_) => {
                                                     let success_toast = Toast::new("Encrypted backup exported successfully!");
-                                                    toast_overlay_export_inner2.add_toast(success_toast);
+                                                    toast_overlay_export_inner3.add_toast(success_toast);
                                                 }
                                                 Err(e) => {
                                                     let error_toast = Toast::new(&format!("Failed to export backup: {}", e));
-                                                    toast_overlay_export_inner2.add_toast(error_toast);
+                                                    toast_overlay_export_inner3.add_toast(error_toast);
                                                 }
-                                            }
+                                            });
                                         } else {
                                             let error_toast = Toast::new("No password available for export");
                                             toast_overlay_export_inner2.add_toast(error_toast);
@@ -515,7 +1426,7 @@ impl HabitApp {
 
         // Import backup button event handler
         let storage_import = storage.clone();
-        let password_import = password.clone();
+        let session_import = session.clone();
         let habit_data_import = habit_data.clone();
         let habit_list_import = habit_list.clone();
         let toast_overlay_import = toast_overlay.clone();
@@ -530,7 +1441,7 @@ impl HabitApp {
             );
             
             let storage_import_inner = storage_import.clone();
-            let password_import_inner = password_import.clone();
+            let session_import_inner = session_import.clone();
             let habit_data_import_inner = habit_data_import.clone();
             let habit_list_import_inner = habit_list_import.clone();
             let toast_overlay_import_inner = toast_overlay_import.clone();
@@ -550,7 +1461,7 @@ impl HabitApp {
                     file_chooser.add_filter(&filter);
                     
                     let storage_import_inner2 = storage_import_inner.clone();
-                    let password_import_inner2 = password_import_inner.clone();
+                    let session_import_inner2 = session_import_inner.clone();
                     let habit_data_import_inner2 = habit_data_import_inner.clone();
                     let habit_list_import_inner2 = habit_list_import_inner.clone();
                     let toast_overlay_import_inner2 = toast_overlay_import_inner.clone();
@@ -574,7 +1485,7 @@ impl HabitApp {
                                     content_area.append(&backup_password_entry);
 
                                     let storage_import_inner3 = storage_import_inner2.clone();
-                                    let password_import_inner3 = password_import_inner2.clone();
+                                    let session_import_inner3 = session_import_inner2.clone();
                                     let habit_data_import_inner3 = habit_data_import_inner2.clone();
                                     let habit_list_import_inner3 = habit_list_import_inner2.clone();
                                     let toast_overlay_import_inner3 = toast_overlay_import_inner2.clone();
@@ -582,29 +1493,30 @@ impl HabitApp {
 
                                     password_dialog.connect_response(move |dialog, response| {
                                         if response == ResponseType::Ok {
-                                            let backup_password = backup_password_entry.text().to_string();
+                                            let backup_password = Zeroizing::new(backup_password_entry.text().to_string());
+                                            backup_password_entry.set_text("");
                                             if !backup_password.is_empty() {
-                                                if let Some(ref current_pass) = *password_import_inner3.borrow() {
-                                                    match storage_import_inner3.import_backup(&path_clone, &backup_password, current_pass) {
-                                                        Ok(_) => {
-                                                            match storage_import_inner3.load(current_pass) {
-                                                                Ok(new_data) => {
-                                                                    habit_data_import_inner3.replace(new_data);
-                                                                    Self::refresh_habit_list(&habit_list_import_inner3, &habit_data_import_inner3, &storage_import_inner3, &password_import_inner3);
-                                                                    let success_toast = Toast::new("Encrypted backup imported successfully!");
-                                                                    toast_overlay_import_inner3.add_toast(success_toast);
-                                                                }
-                                                                Err(e) => {
-                                                                    let error_toast = Toast::new(&format!("Failed to reload data after import: {}", e));
-                                                                    toast_overlay_import_inner3.add_toast(error_toast);
-                                                                }
+                                                if session_import_inner3.borrow().is_some() {
+                                                    let storage_import_inner4 = storage_import_inner3.clone();
+                                                    let session_import_inner4 = session_import_inner3.clone();
+                                                    let habit_data_import_inner4 = habit_data_import_inner3.clone();
+                                                    let habit_list_import_inner4 = habit_list_import_inner3.clone();
+                                                    let toast_overlay_import_inner4 = toast_overlay_import_inner3.clone();
+                                                    storage_import_inner3.read_backup(path_clone.clone(), backup_password, move |result| match result {
+                                                        Ok(new_data) => {
+                                                            if let Some(ref session) = *session_import_inner4.borrow() {
+                                                                storage_import_inner4.save(&new_data, session);
                                                             }
+                                                            habit_data_import_inner4.replace(new_data);
+                                                            Self::refresh_habit_list(&habit_list_import_inner4, &habit_data_import_inner4, &storage_import_inner4, &session_import_inner4);
+                                                            let success_toast = Toast::new("Encrypted backup imported successfully!");
+                                                            toast_overlay_import_inner4.add_toast(success_toast);
                                                         }
                                                         Err(e) => {
                                                             let error_toast = Toast::new(&format!("Failed to import backup: {}", e));
-                                                            toast_overlay_import_inner3.add_toast(error_toast);
+                                                            toast_overlay_import_inner4.add_toast(error_toast);
                                                         }
-                                                    }
+                                                    });
                                                 } else {
                                                     let error_toast = Toast::new("No password available for import");
                                                     toast_overlay_import_inner3.add_toast(error_toast);
@@ -632,9 +1544,115 @@ impl HabitApp {
             confirmation_dialog.show();
         });
 
+        // Export CSV button event handler
+        let storage_export_csv = storage.clone();
+        let habit_data_export_csv = habit_data.clone();
+        let toast_overlay_export_csv = toast_overlay.clone();
+        
+        export_csv_button.connect_clicked(move |_| {
+            let file_chooser = FileChooserDialog::new(
+                Some("Export CSV to Folder"),
+                None::<&ApplicationWindow>,
+                FileChooserAction::SelectFolder,
+                &[("Cancel", ResponseType::Cancel), ("Export", ResponseType::Accept)]
+            );
+            
+            let storage_export_csv_inner = storage_export_csv.clone();
+            let habit_data_export_csv_inner = habit_data_export_csv.clone();
+            let toast_overlay_export_csv_inner = toast_overlay_export_csv.clone();
+            
+            file_chooser.connect_response(move |dialog, response| {
+                if response == ResponseType::Accept
+                    && let Some(dir) = dialog.file().and_then(|file| file.path()) {
+                    let toast_overlay = toast_overlay_export_csv_inner.clone();
+                    let data = habit_data_export_csv_inner.borrow().clone();
+                    storage_export_csv_inner.export_csv(data, dir, move |result| match result {
+                        Ok(_) => {
+                            let success_toast = Toast::new(&format!("Exported {} and {}. These files are not encrypted", csv_io::HABITS_FILE, csv_io::COMPLETIONS_FILE));
+                            toast_overlay.add_toast(success_toast);
+                        }
+                        Err(e) => {
+                            let error_toast = Toast::new(&format!("Failed to export CSV: {}", e));
+                            toast_overlay.add_toast(error_toast);
+                        }
+                    });
+                }
+                dialog.close();
+            });
+            
+            file_chooser.show();
+        });
+        
+        // Import CSV button event handler
+        let storage_import_csv = storage.clone();
+        let session_import_csv = session.clone();
+        let habit_data_import_csv = habit_data.clone();
+        let habit_list_import_csv = habit_list.clone();
+        let toast_overlay_import_csv = toast_overlay.clone();
+        
+        import_csv_button.connect_clicked(move |_| {
+            let file_chooser = FileChooserDialog::new(
+                Some("Import CSV"),
+                None::<&ApplicationWindow>,
+                FileChooserAction::Open,
+                &[("Cancel", ResponseType::Cancel), ("Open", ResponseType::Accept)]
+            );
+            
+            let filter = FileFilter::new();
+            filter.add_pattern("*.csv");
+            filter.add_pattern("*.tsv");
+            filter.add_pattern("*.txt");
+            filter.set_name(Some("CSV files"));
+            file_chooser.add_filter(&filter);
+            
+            let storage_import_csv_inner = storage_import_csv.clone();
+            let session_import_csv_inner = session_import_csv.clone();
+            let habit_data_import_csv_inner = habit_data_import_csv.clone();
+            let habit_list_import_csv_inner = habit_list_import_csv.clone();
+            let toast_overlay_import_csv_inner = toast_overlay_import_csv.clone();
+            
+            file_chooser.connect_response(move |dialog, response| {
+                if response == ResponseType::Accept
+                    && let Some(path) = dialog.file().and_then(|file| file.path()) {
+                    let storage = storage_import_csv_inner.clone();
+                    let session = session_import_csv_inner.clone();
+                    let habit_data = habit_data_import_csv_inner.clone();
+                    let habit_list = habit_list_import_csv_inner.clone();
+                    let toast_overlay = toast_overlay_import_csv_inner.clone();
+                    storage_import_csv_inner.read_csv(path, move |result| match result {
+                        Ok(table) if table.headers.is_empty() => {
+                            toast_overlay.add_toast(Toast::new("The CSV file is empty"));
+                        }
+                        Ok(table) => {
+                            let habit_data_import = habit_data.clone();
+                            show_csv_import_dialog(table, &habit_data, move |plan| {
+                                let imported = csv_io::apply_import(&mut habit_data_import.borrow_mut(), &plan);
+                                if let Some(ref session) = *session.borrow() {
+                                    storage.save(&habit_data_import.borrow(), session);
+                                }
+                                Self::refresh_habit_list(&habit_list, &habit_data_import, &storage, &session);
+                                let message = match imported {
+                                    1 => "Imported 1 day".to_string(),
+                                    count => format!("Imported {} days", count),
+                                };
+                                toast_overlay.add_toast(Toast::new(&message));
+                            });
+                        }
+                        Err(e) => {
+                            let error_toast = Toast::new(&format!("Failed to read CSV: {}", e));
+                            toast_overlay.add_toast(error_toast);
+                        }
+                    });
+                }
+                dialog.close();
+            });
+            
+            file_chooser.show();
+        });
+        
         // Delete all data button event handler
         let storage_delete = storage.clone();
-        let password_delete = password.clone();
+        let session_delete = session.clone();
         let habit_data_delete = habit_data.clone();
         let habit_list_delete = habit_list.clone();
         let toast_overlay_delete = toast_overlay.clone();
@@ -649,7 +1667,7 @@ impl HabitApp {
             );
             
             let storage_delete_inner = storage_delete.clone();
-            let password_delete_inner = password_delete.clone();
+            let session_delete_inner = session_delete.clone();
             let habit_data_delete_inner = habit_data_delete.clone();
             let habit_list_delete_inner = habit_list_delete.clone();
             let toast_overlay_delete_inner = toast_overlay_delete.clone();
@@ -657,13 +1675,18 @@ impl HabitApp {
             confirmation_dialog.connect_response(move |dialog, response| {
                 if response == ResponseType::Yes {
                     // Delete all data
-                    if let Err(e) = storage_delete_inner.delete_all_data() {
+                    let session_delete_inner = session_delete_inner.clone();
+                    let habit_data_delete_inner = habit_data_delete_inner.clone();
+                    let habit_list_delete_inner = habit_list_delete_inner.clone();
+                    let toast_overlay_delete_inner = toast_overlay_delete_inner.clone();
+                    let storage_delete_done = storage_delete_inner.clone();
+                    storage_delete_inner.delete_all_data(move |result| if let Err(e) = result {
                         let error_toast = Toast::new(&format!("Failed to delete data: {}", e));
                         toast_overlay_delete_inner.add_toast(error_toast);
                     } else {
                         // Clear in-memory data
                         habit_data_delete_inner.replace(HabitData::new());
-                        password_delete_inner.replace(None);
+                        session_delete_inner.replace(None);
                         
                         // Clear the habit list
                         while let Some(child) = habit_list_delete_inner.first_child() {
@@ -675,13 +1698,14 @@ impl HabitApp {
                         toast_overlay_delete_inner.add_toast(success_toast);
                         
                         // Trigger password setup dialog
-                        let storage_for_dialog = storage_delete_inner.clone();
-                        let password_for_dialog = password_delete_inner.clone();
+                        let storage_for_dialog = storage_delete_done.clone();
+                        let session_for_dialog = session_delete_inner.clone();
                         let habit_data_for_dialog = habit_data_delete_inner.clone();
+                        let toast_overlay_for_dialog = toast_overlay_delete_inner.clone();
                         glib::idle_add_local_once(move || {
-                            let _ = Self::show_password_setup_dialog_static(&storage_for_dialog, &password_for_dialog, &habit_data_for_dialog);
+                            let _ = Self::show_password_setup_dialog_static(&storage_for_dialog, &session_for_dialog, &habit_data_for_dialog, &toast_overlay_for_dialog);
                         });
-                    }
+                    });
                 }
                 dialog.close();
             });
@@ -699,8 +1723,8 @@ impl HabitApp {
 
     fn show_add_habit_dialog(
         habit_data: &Rc<RefCell<HabitData>>,
-        storage: SecureStorage,
-        password: &Rc<RefCell<Option<String>>>,
+        storage: StorageService,
+        session: &Rc<RefCell<Option<Session>>>,
         habit_list: &ListBox,
         toast_overlay: &ToastOverlay,
     ) {
@@ -721,16 +1745,21 @@ impl HabitApp {
         let desc_entry = Entry::new();
         desc_entry.set_placeholder_text(Some("Description"));
         
+        let schedule_editor = ScheduleEditor::new(&Schedule::Daily, 7, habit_data.borrow().today());
+        let quantity_editor = QuantityEditor::new(None);
+        
         vbox.append(&Label::new(Some("Name:")));
         vbox.append(&name_entry);
         vbox.append(&Label::new(Some("Description:")));
         vbox.append(&desc_entry);
+        vbox.append(schedule_editor.widget());
+        vbox.append(quantity_editor.widget());
         
         content_area.append(&vbox);
 
         let habit_data_clone = habit_data.clone();
         let storage_clone = storage;
-        let password_clone = password.clone();
+        let session_clone = session.clone();
         let habit_list_clone = habit_list.clone();
         let toast_overlay_clone = toast_overlay.clone();
         
@@ -756,27 +1785,32 @@ impl HabitApp {
             if response == ResponseType::Ok {
                 let name = name_entry.text().to_string();
                 let description = desc_entry.text().to_string();
+                let target_days_per_week = schedule_editor.target_days_per_week();
+                let (Some(schedule), Some(quantity)) = (schedule_editor.schedule(), quantity_editor.quantity()) else {
+                    // Keep the dialog open so the input can be corrected
+                    return;
+                };
                 
                 if !name.is_empty() {
                     // Check if password is available before allowing habit creation
-                    if let Some(ref pass) = *password_clone.borrow() {
+                    if let Some(ref session) = *session_clone.borrow() {
                         let habit = Habit {
-                            id: format!("habit_{}", Utc::now().timestamp()),
+                            id: habit_data_clone.borrow().new_habit_id(),
                             name: name.clone(),
                             description,
-                            created_at: Utc::now(),
-                            target_days_per_week: 7,
+                            created_at: habit_data_clone.borrow().clock().now(),
+                            target_days_per_week,
+                            schedule,
+                            quantity,
                             streak: 0,
                             longest_streak: 0,
                         };
                         
                         habit_data_clone.borrow_mut().add_habit(habit);
                         
-                        if let Err(e) = storage_clone.save(&habit_data_clone.borrow(), pass) {
-                            eprintln!("Failed to save data: {}", e);
-                        }
+                        storage_clone.save(&habit_data_clone.borrow(), session);
                         
-                        Self::refresh_habit_list(&habit_list_clone, &habit_data_clone, &storage_clone, &password_clone);
+                        Self::refresh_habit_list(&habit_list_clone, &habit_data_clone, &storage_clone, &session_clone);
                         
                         let toast = Toast::new(&format!("Added habit: {}", name));
                         toast_overlay_clone.add_toast(toast);
@@ -797,8 +1831,8 @@ impl HabitApp {
         habit_name: &str,
         habit_data: &Rc<RefCell<HabitData>>,
         habit_list: &ListBox,
-        storage: &SecureStorage,
-        password: &Rc<RefCell<Option<String>>>
+        storage: &StorageService,
+        session: &Rc<RefCell<Option<Session>>>
     ) {
         println!("Showing delete confirmation for habit: {}", habit_name);
         let dialog = MessageDialog::new(
@@ -813,7 +1847,7 @@ impl HabitApp {
         let habit_data = habit_data.clone();
         let habit_list = habit_list.clone();
         let storage = storage.clone();
-        let password = password.clone();
+        let session = session.clone();
         
         dialog.connect_response(move |dialog, response| {
             if response == ResponseType::Yes {
@@ -821,14 +1855,12 @@ impl HabitApp {
                 habit_data.borrow_mut().remove_habit(&habit_id);
                 
                 // Save the updated data
-                if let Some(ref pass) = *password.borrow() {
-                    if let Err(e) = storage.save(&habit_data.borrow(), pass) {
-                        eprintln!("Failed to save data after deletion: {}", e);
-                    }
+                if let Some(ref session) = *session.borrow() {
+                    storage.save(&habit_data.borrow(), session);
                 }
                 
                 // Refresh the habit list
-                Self::refresh_habit_list(&habit_list, &habit_data, &storage, &password);
+                Self::refresh_habit_list(&habit_list, &habit_data, &storage, &session);
             }
             dialog.close();
         });
@@ -837,13 +1869,11 @@ impl HabitApp {
     }
 
     fn show_edit_dialog(
-        habit_id: &str,
-        current_name: &str,
-        current_description: &str,
+        habit: &Habit,
         habit_data: &Rc<RefCell<HabitData>>,
         habit_list: &ListBox,
-        storage: &SecureStorage,
-        password: &Rc<RefCell<Option<String>>>
+        storage: &StorageService,
+        session: &Rc<RefCell<Option<Session>>>
     ) {
         let dialog = Dialog::new();
         dialog.set_title(Some("Edit Habit"));
@@ -862,20 +1892,28 @@ impl HabitApp {
         let name_label = Label::new(Some("Habit Name:"));
         name_label.set_halign(gtk4::Align::Start);
         let name_entry = Entry::new();
-        name_entry.set_text(current_name);
+        name_entry.set_text(&habit.name);
         name_entry.set_placeholder_text(Some("Enter habit name"));
         
         // Description field
         let desc_label = Label::new(Some("Description:"));
         desc_label.set_halign(gtk4::Align::Start);
         let desc_entry = Entry::new();
-        desc_entry.set_text(current_description);
+        desc_entry.set_text(&habit.description);
         desc_entry.set_placeholder_text(Some("Enter habit description"));
         
+        // Schedule fields
+        let schedule_editor = ScheduleEditor::new(&habit.schedule, habit.target_days_per_week, habit_data.borrow().today());
+        
+        // Amount fields
+        let quantity_editor = QuantityEditor::new(habit.quantity.as_ref());
+        
         main_box.append(&name_label);
         main_box.append(&name_entry);
         main_box.append(&desc_label);
         main_box.append(&desc_entry);
+        main_box.append(schedule_editor.widget());
+        main_box.append(quantity_editor.widget());
         
         content_area.append(&main_box);
         
@@ -883,30 +1921,33 @@ impl HabitApp {
         dialog.add_button("Cancel", ResponseType::Cancel);
         dialog.add_button("Save", ResponseType::Accept);
         
-        let habit_id = habit_id.to_string();
+        let habit_id = habit.id.clone();
         let habit_data = habit_data.clone();
         let habit_list = habit_list.clone();
         let storage = storage.clone();
-        let password = password.clone();
+        let session = session.clone();
         
         dialog.connect_response(move |dialog, response| {
             if response == ResponseType::Accept {
                 let new_name = name_entry.text().to_string();
                 let new_description = desc_entry.text().to_string();
+                let new_target = schedule_editor.target_days_per_week();
+                let (Some(new_schedule), Some(new_quantity)) = (schedule_editor.schedule(), quantity_editor.quantity()) else {
+                    // Keep the dialog open so the input can be corrected
+                    return;
+                };
                 
                 if !new_name.trim().is_empty() {
                     // Update the habit
-                    habit_data.borrow_mut().update_habit(&habit_id, &new_name, &new_description);
+                    habit_data.borrow_mut().update_habit(&habit_id, &new_name, &new_description, new_target, new_schedule, new_quantity);
                     
                     // Save the updated data
-                    if let Some(ref pass) = *password.borrow() {
-                        if let Err(e) = storage.save(&habit_data.borrow(), pass) {
-                            eprintln!("Failed to save data after edit: {}", e);
-                        }
+                    if let Some(ref session) = *session.borrow() {
+                        storage.save(&habit_data.borrow(), session);
                     }
                     
                     // Refresh the habit list
-                    Self::refresh_habit_list(&habit_list, &habit_data, &storage, &password);
+                    Self::refresh_habit_list(&habit_list, &habit_data, &storage, &session);
                 }
             }
             dialog.close();
@@ -918,12 +1959,42 @@ impl HabitApp {
     fn refresh_habit_list(
         habit_list: &ListBox, 
         habit_data: &Rc<RefCell<HabitData>>,
-        storage: &SecureStorage,
-        password: &Rc<RefCell<Option<String>>>
+        storage: &StorageService,
+        session: &Rc<RefCell<Option<Session>>>
     ) {
         while let Some(child) = habit_list.first_child() {
             habit_list.remove(&child);
         }
+        
+        // Opens each habit's monthly calendar at a given day, filled in below
+        // and used by the overview heatmap
+        let open_at_day: Rc<RefCell<Vec<(String, OpenAtDay)>>> = Rc::new(RefCell::new(Vec::new()));
+        
+        if !habit_data.borrow().habits.is_empty() {
+            let open_at_day_overview = open_at_day.clone();
+            let habit_data_overview = habit_data.clone();
+            let overview = HabitHeatmap::new(habit_data.clone(), None, move |date| {
+                // Open the habits that counted that day
+                for (habit_id, open) in open_at_day_overview.borrow().iter() {
+                    if habit_data_overview.borrow().rate_on_date(habit_id, date).is_some() {
+                        open(date);
+                    }
+                }
+            });
+            let overview_box = GtkBox::new(Orientation::Vertical, 10);
+            overview_box.append(overview.widget());
+            overview_box.append(TrendsView::new(habit_data.clone(), None).widget());
+            
+            let overview_expander = Expander::new(Some("Overview"));
+            overview_expander.add_css_class("overview");
+            overview_expander.set_child(Some(&overview_box));
+            overview_expander.connect_expanded_notify(move |expander| {
+                if expander.is_expanded() {
+                    overview.refresh();
+                }
+            });
+            habit_list.append(&overview_expander);
+        }
 
         for habit in &habit_data.borrow().habits {
             // Create main container for habit
@@ -960,12 +2031,19 @@ impl HabitApp {
                 streak_number.add_css_class("high-streak");
             }
             
-            let streak_text = Label::new(Some(if habit.streak == 1 { "day streak" } else { "day streak" }));
+            let streak_text = Label::new(Some(habit.streak_unit()));
             streak_text.add_css_class("streak-text");
             
+            // Goal progress or schedule summary, hidden for plain daily habits
+            let schedule_status = get_schedule_status_text(habit, &habit_data.borrow());
+            let week_progress = Label::new(schedule_status.as_deref());
+            week_progress.add_css_class("week-progress");
+            week_progress.set_visible(schedule_status.is_some());
+            
             streak_box.append(&streak_label);
             streak_box.append(&streak_number);
             streak_box.append(&streak_text);
+            streak_box.append(&week_progress);
             
             habit_info_box.append(&title_button);
             habit_info_box.append(&streak_box);
@@ -981,8 +2059,17 @@ impl HabitApp {
             complete_button.set_halign(gtk4::Align::Center);
             complete_button.set_valign(gtk4::Align::Center);
             
+            // Quantitative habits also get a button for logging a partial amount
+            let log_button = MenuButton::new();
+            log_button.set_label("➕");
+            log_button.set_tooltip_text(Some("Log today's amount"));
+            log_button.add_css_class("compact-button");
+            log_button.set_size_request(40, 40);
+            log_button.set_valign(gtk4::Align::Center);
+            log_button.set_visible(habit.quantity.is_some());
+            
             let edit_button = Button::with_label("✏️");
-            edit_button.set_tooltip_text(Some("Edit this habit's name and description"));
+            edit_button.set_tooltip_text(Some("Edit this habit's name, description and schedule"));
             edit_button.set_has_tooltip(true);
             edit_button.add_css_class("compact-button");
             edit_button.set_size_request(40, 40);
@@ -1004,27 +2091,38 @@ impl HabitApp {
             // Add widgets to header row
             header_row.append(&habit_info_box);
             header_row.append(&complete_button);
+            header_row.append(&log_button);
             header_row.append(&edit_button);
             header_row.append(&delete_button);
             
+            // Trend charts shown under the calendar
+            let trends = TrendsView::new(habit_data.clone(), Some(habit.id.clone()));
+            
             // Create calendar view (initially hidden)
+            let trends_for_callback = trends.clone();
             let habit_id_for_callback = habit.id.clone();
             let habit_data_for_callback = habit_data.clone();
             let title_button_for_callback = title_button.clone();
             let streak_number_for_callback = streak_number.clone();
             let streak_label_for_callback = streak_label.clone();
+            let week_progress_for_callback = week_progress.clone();
             let habit_name_for_callback = habit.name.clone();
             let habit_description_for_callback = habit.description.clone();
             
             let on_change_callback = Rc::new(move || {
+                trends_for_callback.refresh();
+                
                 // Update just the streak display for this specific habit
-                if let Some(updated_habit) = habit_data_for_callback.borrow().habits.iter()
-                    .find(|h| h.id == habit_id_for_callback) {
+                let data = habit_data_for_callback.borrow();
+                if let Some(updated_habit) = data.get_habit_by_id(&habit_id_for_callback) {
                     title_button_for_callback.set_label(&format!("{}\n{}", 
                         habit_name_for_callback,
                         habit_description_for_callback
                     ));
                     streak_number_for_callback.set_text(&format!("{}", updated_habit.streak));
+                    if let Some(status) = get_schedule_status_text(updated_habit, &data) {
+                        week_progress_for_callback.set_text(&status);
+                    }
                     
                     // Update streak emoji based on current streak
                     streak_label_for_callback.set_text(get_streak_emoji(updated_habit.streak));
@@ -1042,7 +2140,7 @@ impl HabitApp {
                 habit.id.clone(), 
                 habit_data.clone(),
                 storage.clone(),
-                password.clone(),
+                session.clone(),
                 Some(on_change_callback)
             );
             
@@ -1050,7 +2148,49 @@ impl HabitApp {
                 let borrowed_calendar = calendar.borrow();
                 borrowed_calendar.widget().clone()
             };
-            calendar_widget.set_visible(false); // Initially hidden
+            
+            // Month, year and stats views, shown when the habit is expanded
+            let habit_views = Stack::new();
+            habit_views.set_hhomogeneous(false);
+            habit_views.set_vhomogeneous(false);
+            let month_page = GtkBox::new(Orientation::Vertical, 10);
+            month_page.append(&calendar_widget);
+            month_page.append(trends.widget());
+            habit_views.add_titled(&month_page, Some("month"), "Month");
+            
+            let views_box = GtkBox::new(Orientation::Vertical, 5);
+            let views_switcher = StackSwitcher::new();
+            views_switcher.set_stack(Some(&habit_views));
+            views_switcher.set_halign(gtk4::Align::Center);
+            views_box.append(&views_switcher);
+            views_box.append(&habit_views);
+            views_box.set_visible(false); // Initially hidden
+            
+            let calendar_weak = Rc::downgrade(&calendar);
+            let habit_views_open = habit_views.clone();
+            let views_box_open = views_box.clone();
+            let main_box_open = main_box.clone();
+            let open_day: OpenAtDay = Rc::new(move |date| {
+                if let Some(calendar) = calendar_weak.upgrade() {
+                    calendar.borrow_mut().show_month(date);
+                }
+                habit_views_open.set_visible_child_name("month");
+                views_box_open.set_visible(true);
+                main_box_open.add_css_class("expanded");
+            });
+            open_at_day.borrow_mut().push((habit.id.clone(), open_day.clone()));
+            
+            let heatmap = HabitHeatmap::new(habit_data.clone(), Some(habit.id.clone()), move |date| open_day(date));
+            habit_views.add_titled(heatmap.widget(), Some("year"), "Year");
+            let stats_view = HabitStatsView::new(habit_data.clone(), habit.id.clone());
+            habit_views.add_titled(stats_view.widget(), Some("stats"), "Stats");
+            habit_views.connect_visible_child_name_notify(move |stack| {
+                match stack.visible_child_name().as_deref() {
+                    Some("year") => heatmap.refresh(),
+                    Some("stats") => stats_view.refresh(),
+                    _ => {}
+                }
+            });
             
             // Store the calendar reference in the widget to keep it alive
             unsafe {
@@ -1058,14 +2198,14 @@ impl HabitApp {
             }
             
             main_box.append(&header_row);
-            main_box.append(&calendar_widget);
+            main_box.append(&views_box);
             
-            // Make title button clickable to toggle calendar
-            let calendar_widget_clone = calendar_widget.clone();
+            // Make title button clickable to toggle the calendar
+            let views_box_clone = views_box.clone();
             let main_box_clone = main_box.clone();
             title_button.connect_clicked(move |_| {
-                let is_visible = calendar_widget_clone.is_visible();
-                calendar_widget_clone.set_visible(!is_visible);
+                let is_visible = views_box_clone.is_visible();
+                views_box_clone.set_visible(!is_visible);
                 
                 // Toggle expanded state visual indication
                 if is_visible {
@@ -1081,7 +2221,7 @@ impl HabitApp {
             let habit_data_delete = habit_data.clone();
             let habit_list_delete = habit_list.clone();
             let storage_delete = storage.clone();
-            let password_delete = password.clone();
+            let session_delete = session.clone();
             
             delete_button.connect_clicked(move |_| {
                 println!("Delete button clicked for habit: {}", habit_name_delete);
@@ -1091,28 +2231,24 @@ impl HabitApp {
                     &habit_data_delete,
                     &habit_list_delete,
                     &storage_delete,
-                    &password_delete
+                    &session_delete
                 );
             });
             
             // Add click handler for edit button
-            let habit_id_edit = habit.id.clone();
-            let habit_name_edit = habit.name.clone();
-            let habit_description_edit = habit.description.clone();
+            let habit_edit = habit.clone();
             let habit_data_edit = habit_data.clone();
             let habit_list_edit = habit_list.clone();
             let storage_edit = storage.clone();
-            let password_edit = password.clone();
+            let session_edit = session.clone();
             
             edit_button.connect_clicked(move |_| {
                 Self::show_edit_dialog(
-                    &habit_id_edit,
-                    &habit_name_edit,
-                    &habit_description_edit,
+                    &habit_edit,
                     &habit_data_edit,
                     &habit_list_edit,
                     &storage_edit,
-                    &password_edit
+                    &session_edit
                 );
             });
             
@@ -1122,10 +2258,10 @@ impl HabitApp {
             let habit_list_clone = habit_list.clone();
             let habit_data_refresh = habit_data.clone();
             let storage_clone = storage.clone();
-            let password_clone = password.clone();
+            let session_clone = session.clone();
             
             complete_button.connect_clicked(move |_| {
-                let today = Utc::now().date_naive();
+                let today = habit_data_clone.borrow().today();
                 let is_completed = habit_data_clone.borrow().is_completed_on_date(&habit_id, today);
                 
                 if is_completed {
@@ -1135,16 +2271,46 @@ impl HabitApp {
                 }
                 
                 // Save data after change
-                if let Some(ref pass) = *password_clone.borrow() {
-                    if let Err(e) = storage_clone.save(&habit_data_clone.borrow(), pass) {
-                        eprintln!("Failed to save data: {}", e);
-                    }
+                if let Some(ref session) = *session_clone.borrow() {
+                    storage_clone.save(&habit_data_clone.borrow(), session);
                 }
                 
                 // Refresh the entire list to update streak display
-                Self::refresh_habit_list(&habit_list_clone, &habit_data_refresh, &storage_clone, &password_clone);
+                Self::refresh_habit_list(&habit_list_clone, &habit_data_refresh, &storage_clone, &session_clone);
             });
             
+            if let Some(quantity) = &habit.quantity {
+                let habit_id = habit.id.clone();
+                let habit_data_clone = habit_data.clone();
+                let habit_list_clone = habit_list.clone();
+                let storage_clone = storage.clone();
+                let session_clone = session.clone();
+                let today_value = {
+                    let data = habit_data.borrow();
+                    data.value_on_date(&habit.id, data.today())
+                };
+                
+                let popover = build_log_popover(quantity, today_value, move |value| {
+                    let today = habit_data_clone.borrow().today();
+                    habit_data_clone.borrow_mut().log_value(&habit_id, today, value);
+                    
+                    // Save data after change
+                    if let Some(ref session) = *session_clone.borrow() {
+                        storage_clone.save(&habit_data_clone.borrow(), session);
+                    }
+                    
+                    // Rebuild once the popover is gone, since the list owns it
+                    let habit_list_idle = habit_list_clone.clone();
+                    let habit_data_idle = habit_data_clone.clone();
+                    let storage_idle = storage_clone.clone();
+                    let session_idle = session_clone.clone();
+                    glib::idle_add_local_once(move || {
+                        Self::refresh_habit_list(&habit_list_idle, &habit_data_idle, &storage_idle, &session_idle);
+                    });
+                });
+                log_button.set_popover(Some(&popover));
+            }
+            
             habit_list.append(&main_box);
         }
     }
@@ -1155,9 +2321,10 @@ impl HabitApp {
     
     
     fn show_password_setup_dialog_static(
-        storage: &SecureStorage,
-        password: &Rc<RefCell<Option<String>>>,
-        habit_data: &Rc<RefCell<HabitData>>
+        storage: &StorageService,
+        session: &Rc<RefCell<Option<Session>>>,
+        habit_data: &Rc<RefCell<HabitData>>,
+        toast_overlay: &ToastOverlay
     ) -> Result<(), std::boxed::Box<dyn std::error::Error>> {
         let dialog = MessageDialog::new(
             None::<&ApplicationWindow>,
@@ -1173,28 +2340,39 @@ impl HabitApp {
         entry.set_placeholder_text(Some("Enter new password"));
         content_area.append(&entry);
 
-        let password_clone = password.clone();
+        let session_clone = session.clone();
         let storage_clone = storage.clone();
         let habit_data_clone = habit_data.clone();
+        let toast_overlay_clone = toast_overlay.clone();
         
         // Add Enter key support
         let dialog_clone = dialog.clone();
         entry.connect_activate(move |entry| {
-            let pass = entry.text().to_string();
-            if !pass.is_empty() {
+            if !entry.text().is_empty() {
                 dialog_clone.response(ResponseType::Ok);
             }
         });
         
         dialog.connect_response(move |dialog, response| {
             if response == ResponseType::Ok {
-                let pass = entry.text().to_string();
+                let pass = Zeroizing::new(entry.text().to_string());
+                entry.set_text("");
                 if !pass.is_empty() {
-                    password_clone.replace(Some(pass.clone()));
-                    
-                    if let Err(e) = storage_clone.save(&habit_data_clone.borrow(), &pass) {
-                        eprintln!("Failed to save initial data: {}", e);
-                    }
+                    let session_inner = session_clone.clone();
+                    let storage_inner = storage_clone.clone();
+                    let habit_data_inner = habit_data_clone.clone();
+                    let toast_overlay_inner = toast_overlay_clone.clone();
+                    storage_clone.create_session(pass, move |result| match result {
+                        Ok(new_session) => {
+                            storage_inner.save(&habit_data_inner.borrow(), &new_session);
+                            session_inner.replace(Some(new_session));
+                            Self::offer_recovery_key(&storage_inner, &session_inner, &habit_data_inner, &toast_overlay_inner);
+                        }
+                        Err(e) => {
+                            let error_toast = Toast::new(&format!("Failed to set up encryption: {}", e));
+                            toast_overlay_inner.add_toast(error_toast);
+                        }
+                    });
                 }
             }
             dialog.close();
@@ -1203,4 +2381,98 @@ impl HabitApp {
         dialog.show();
         Ok(())
     }
+    
+    /// Asks whether to create a recovery key right after a new password has
+    /// been set up.
+    fn offer_recovery_key(
+        storage: &StorageService,
+        session: &Rc<RefCell<Option<Session>>>,
+        habit_data: &Rc<RefCell<HabitData>>,
+        toast_overlay: &ToastOverlay
+    ) {
+        let dialog = MessageDialog::new(
+            None::<&ApplicationWindow>,
+            DialogFlags::MODAL,
+            gtk4::MessageType::Question,
+            gtk4::ButtonsType::YesNo,
+            "Create a recovery key?",
+        );
+        dialog.set_secondary_text(Some(
+            "A recovery key lets you reset your password if you forget it. Without one, a forgotten password means losing your data. You can also create one later in Settings."
+        ));
+        
+        let storage_clone = storage.clone();
+        let session_clone = session.clone();
+        let habit_data_clone = habit_data.clone();
+        let toast_overlay_clone = toast_overlay.clone();
+        dialog.connect_response(move |dialog, response| {
+            if response == ResponseType::Yes {
+                Self::create_recovery_key(&storage_clone, &session_clone, &habit_data_clone, &toast_overlay_clone);
+            }
+            dialog.close();
+        });
+        
+        dialog.show();
+    }
+    
+    /// Generates a recovery key for the current session, saves the vault with
+    /// it and shows it to the user. Any previous recovery key stops working.
+    fn create_recovery_key(
+        storage: &StorageService,
+        session: &Rc<RefCell<Option<Session>>>,
+        habit_data: &Rc<RefCell<HabitData>>,
+        toast_overlay: &ToastOverlay
+    ) {
+        let Some(current_session) = session.borrow().clone() else {
+            return;
+        };
+        
+        let storage_inner = storage.clone();
+        let session_inner = session.clone();
+        let habit_data_inner = habit_data.clone();
+        let toast_overlay_inner = toast_overlay.clone();
+        let previous_session = current_session.clone();
+        storage.regenerate_recovery_key(current_session, move |result| match result {
+            Ok((new_session, recovery_key)) => {
+                storage_inner.save_rekeyed(&habit_data_inner.borrow(), &previous_session, &new_session);
+                session_inner.replace(Some(new_session));
+                Self::show_recovery_key_dialog(&recovery_key, "");
+            }
+            Err(e) => {
+                let error_toast = Toast::new(&format!("Failed to create recovery key: {}", e));
+                toast_overlay_inner.add_toast(error_toast);
+            }
+        });
+    }
+    
+    /// Shows a new recovery key, after `intro` if there is something to say
+    /// about why it is new.
+    fn show_recovery_key_dialog(recovery_key: &str, intro: &str) {
+        let dialog = MessageDialog::new(
+            None::<&ApplicationWindow>,
+            DialogFlags::MODAL,
+            gtk4::MessageType::Info,
+            gtk4::ButtonsType::Ok,
+            "Your recovery key",
+        );
+        dialog.set_secondary_text(Some(&format!(
+            "{}Print it or write it down and keep it somewhere safe, away from this computer. It will not be shown again.",
+            intro
+        )));
+        
+        let key_label = Label::new(Some(recovery_key));
+        key_label.set_selectable(true);
+        key_label.set_wrap(true);
+        key_label.add_css_class("monospace");
+        key_label.add_css_class("title-4");
+        key_label.set_margin_top(10);
+        key_label.set_margin_bottom(10);
+        dialog.content_area().append(&key_label);
+        
+        dialog.connect_response(|dialog, _| {
+            dialog.close();
+        });
+        
+        dialog.show();
+    }
 }
\ No newline at end of file